@@ -1,12 +1,13 @@
 #![allow(unused)]
 
-use std::collections::hash_map::DefaultHasher;
 use std::fs;
-use std::hash::{Hash, Hasher};
 use std::path::Path;
 
 use serde::{Deserialize, Serialize};
 
+use crate::git::hash::HashAlgorithm;
+use crate::git::object_store::{hash_object, ObjectKind};
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Blob {
     content: Vec<u8>,
@@ -14,17 +15,18 @@ pub struct Blob {
 }
 
 impl Blob {
-    pub fn new(content: Vec<u8>) -> Self {
-        let mut hasher = DefaultHasher::new();
-        content.hash(&mut hasher);
-        let hash = format!("{:x}", hasher.finish());
+    pub fn new(content: Vec<u8>, algorithm: HashAlgorithm) -> Self {
+        let hash = hash_object(algorithm, ObjectKind::Blob, &content);
 
         Blob { content, hash }
     }
 
-    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, std::io::Error> {
+    pub fn from_file<P: AsRef<Path>>(
+        path: P,
+        algorithm: HashAlgorithm,
+    ) -> Result<Self, std::io::Error> {
         let content = fs::read(path)?;
-        Ok(Blob::new(content))
+        Ok(Blob::new(content, algorithm))
     }
 
     pub fn get_hash(&self) -> &str {
@@ -4,7 +4,9 @@
 // use std::path::PathBuf;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Clone, Debug)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Branch {
     name: String,
     head_commit_id: Option<String>,
@@ -25,6 +27,15 @@ impl Branch {
         }
     }
 
+    /// A branch that has pointed at `commits`, oldest first.
+    pub fn with_commits(name: String, commits: Vec<String>) -> Self {
+        Branch {
+            head_commit_id: commits.last().cloned(),
+            commits,
+            ..Branch::new(name)
+        }
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -41,4 +52,4 @@ impl Branch {
     pub fn commits(&self) -> &Vec<String> {
         &self.commits
     }
-}
\ No newline at end of file
+}
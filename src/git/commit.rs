@@ -1,54 +1,199 @@
 #![allow(unused)]
 
-use std::collections::HashMap;
-use std::hash::Hasher;
-use std::path::PathBuf;
-use std::time::{SystemTime, UNIX_EPOCH};
-use crate::git::blob::Blob;
+use std::fmt;
+use std::io::{Error, ErrorKind, Result};
 
-#[derive(Debug)]
+use serde::{Deserialize, Serialize};
+
+use crate::git::config::parse_offset;
+use crate::git::hash::HashAlgorithm;
+use crate::git::object_store::{hash_object, ObjectKind, ObjectStore};
+
+/// Who made a commit and when, in the time zone they made it in.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Signature {
+    pub name: String,
+    pub email: String,
+    pub timestamp: u64,
+    /// Minutes east of UTC
+    pub offset: i32,
+}
+
+impl Signature {
+    pub fn new(name: String, email: String, timestamp: u64, offset: i32) -> Self {
+        Signature {
+            name,
+            email,
+            timestamp,
+            offset,
+        }
+    }
+
+    /// Parses `Name <email> <timestamp> <+hhmm>` as found in commit headers.
+    pub fn parse(text: &str) -> Option<Self> {
+        // The identity may contain spaces, the timestamp and zone never do
+        let mut parts = text.rsplitn(3, ' ');
+        let offset = parse_offset(parts.next()?)?;
+        let timestamp = parts.next()?.parse().ok()?;
+        let identity = parts.next()?;
+
+        let (name, email) = match identity.rsplit_once('<') {
+            Some((name, email)) => (name.trim_end(), email.strip_suffix('>')?),
+            None => (identity, ""),
+        };
+        Some(Signature::new(
+            name.to_string(),
+            email.to_string(),
+            timestamp,
+            offset,
+        ))
+    }
+
+    /// `Name <email>`, as `log` shows it.
+    pub fn identity(&self) -> String {
+        format!("{} <{}>", self.name, self.email)
+    }
+
+    /// The zone as `+hhmm` or `-hhmm`.
+    pub fn zone(&self) -> String {
+        let sign = if self.offset < 0 { '-' } else { '+' };
+        let minutes = self.offset.abs();
+        format!("{}{:02}{:02}", sign, minutes / 60, minutes % 60)
+    }
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.identity(), self.timestamp, self.zone())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Commit {
     pub id: String,
-    pub parent: Option<String>,
-    pub tree: HashMap<PathBuf, Vec<u8>>,
+    /// Empty for a root commit, two or more for a merge.
+    pub parents: Vec<String>,
+    pub tree: String,
     pub message: String,
-    pub timestamp: u64,
-    pub author: String,
+    pub author: Signature,
+    pub committer: Signature,
 }
 
 impl Commit {
     pub fn new(
-        parent: Option<String>,
-        tree: HashMap<PathBuf, Vec<u8>>,
+        parents: Vec<String>,
+        tree: String,
         message: String,
-        author: String
+        author: Signature,
+        committer: Signature,
+        algorithm: HashAlgorithm,
     ) -> Self {
-        // Generate a simple hash for the commit
-        let mut hasher = std::collections::hash_map::DefaultHasher::new();
-        std::hash::Hash::hash(&message, &mut hasher);
-        std::hash::Hash::hash(&SystemTime::now(), &mut hasher);
-        let id = format!("{:x}", hasher.finish());
-
-        let timestamp = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
-
-        Commit {
-            id,
-            parent,
+        let mut commit = Commit {
+            id: String::new(),
+            parents,
             tree,
             message,
-            timestamp,
             author,
+            committer,
+        };
+
+        // The id is the hash of the serialized commit, so it is only known once
+        // every other field is filled in
+        commit.id = hash_object(algorithm, ObjectKind::Commit, &commit.serialize());
+        commit
+    }
+
+    /// Serializes every field but the id in Git's commit format:
+    ///
+    /// ```text
+    /// tree <id>
+    /// parent <id>
+    /// author <name> <<email>> <timestamp> <zone>
+    /// committer <name> <<email>> <timestamp> <zone>
+    ///
+    /// <message>
+    /// ```
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut body = format!("tree {}\n", self.tree);
+        for parent in self.parents() {
+            body.push_str(&format!("parent {}\n", parent));
+        }
+        body.push_str(&format!("author {}\n", self.author));
+        body.push_str(&format!("committer {}\n", self.committer));
+        body.push('\n');
+        body.push_str(&self.message);
+        if !self.message.ends_with('\n') {
+            body.push('\n');
         }
+        body.into_bytes()
+    }
+
+    pub fn deserialize(id: &str, data: &[u8]) -> Result<Self> {
+        let invalid = |what: &str| {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Malformed commit {}: {}", id, what),
+            )
+        };
+
+        let text = std::str::from_utf8(data).map_err(|_| invalid("not valid UTF-8"))?;
+        let (headers, message) = text.split_once("\n\n").unwrap_or((text, ""));
+
+        let mut tree = None;
+        let mut parents = Vec::new();
+        let mut author = None;
+        let mut committer = None;
+        for line in headers.lines() {
+            let (key, value) = line.split_once(' ').ok_or_else(|| invalid(line))?;
+            match key {
+                "tree" => tree = Some(value.to_string()),
+                "parent" => parents.push(value.to_string()),
+                "author" => author = Some(Signature::parse(value).ok_or_else(|| invalid(line))?),
+                "committer" => {
+                    committer = Some(Signature::parse(value).ok_or_else(|| invalid(line))?)
+                }
+                _ => {}
+            }
+        }
+
+        let tree = tree.ok_or_else(|| invalid("missing tree"))?;
+        let author = author.ok_or_else(|| invalid("missing author"))?;
+        let committer = committer.unwrap_or_else(|| author.clone());
+
+        Ok(Commit {
+            id: id.to_string(),
+            parents,
+            tree,
+            message: message.strip_suffix('\n').unwrap_or(message).to_string(),
+            author,
+            committer,
+        })
+    }
+
+    pub fn read(store: &ObjectStore, id: &str) -> Result<Self> {
+        let data = store.read_kind(id, ObjectKind::Commit)?;
+        Self::deserialize(id, &data)
+    }
+
+    /// The ids of the commits this one was made on top of, in order.
+    pub fn parents(&self) -> &[String] {
+        &self.parents
+    }
+
+    /// When the commit was made, which orders history.
+    pub fn timestamp(&self) -> u64 {
+        self.committer.timestamp
+    }
+
+    pub fn is_merge(&self) -> bool {
+        self.parents.len() > 1
     }
 
     pub fn get_id(&self) -> &str {
         &self.id
     }
 
-    pub fn get_tree(&self) -> &HashMap<PathBuf, Vec<u8>> {
+    pub fn get_tree(&self) -> &str {
         &self.tree
     }
 }
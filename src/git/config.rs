@@ -0,0 +1,754 @@
+#![allow(unused)]
+
+use std::env;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::git::commit::Signature;
+use crate::git::log::{local_utc_offset, parse_date};
+
+/// Where a setting comes from, from the most general to the most specific.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ConfigScope {
+    System,
+    Global,
+    Local,
+    /// `-c name=value` on the command line
+    Command,
+}
+
+impl ConfigScope {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConfigScope::System => "system",
+            ConfigScope::Global => "global",
+            ConfigScope::Local => "local",
+            ConfigScope::Command => "command",
+        }
+    }
+}
+
+/// A `section.name` or `section.subsection.name` setting and its value.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigEntry {
+    pub key: String,
+    pub value: String,
+    pub scope: ConfigScope,
+}
+
+/// Settings read from Git-style INI files. Later entries override earlier
+/// ones, so scopes are loaded from the most general to the most specific.
+#[derive(Clone, Debug, Default)]
+pub struct Config {
+    entries: Vec<ConfigEntry>,
+}
+
+/// Whose identity a commit records.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IdentityRole {
+    Author,
+    Committer,
+}
+
+impl IdentityRole {
+    fn env_prefix(&self) -> &'static str {
+        match self {
+            IdentityRole::Author => "GIT_AUTHOR",
+            IdentityRole::Committer => "GIT_COMMITTER",
+        }
+    }
+}
+
+/// Holds the `-c` settings of this invocation, as Git passes them on.
+const PARAMETERS_VARIABLE: &str = "GIT_CONFIG_PARAMETERS";
+
+impl Config {
+    /// Reads every scope: the system and global files, the repository's own
+    /// `config` in `git_dir` when there is a repository, and the `-c`
+    /// settings of the command line.
+    pub fn load(git_dir: Option<&Path>) -> Result<Self> {
+        let mut config = Config::default();
+        if env::var_os("GIT_CONFIG_NOSYSTEM").is_none() {
+            config.read_file(&Self::system_path(), ConfigScope::System)?;
+        }
+        if let Some(global) = Self::global_path() {
+            config.read_file(&global, ConfigScope::Global)?;
+        }
+        if let Some(git_dir) = git_dir {
+            config.read_file(&git_dir.join("config"), ConfigScope::Local)?;
+        }
+
+        if let Ok(parameters) = env::var(PARAMETERS_VARIABLE) {
+            for parameter in split_parameters(&parameters)? {
+                config.entries.push(parse_parameter(&parameter)?);
+            }
+        }
+        Ok(config)
+    }
+
+    /// `$GIT_CONFIG_SYSTEM`, or `/etc/git-scmconfig`.
+    pub fn system_path() -> PathBuf {
+        env::var_os("GIT_CONFIG_SYSTEM")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("/etc/git-scmconfig"))
+    }
+
+    /// `$GIT_CONFIG_GLOBAL`, or `.git-scmconfig` in the home directory.
+    pub fn global_path() -> Option<PathBuf> {
+        if let Some(path) = env::var_os("GIT_CONFIG_GLOBAL") {
+            return Some(PathBuf::from(path));
+        }
+        env::var_os("HOME").map(|home| PathBuf::from(home).join(".git-scmconfig"))
+    }
+
+    /// Makes `name=value` settings from the command line visible to every
+    /// later `load` in this process.
+    pub fn add_parameters(parameters: &[String]) -> Result<()> {
+        let mut quoted: Vec<String> = env::var(PARAMETERS_VARIABLE).into_iter().collect();
+        for parameter in parameters {
+            // Check the setting now rather than on every load
+            parse_parameter(parameter)?;
+            quoted.push(format!("'{}'", parameter.replace('\'', "'\\''")));
+        }
+        env::set_var(PARAMETERS_VARIABLE, quoted.join(" "));
+        Ok(())
+    }
+
+    /// Adds the settings of `path`, if it exists.
+    pub fn read_file(&mut self, path: &Path, scope: ConfigScope) -> Result<()> {
+        if !path.is_file() {
+            return Ok(());
+        }
+        let text = fs::read_to_string(path)?;
+        let document = parse_document(&text)
+            .map_err(|e| Error::new(e.kind(), format!("{}: {}", path.display(), e)))?;
+        self.entries
+            .extend(document.settings.into_iter().map(|setting| ConfigEntry {
+                key: setting.key,
+                value: setting.value,
+                scope,
+            }));
+        Ok(())
+    }
+
+    /// The last value set for `key`.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        let key = normalize_key(key)?;
+        self.entries
+            .iter()
+            .rev()
+            .find(|entry| entry.key == key)
+            .map(|entry| entry.value.as_str())
+    }
+
+    /// Reads `key` as a boolean the way Git does: `true`, `yes`, `on` and
+    /// `1` against `false`, `no`, `off`, `0` and the empty string.
+    pub fn get_bool(&self, key: &str) -> Result<Option<bool>> {
+        self.get(key)
+            .map(|value| parse_bool(key, value))
+            .transpose()
+    }
+
+    pub fn entries(&self) -> &[ConfigEntry] {
+        &self.entries
+    }
+
+    /// The author or committer of a new commit.
+    ///
+    /// `GIT_AUTHOR_NAME`, `GIT_AUTHOR_EMAIL` and `GIT_AUTHOR_DATE` (or their
+    /// `GIT_COMMITTER_` counterparts) take precedence over `user.name` and
+    /// `user.email`. The date defaults to now in the local time zone.
+    pub fn identity(&self, role: IdentityRole) -> Result<Signature> {
+        let prefix = role.env_prefix();
+        let setting = |variable: &str, key: &str| {
+            env::var(format!("{}_{}", prefix, variable))
+                .ok()
+                .or_else(|| self.get(key).map(str::to_string))
+                .filter(|value| !value.trim().is_empty())
+        };
+
+        let name = setting("NAME", "user.name");
+        let email = setting("EMAIL", "user.email").or_else(|| env::var("EMAIL").ok());
+        let (Some(name), Some(email)) = (name, email) else {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                "Author identity unknown\n\nRun\n\n  git config set --global user.email \"you@example.com\"\n  git config set --global user.name \"Your Name\"\n\nto set your account's default identity.",
+            ));
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(Error::other)?
+            .as_secs();
+        let (timestamp, offset) = match env::var(format!("{}_DATE", prefix)) {
+            Ok(date) => parse_signature_date(&date, now)?,
+            Err(_) => (now, local_utc_offset(now)),
+        };
+
+        Ok(Signature::new(
+            name.trim().to_string(),
+            email.trim().to_string(),
+            timestamp,
+            offset,
+        ))
+    }
+}
+
+/// Parses a date given in the environment: Git's internal `<timestamp>
+/// <+hhmm>` form or anything `parse_date` accepts, optionally followed by a
+/// zone offset.
+fn parse_signature_date(text: &str, now: u64) -> Result<(u64, i32)> {
+    let text = text.trim();
+    if let Some((date, zone)) = text.rsplit_once(' ') {
+        if let Some(offset) = parse_offset(zone) {
+            // The date is local to the zone, the timestamp is not
+            let date = date.trim();
+            let timestamp = parse_date(date, now)?;
+            let is_absolute = date
+                .strip_prefix('@')
+                .unwrap_or(date)
+                .parse::<u64>()
+                .is_ok();
+            let timestamp = if is_absolute {
+                timestamp
+            } else {
+                (timestamp as i64 - offset as i64 * 60) as u64
+            };
+            return Ok((timestamp, offset));
+        }
+    }
+    Ok((parse_date(text, now)?, 0))
+}
+
+/// Minutes east of UTC for `+hhmm` or `-hhmm`.
+pub fn parse_offset(zone: &str) -> Option<i32> {
+    let (sign, digits) = match zone.as_bytes().first()? {
+        b'+' => (1, &zone[1..]),
+        b'-' => (-1, &zone[1..]),
+        _ => return None,
+    };
+    if digits.len() != 4 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let hours: i32 = digits[..2].parse().ok()?;
+    let minutes: i32 = digits[2..].parse().ok()?;
+    Some(sign * (hours * 60 + minutes))
+}
+
+/// Reads `value` as a boolean, naming `key` if it is not one.
+pub fn parse_bool(key: &str, value: &str) -> Result<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" | "" => Ok(false),
+        _ => Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("Bad boolean config value '{}' for '{}'", value, key),
+        )),
+    }
+}
+
+/// Sets `key` to `value` in the file at `path`, creating the file if
+/// needed. The last existing occurrence is replaced in place; otherwise the
+/// setting goes at the end of its section, or of the file in a new section.
+pub fn set_value(path: &Path, key: &str, value: &str) -> Result<()> {
+    let (section, subsection, name) = split_key(key).ok_or_else(|| invalid_key(key))?;
+    let key = normalize_key(key).ok_or_else(|| invalid_key(key))?;
+    let text = if path.is_file() {
+        fs::read_to_string(path)?
+    } else {
+        String::new()
+    };
+    let document = parse_document(&text)?;
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+    let line = format!("\t{} = {}", name, quote_value(value));
+
+    let section_id = &key[..key.len() - name.len() - 1];
+    if let Some(setting) = document.settings.iter().rev().find(|s| s.key == key) {
+        let mut replacement = vec![line];
+        if setting.after_header {
+            replacement.insert(0, header_only(&lines[setting.lines.start]));
+        }
+        lines.splice(setting.lines.clone(), replacement);
+    } else if let Some(header) = document.headers.iter().rposition(|h| h.id == section_id) {
+        let end = document
+            .settings
+            .iter()
+            .filter(|setting| setting.header == header)
+            .map(|setting| setting.lines.end)
+            .max()
+            .unwrap_or(document.headers[header].line + 1);
+        lines.insert(end, line);
+    } else {
+        lines.push(match subsection {
+            Some(subsection) => format!(
+                "[{} \"{}\"]",
+                section,
+                subsection.replace('\\', "\\\\").replace('"', "\\\"")
+            ),
+            None => format!("[{}]", section),
+        });
+        lines.push(line);
+    }
+
+    write_lines(path, &lines)
+}
+
+/// Removes every occurrence of `key` from the file at `path` and returns
+/// how many there were.
+pub fn unset_value(path: &Path, key: &str) -> Result<usize> {
+    let key = normalize_key(key).ok_or_else(|| invalid_key(key))?;
+    if !path.is_file() {
+        return Ok(0);
+    }
+    let text = fs::read_to_string(path)?;
+    let document = parse_document(&text)?;
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+
+    let matching: Vec<&Setting> = document.settings.iter().filter(|s| s.key == key).collect();
+    for setting in matching.iter().rev() {
+        let replacement = if setting.after_header {
+            vec![header_only(&lines[setting.lines.start])]
+        } else {
+            Vec::new()
+        };
+        lines.splice(setting.lines.clone(), replacement);
+    }
+
+    if !matching.is_empty() {
+        write_lines(path, &lines)?;
+    }
+    Ok(matching.len())
+}
+
+/// Removes every `[section]` header named `section`, such as
+/// `remote.origin`, together with the lines up to the next header, and
+/// returns how many sections there were.
+pub fn remove_section(path: &Path, section: &str) -> Result<usize> {
+    if !path.is_file() {
+        return Ok(0);
+    }
+    let text = fs::read_to_string(path)?;
+    let document = parse_document(&text)?;
+    let mut lines: Vec<String> = text.lines().map(str::to_string).collect();
+
+    let mut removed = 0;
+    for (position, header) in document.headers.iter().enumerate().rev() {
+        if header.id != section {
+            continue;
+        }
+        let end = document
+            .headers
+            .get(position + 1)
+            .map_or(lines.len(), |next| next.line);
+        lines.drain(header.line..end);
+        removed += 1;
+    }
+
+    if removed > 0 {
+        write_lines(path, &lines)?;
+    }
+    Ok(removed)
+}
+
+fn write_lines(path: &Path, lines: &[String]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut text = lines.join("\n");
+    if !text.is_empty() {
+        text.push('\n');
+    }
+    fs::write(path, text)
+}
+
+/// The `[section]` part of a line that also holds a setting.
+fn header_only(line: &str) -> String {
+    let end = line.find(']').map_or(line.len(), |end| end + 1);
+    line[..end].to_string()
+}
+
+fn invalid_key(key: &str) -> Error {
+    Error::new(ErrorKind::InvalidInput, format!("Invalid key: {}", key))
+}
+
+/// Quotes a value when writing it would otherwise lose whitespace at its
+/// ends or start a comment, and escapes what the parser unescapes.
+fn quote_value(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    let needs_quotes = value.starts_with(char::is_whitespace)
+        || value.ends_with(char::is_whitespace)
+        || value.contains(['#', ';']);
+    if needs_quotes {
+        format!("\"{}\"", escaped)
+    } else {
+        escaped
+    }
+}
+
+/// Splits the shell-quoted list `add_parameters` stores.
+fn split_parameters(text: &str) -> Result<Vec<String>> {
+    let invalid = || {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Bogus format in {}", PARAMETERS_VARIABLE),
+        )
+    };
+
+    let mut parameters = Vec::new();
+    let mut current: Option<String> = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                let parameter = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next().ok_or_else(invalid)? {
+                        '\'' => break,
+                        c => parameter.push(c),
+                    }
+                }
+            }
+            '\\' => current
+                .get_or_insert_with(String::new)
+                .push(chars.next().ok_or_else(invalid)?),
+            c if c.is_whitespace() => parameters.extend(current.take()),
+            _ => return Err(invalid()),
+        }
+    }
+    parameters.extend(current);
+    Ok(parameters)
+}
+
+/// `name=value`, or a bare `name` meaning `true`.
+fn parse_parameter(parameter: &str) -> Result<ConfigEntry> {
+    let (key, value) = parameter.split_once('=').unwrap_or((parameter, "true"));
+    Ok(ConfigEntry {
+        key: normalize_key(key).ok_or_else(|| invalid_key(key))?,
+        value: value.to_string(),
+        scope: ConfigScope::Command,
+    })
+}
+
+/// Splits a key into its lowercased section, its subsection and its name as
+/// written.
+fn split_key(key: &str) -> Option<(String, Option<&str>, &str)> {
+    let (section, rest) = key.split_once('.')?;
+    let (subsection, name) = match rest.rsplit_once('.') {
+        Some((subsection, name)) => (Some(subsection), name),
+        None => (None, rest),
+    };
+    let valid = valid_name(section)
+        && valid_name(name)
+        && name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && !subsection.is_some_and(|s| s.contains('\n'));
+    valid.then(|| (section.to_ascii_lowercase(), subsection, name))
+}
+
+/// Lowercases the section and name of a key, keeping the subsection as is.
+fn normalize_key(key: &str) -> Option<String> {
+    let (section, subsection, name) = split_key(key)?;
+    let name = name.to_ascii_lowercase();
+    Some(match subsection {
+        Some(subsection) => format!("{}.{}.{}", section, subsection, name),
+        None => format!("{}.{}", section, name),
+    })
+}
+
+/// A section header and the line it is on.
+#[derive(Debug)]
+struct Header {
+    /// `section` or `section.subsection`
+    id: String,
+    line: usize,
+}
+
+/// A setting and where the file holds it.
+#[derive(Debug)]
+struct Setting {
+    key: String,
+    value: String,
+    /// The lines the setting spans, more than one when it is continued
+    lines: Range<usize>,
+    /// Index of the header of its section
+    header: usize,
+    /// Whether it follows its header on the same line
+    after_header: bool,
+}
+
+#[derive(Debug, Default)]
+struct Document {
+    headers: Vec<Header>,
+    settings: Vec<Setting>,
+}
+
+/// Parses Git's INI dialect: `[section]` and `[section "subsection"]`
+/// headers, `name = value` lines, `#` and `;` comments, quoted values,
+/// backslash escapes and continuation lines. A name without a value is
+/// `true`.
+fn parse_document(text: &str) -> Result<Document> {
+    let mut document = Document::default();
+    let mut lines = text.lines().enumerate().peekable();
+
+    while let Some((number, line)) = lines.next() {
+        let invalid = || {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!("Bad config line {}", number + 1),
+            )
+        };
+        let mut setting = line.trim();
+        if setting.is_empty() || setting.starts_with(['#', ';']) {
+            continue;
+        }
+
+        let mut after_header = false;
+        if let Some(header) = setting.strip_prefix('[') {
+            let (header, rest) = header.split_once(']').ok_or_else(invalid)?;
+            document.headers.push(Header {
+                id: parse_section(header).ok_or_else(invalid)?,
+                line: number,
+            });
+
+            // A setting may follow the header on the same line
+            setting = rest.trim();
+            if setting.is_empty() || setting.starts_with(['#', ';']) {
+                continue;
+            }
+            after_header = true;
+        }
+
+        let header = document.headers.len().checked_sub(1).ok_or_else(invalid)?;
+        let (key, value) =
+            parse_setting(&document.headers[header].id, setting, &mut lines).ok_or_else(invalid)?;
+        let end = lines.peek().map_or(text.lines().count(), |(next, _)| *next);
+        document.settings.push(Setting {
+            key,
+            value,
+            lines: number..end,
+            header,
+            after_header,
+        });
+    }
+    Ok(document)
+}
+
+/// `core`, `remote "origin"` or the older `branch.main` form.
+fn parse_section(header: &str) -> Option<String> {
+    let header = header.trim();
+    match header.split_once(|c: char| c.is_whitespace()) {
+        Some((name, subsection)) => {
+            let subsection = subsection.trim().strip_prefix('"')?.strip_suffix('"')?;
+            let subsection = subsection.replace("\\\"", "\"").replace("\\\\", "\\");
+            valid_name(name).then(|| format!("{}.{}", name.to_ascii_lowercase(), subsection))
+        }
+        None => {
+            let (name, subsection) = header.split_once('.').unwrap_or((header, ""));
+            if !valid_name(name) {
+                return None;
+            }
+            let name = name.to_ascii_lowercase();
+            Some(if subsection.is_empty() {
+                name
+            } else {
+                format!("{}.{}", name, subsection.to_ascii_lowercase())
+            })
+        }
+    }
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+fn parse_setting<'a>(
+    section: &str,
+    line: &str,
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+) -> Option<(String, String)> {
+    let (name, raw) = match line.split_once('=') {
+        Some((name, raw)) => (name.trim(), Some(raw)),
+        None => (line.trim(), None),
+    };
+    if !valid_name(name) || !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return None;
+    }
+
+    let value = match raw {
+        Some(raw) => parse_value(raw, lines)?,
+        None => "true".to_string(),
+    };
+    Some((format!("{}.{}", section, name.to_ascii_lowercase()), value))
+}
+
+/// Unquotes a value, dropping trailing comments and surrounding whitespace
+/// and joining lines that end with a backslash.
+fn parse_value<'a>(
+    raw: &str,
+    lines: &mut impl Iterator<Item = (usize, &'a str)>,
+) -> Option<String> {
+    let mut value = String::new();
+    let mut quoted = false;
+    // Unquoted whitespace is only kept between words
+    let mut pending_space = String::new();
+    let mut line = raw.trim_start().to_string();
+
+    loop {
+        let mut chars = line.chars();
+        let mut continued = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => quoted = !quoted,
+                '\\' => match chars.next() {
+                    None => continued = true,
+                    Some(escaped) => {
+                        value.push_str(&pending_space);
+                        pending_space.clear();
+                        value.push(match escaped {
+                            'n' => '\n',
+                            't' => '\t',
+                            'b' => '\u{8}',
+                            '\\' | '"' => escaped,
+                            _ => return None,
+                        });
+                    }
+                },
+                '#' | ';' if !quoted => break,
+                c if c.is_whitespace() && !quoted => {
+                    if !value.is_empty() {
+                        pending_space.push(c);
+                    }
+                }
+                c => {
+                    value.push_str(&pending_space);
+                    pending_space.clear();
+                    value.push(c);
+                }
+            }
+        }
+
+        if !continued {
+            break;
+        }
+        line = lines.next()?.1.to_string();
+    }
+
+    if quoted {
+        return None;
+    }
+    Some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every setting of `text` as `(key, value)`.
+    fn settings(text: &str) -> Vec<(String, String)> {
+        parse_document(text)
+            .unwrap()
+            .settings
+            .into_iter()
+            .map(|setting| (setting.key, setting.value))
+            .collect()
+    }
+
+    fn pair(key: &str, value: &str) -> (String, String) {
+        (key.to_string(), value.to_string())
+    }
+
+    #[test]
+    fn parses_sections_and_subsections() {
+        let text = "[Core]\n\tBare = false\n[remote \"Origin\"]\n\turl = /tmp/x\n[branch.Main]\n\tremote = origin\n";
+        assert_eq!(
+            settings(text),
+            [
+                pair("core.bare", "false"),
+                pair("remote.Origin.url", "/tmp/x"),
+                pair("branch.main.remote", "origin"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_values() {
+        let text = concat!(
+            "[user]\n",
+            "  name = \"Ada  Lovelace\" # a comment\n",
+            "  email = ada@example.com ; another\n",
+            "[alias]\n",
+            "  lg = log \\\n    --oneline\n",
+            "  tab = \"a\\tb\"\n",
+            "  flag\n",
+            "; [ignored]\n",
+        );
+        assert_eq!(
+            settings(text),
+            [
+                pair("user.name", "Ada  Lovelace"),
+                pair("user.email", "ada@example.com"),
+                pair("alias.lg", "log     --oneline"),
+                pair("alias.tab", "a\tb"),
+                pair("alias.flag", "true"),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_a_setting_on_its_header_line() {
+        assert_eq!(
+            settings("[core] bare = true\n"),
+            [pair("core.bare", "true")]
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_lines() {
+        for text in [
+            "name = value\n",
+            "[core\n",
+            "[core]\n1name = x\n",
+            "[core]\nname = \"open\n",
+            "[core]\nname = bad\\q\n",
+        ] {
+            assert!(parse_document(text).is_err(), "{:?} should not parse", text);
+        }
+    }
+
+    #[test]
+    fn normalizes_keys() {
+        assert_eq!(
+            normalize_key("Remote.Origin.URL").as_deref(),
+            Some("remote.Origin.url")
+        );
+        assert_eq!(normalize_key("core").as_deref(), None);
+        assert_eq!(normalize_key("core.1st").as_deref(), None);
+    }
+
+    #[test]
+    fn splits_quoted_parameters() {
+        assert_eq!(
+            split_parameters("'user.name=Ada L' 'core.bare'").unwrap(),
+            ["user.name=Ada L", "core.bare"]
+        );
+        assert!(split_parameters("'unterminated").is_err());
+    }
+
+    #[test]
+    fn parses_booleans() {
+        for value in ["true", "Yes", "on", "1"] {
+            assert!(parse_bool("core.bare", value).unwrap(), "{:?}", value);
+        }
+        // An empty value, as in `bare =`, is false
+        for value in ["false", "no", "OFF", "0", ""] {
+            assert!(!parse_bool("core.bare", value).unwrap(), "{:?}", value);
+        }
+        assert!(parse_bool("core.bare", "maybe").is_err());
+    }
+}
@@ -0,0 +1,258 @@
+#![allow(unused)]
+
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result};
+
+/// Bytes hashed together to find regions the target shares with the source.
+const BLOCK: usize = 16;
+/// Source positions remembered per block, bounding the work on repetitive data.
+const MAX_CANDIDATES: usize = 64;
+/// Longest literal run a single insert instruction carries.
+const MAX_INSERT: usize = 0x7f;
+/// Longest region a single copy instruction carries.
+const MAX_COPY: usize = 0xff_ffff;
+
+/// Encodes `target` as a Git delta against `source`: the two sizes followed
+/// by instructions that either copy a range of `source` or insert literal
+/// bytes.
+pub fn compute(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    write_size(&mut delta, source.len());
+    write_size(&mut delta, target.len());
+
+    // Where each aligned block of the source starts
+    let mut blocks: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for start in (0..source.len().saturating_sub(BLOCK - 1)).step_by(BLOCK) {
+        let positions = blocks.entry(&source[start..start + BLOCK]).or_default();
+        if positions.len() < MAX_CANDIDATES {
+            positions.push(start);
+        }
+    }
+
+    let mut pending = Vec::new();
+    let mut at = 0;
+    while at < target.len() {
+        let best = target
+            .get(at..at + BLOCK)
+            .and_then(|block| blocks.get(block))
+            .into_iter()
+            .flatten()
+            .map(|&start| {
+                let length = source[start..]
+                    .iter()
+                    .zip(&target[at..])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                (start, length)
+            })
+            .max_by_key(|&(_, length)| length);
+
+        match best {
+            Some((start, length)) => {
+                flush_insert(&mut delta, &mut pending);
+                let mut copied = 0;
+                while copied < length {
+                    let size = (length - copied).min(MAX_COPY);
+                    write_copy(&mut delta, start + copied, size);
+                    copied += size;
+                }
+                at += length;
+            }
+            None => {
+                pending.push(target[at]);
+                if pending.len() == MAX_INSERT {
+                    flush_insert(&mut delta, &mut pending);
+                }
+                at += 1;
+            }
+        }
+    }
+    flush_insert(&mut delta, &mut pending);
+    delta
+}
+
+/// Rebuilds the target a delta was computed for from its `source`.
+pub fn apply(source: &[u8], delta: &[u8]) -> Result<Vec<u8>> {
+    let malformed =
+        |what: &str| Error::new(ErrorKind::InvalidData, format!("Malformed delta: {}", what));
+
+    let mut at = 0;
+    let source_len = read_size(delta, &mut at)?;
+    let target_len = read_size(delta, &mut at)?;
+    if source_len != source.len() {
+        return Err(malformed("source size mismatch"));
+    }
+
+    // The target size comes from the delta, so the target grows as it is
+    // built rather than being allocated up front
+    let mut target = Vec::new();
+    while at < delta.len() {
+        let instruction = delta[at];
+        at += 1;
+        if instruction & 0x80 != 0 {
+            // Which offset and size bytes follow is given by the low seven bits
+            let mut offset = 0usize;
+            let mut size = 0usize;
+            for bit in 0..7 {
+                if instruction & (1 << bit) == 0 {
+                    continue;
+                }
+                let byte = *delta.get(at).ok_or_else(|| malformed("truncated copy"))? as usize;
+                at += 1;
+                if bit < 4 {
+                    offset |= byte << (8 * bit);
+                } else {
+                    size |= byte << (8 * (bit - 4));
+                }
+            }
+            if size == 0 {
+                size = 0x10000;
+            }
+            let range = source
+                .get(offset..offset + size)
+                .ok_or_else(|| malformed("copy outside source"))?;
+            target.extend_from_slice(range);
+        } else if instruction != 0 {
+            let size = instruction as usize;
+            let bytes = delta
+                .get(at..at + size)
+                .ok_or_else(|| malformed("truncated insert"))?;
+            target.extend_from_slice(bytes);
+            at += size;
+        } else {
+            return Err(malformed("reserved instruction"));
+        }
+        if target.len() > target_len {
+            return Err(malformed("target size mismatch"));
+        }
+    }
+
+    if target.len() != target_len {
+        return Err(malformed("target size mismatch"));
+    }
+    Ok(target)
+}
+
+/// Seven bits at a time, least significant first, the high bit marking that
+/// more follow.
+fn write_size(out: &mut Vec<u8>, mut size: usize) {
+    loop {
+        let byte = (size & 0x7f) as u8;
+        size >>= 7;
+        if size == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn read_size(data: &[u8], at: &mut usize) -> Result<usize> {
+    let malformed =
+        |what: &str| Error::new(ErrorKind::InvalidData, format!("Malformed delta: {}", what));
+    let mut size = 0;
+    let mut shift = 0;
+    loop {
+        let byte = *data.get(*at).ok_or_else(|| malformed("truncated size"))?;
+        *at += 1;
+        if shift >= usize::BITS {
+            return Err(malformed("size too large"));
+        }
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Ok(size);
+        }
+    }
+}
+
+fn write_copy(out: &mut Vec<u8>, offset: usize, size: usize) {
+    let mut instruction = 0x80u8;
+    let mut arguments = Vec::new();
+    for (bit, byte) in (0..4).map(|i| (i, (offset >> (8 * i)) as u8)) {
+        if byte != 0 {
+            instruction |= 1 << bit;
+            arguments.push(byte);
+        }
+    }
+    for (bit, byte) in (0..3).map(|i| (i + 4, (size >> (8 * i)) as u8)) {
+        if byte != 0 {
+            instruction |= 1 << bit;
+            arguments.push(byte);
+        }
+    }
+    out.push(instruction);
+    out.extend(arguments);
+}
+
+fn flush_insert(out: &mut Vec<u8>, pending: &mut Vec<u8>) {
+    if !pending.is_empty() {
+        out.push(pending.len() as u8);
+        out.append(pending);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(range: std::ops::Range<usize>) -> Vec<u8> {
+        range
+            .map(|n| format!("line {} of a file long enough to share blocks\n", n))
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    #[test]
+    fn applies_what_it_computes() {
+        let source = lines(0..200);
+        let mut target = lines(0..80);
+        target.extend_from_slice(b"an inserted line\n");
+        target.extend(lines(120..200));
+        target.extend(vec![b'x'; 300]);
+
+        let delta = compute(&source, &target);
+        assert!(delta.len() < target.len() / 4);
+        assert_eq!(apply(&source, &delta).unwrap(), target);
+
+        for (source, target) in [(&b""[..], &b"new"[..]), (b"old", b""), (b"", b"")] {
+            assert_eq!(apply(source, &compute(source, target)).unwrap(), target);
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_deltas() {
+        let source = lines(0..50);
+        let mut target = lines(10..50);
+        target.extend_from_slice(b"tail\n");
+        let delta = compute(&source, &target);
+
+        for end in 0..delta.len() {
+            let error = apply(&source, &delta[..end]).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData, "at {}", end);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_deltas() {
+        let source = b"source".to_vec();
+        let cases: [&[u8]; 5] = [
+            // A size that overflows
+            &[
+                0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01,
+            ],
+            // The wrong source size
+            &[5, 1, 1, b'x'],
+            // A copy past the end of the source
+            &[6, 4, 0x91, 4, 4],
+            // The reserved instruction
+            &[6, 1, 0],
+            // More than the target size
+            &[6, 1, 2, b'x', b'y'],
+        ];
+        for delta in cases {
+            let error = apply(&source, delta).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData, "{:?}", delta);
+        }
+    }
+}
@@ -1,8 +1,13 @@
 #![allow(unused)]
 
 use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result};
 use std::path::PathBuf;
 
+use colored::Colorize;
+
+use crate::git::tree::FileMode;
+
 #[derive(Debug)]
 pub enum DiffType {
     Added,
@@ -17,6 +22,430 @@ pub struct DiffResult {
     pub diff_type: DiffType,
     pub old_content: Option<Vec<u8>>,
     pub new_content: Option<Vec<u8>>,
+    /// Modes are not part of the compared contents, the caller fills them in
+    /// when it knows them.
+    pub old_mode: Option<FileMode>,
+    pub new_mode: Option<FileMode>,
+}
+
+/// Strategy used to line up the lines of two versions of a file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DiffAlgorithm {
+    /// Shortest edit script, as Git uses by default.
+    #[default]
+    Myers,
+    /// Anchors on lines that occur once on each side, which keeps moved
+    /// blocks and braces from being matched up with the wrong code.
+    Patience,
+    /// Like patience, but anchors on the rarest common line when no line is
+    /// unique.
+    Histogram,
+}
+
+impl DiffAlgorithm {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "myers" | "default" => Ok(DiffAlgorithm::Myers),
+            "patience" => Ok(DiffAlgorithm::Patience),
+            "histogram" => Ok(DiffAlgorithm::Histogram),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unknown diff algorithm: {}", name),
+            )),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct DiffOptions {
+    /// Unchanged lines shown around each change.
+    pub context: usize,
+    pub algorithm: DiffAlgorithm,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            context: 3,
+            algorithm: DiffAlgorithm::default(),
+        }
+    }
+}
+
+/// One step of an edit script turning the old lines into the new ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edit {
+    /// The next old line and the next new line are the same
+    Equal,
+    /// The next old line is removed
+    Delete,
+    /// The next new line is inserted
+    Insert,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineKind {
+    Context,
+    Removed,
+    Added,
+}
+
+/// A run of changes with its surrounding context. Line numbers are 1-based.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_len: usize,
+    pub new_start: usize,
+    pub new_len: usize,
+    /// Each line keeps its trailing newline, if it had one.
+    pub lines: Vec<(LineKind, Vec<u8>)>,
+}
+
+impl Hunk {
+    /// The `@@ -a,b +c,d @@` line. An empty range starts at the line before it,
+    /// and a length of one is left out, as `diff -u` does.
+    pub fn header(&self) -> String {
+        let range = |start: usize, len: usize| match len {
+            0 => format!("{},0", start.saturating_sub(1)),
+            1 => start.to_string(),
+            _ => format!("{},{}", start, len),
+        };
+        format!(
+            "@@ -{} +{} @@",
+            range(self.old_start, self.old_len),
+            range(self.new_start, self.new_len)
+        )
+    }
+}
+
+/// Splits content into lines, each keeping its `\n`. Only the last line can
+/// lack one.
+pub fn split_lines(content: &[u8]) -> Vec<&[u8]> {
+    content.split_inclusive(|&b| b == b'\n').collect()
+}
+
+/// Git treats content as binary when a NUL byte shows up near the start.
+pub fn is_binary(content: &[u8]) -> bool {
+    content.iter().take(8000).any(|&b| b == 0)
+}
+
+/// Computes an edit script from `old` to `new`.
+pub fn diff_lines(old: &[&[u8]], new: &[&[u8]], algorithm: DiffAlgorithm) -> Vec<Edit> {
+    let mut edits = Vec::with_capacity(old.len().max(new.len()));
+    match algorithm {
+        DiffAlgorithm::Myers => myers(old, new, &mut edits),
+        DiffAlgorithm::Patience => patience(old, new, &mut edits),
+        DiffAlgorithm::Histogram => histogram(old, new, &mut edits),
+    }
+    edits
+}
+
+/// Strips the lines both sides start and end with, which every algorithm
+/// would match anyway, and returns how many there were.
+fn common_affixes(old: &[&[u8]], new: &[&[u8]]) -> (usize, usize) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    (prefix, suffix)
+}
+
+/// Appends the edits turning one run of lines into another.
+type LineDiffer = fn(&[&[u8]], &[&[u8]], &mut Vec<Edit>);
+
+/// Runs `middle` on what is left once the common prefix and suffix are
+/// removed, recording those as equal lines around its output.
+fn with_affixes(old: &[&[u8]], new: &[&[u8]], edits: &mut Vec<Edit>, middle: LineDiffer) {
+    let (prefix, suffix) = common_affixes(old, new);
+    edits.extend(std::iter::repeat_n(Edit::Equal, prefix));
+
+    let old_mid = &old[prefix..old.len() - suffix];
+    let new_mid = &new[prefix..new.len() - suffix];
+    if old_mid.is_empty() {
+        edits.extend(std::iter::repeat_n(Edit::Insert, new_mid.len()));
+    } else if new_mid.is_empty() {
+        edits.extend(std::iter::repeat_n(Edit::Delete, old_mid.len()));
+    } else {
+        middle(old_mid, new_mid, edits);
+    }
+
+    edits.extend(std::iter::repeat_n(Edit::Equal, suffix));
+}
+
+fn myers(old: &[&[u8]], new: &[&[u8]], edits: &mut Vec<Edit>) {
+    with_affixes(old, new, edits, myers_core);
+}
+
+/// Myers' O(ND) algorithm in linear space: finds the middle snake of the
+/// shortest edit script and recurses on both sides of it.
+///
+/// Only called once the common prefix and suffix are stripped, so both sides
+/// are non-empty and at least two edits apart, which keeps each half smaller
+/// than the whole.
+fn myers_core(old: &[&[u8]], new: &[&[u8]], edits: &mut Vec<Edit>) {
+    let (start_old, start_new, end_old, end_new) = middle_snake(old, new);
+
+    myers(&old[..start_old], &new[..start_new], edits);
+    edits.extend(std::iter::repeat_n(Edit::Equal, end_old - start_old));
+    myers(&old[end_old..], &new[end_new..], edits);
+}
+
+/// Runs the search forwards from the start and backwards from the end until
+/// the two meet, returning the snake where they do as
+/// `(old start, new start, old end, new end)`.
+fn middle_snake(old: &[&[u8]], new: &[&[u8]]) -> (usize, usize, usize, usize) {
+    let n = old.len() as isize;
+    let m = new.len() as isize;
+    let delta = n - m;
+    let odd = delta % 2 != 0;
+    let max = (n + m + 1) / 2;
+    let offset = max + 1;
+    let at = |k: isize| (k + offset) as usize;
+
+    // Furthest x reached on each diagonal, counted from the start for the
+    // forward search and from the end for the backward one
+    let mut forward = vec![0isize; (2 * offset + 1) as usize];
+    let mut backward = vec![0isize; (2 * offset + 1) as usize];
+
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)]
+            } else {
+                forward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let (snake_x, snake_y) = (x, y);
+            while x < n && y < m && old[x as usize] == new[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            forward[at(k)] = x;
+
+            let reverse_k = delta - k;
+            let in_grid = x <= n && y <= m;
+            if odd
+                && in_grid
+                && (-(d - 1)..=d - 1).contains(&reverse_k)
+                && x + backward[at(reverse_k)] >= n
+            {
+                return (snake_x as usize, snake_y as usize, x as usize, y as usize);
+            }
+        }
+
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                backward[at(k + 1)]
+            } else {
+                backward[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            let (snake_x, snake_y) = (x, y);
+            while x < n && y < m && old[(n - x - 1) as usize] == new[(m - y - 1) as usize] {
+                x += 1;
+                y += 1;
+            }
+            backward[at(k)] = x;
+
+            let forward_k = delta - k;
+            let in_grid = x <= n && y <= m;
+            if !odd && in_grid && (-d..=d).contains(&forward_k) && x + forward[at(forward_k)] >= n {
+                return (
+                    (n - x) as usize,
+                    (m - y) as usize,
+                    (n - snake_x) as usize,
+                    (m - snake_y) as usize,
+                );
+            }
+        }
+    }
+
+    unreachable!("the forward and backward searches always meet")
+}
+
+fn patience(old: &[&[u8]], new: &[&[u8]], edits: &mut Vec<Edit>) {
+    with_affixes(old, new, edits, patience_core);
+}
+
+fn patience_core(old: &[&[u8]], new: &[&[u8]], edits: &mut Vec<Edit>) {
+    // Lines that appear exactly once on each side, in old-side order
+    let mut counts: HashMap<&[u8], (usize, usize, usize, usize)> = HashMap::new();
+    for (i, line) in old.iter().enumerate() {
+        let entry = counts.entry(line).or_insert((0, 0, i, 0));
+        entry.0 += 1;
+    }
+    for (j, line) in new.iter().enumerate() {
+        if let Some(entry) = counts.get_mut(line) {
+            entry.1 += 1;
+            entry.3 = j;
+        }
+    }
+    let mut unique: Vec<(usize, usize)> = counts
+        .values()
+        .filter(|&&(in_old, in_new, _, _)| in_old == 1 && in_new == 1)
+        .map(|&(_, _, i, j)| (i, j))
+        .collect();
+    unique.sort();
+
+    let anchors = longest_increasing(&unique);
+    if anchors.is_empty() {
+        return myers_core(old, new, edits);
+    }
+
+    let (mut i, mut j) = (0, 0);
+    for (ai, aj) in anchors {
+        patience(&old[i..ai], &new[j..aj], edits);
+        edits.push(Edit::Equal);
+        i = ai + 1;
+        j = aj + 1;
+    }
+    patience(&old[i..], &new[j..], edits);
+}
+
+/// Longest chain of pairs increasing on both sides, found with patience
+/// sorting. `pairs` must be sorted by their first element.
+fn longest_increasing(pairs: &[(usize, usize)]) -> Vec<(usize, usize)> {
+    // Top of each pile, and for every pair the pair below it in the chain
+    let mut piles: Vec<usize> = Vec::new();
+    let mut previous: Vec<Option<usize>> = vec![None; pairs.len()];
+
+    for (index, &(_, j)) in pairs.iter().enumerate() {
+        let pile = piles.partition_point(|&top| pairs[top].1 < j);
+        if pile > 0 {
+            previous[index] = Some(piles[pile - 1]);
+        }
+        if pile == piles.len() {
+            piles.push(index);
+        } else {
+            piles[pile] = index;
+        }
+    }
+
+    let mut chain = Vec::new();
+    let mut current = piles.last().copied();
+    while let Some(index) = current {
+        chain.push(pairs[index]);
+        current = previous[index];
+    }
+    chain.reverse();
+    chain
+}
+
+fn histogram(old: &[&[u8]], new: &[&[u8]], edits: &mut Vec<Edit>) {
+    with_affixes(old, new, edits, histogram_core);
+}
+
+/// Lines repeated more often than this are never used as anchors.
+const HISTOGRAM_MAX_OCCURRENCES: usize = 64;
+
+fn histogram_core(old: &[&[u8]], new: &[&[u8]], edits: &mut Vec<Edit>) {
+    let mut occurrences: HashMap<&[u8], usize> = HashMap::new();
+    for line in old {
+        *occurrences.entry(line).or_default() += 1;
+    }
+
+    // Anchor on the first new line whose copies in the old side are rarest
+    let anchor = new
+        .iter()
+        .enumerate()
+        .filter_map(|(j, line)| occurrences.get(line).map(|&count| (count, j)))
+        .filter(|&(count, _)| count <= HISTOGRAM_MAX_OCCURRENCES)
+        .min();
+    let Some((_, j)) = anchor else {
+        return myers_core(old, new, edits);
+    };
+    let i = old
+        .iter()
+        .position(|line| *line == new[j])
+        .unwrap_or_default();
+
+    // Grow the match in both directions
+    let (mut start_old, mut start_new) = (i, j);
+    while start_old > 0 && start_new > 0 && old[start_old - 1] == new[start_new - 1] {
+        start_old -= 1;
+        start_new -= 1;
+    }
+    let (mut end_old, mut end_new) = (i + 1, j + 1);
+    while end_old < old.len() && end_new < new.len() && old[end_old] == new[end_new] {
+        end_old += 1;
+        end_new += 1;
+    }
+
+    histogram(&old[..start_old], &new[..start_new], edits);
+    edits.extend(std::iter::repeat_n(Edit::Equal, end_old - start_old));
+    histogram(&old[end_old..], &new[end_new..], edits);
+}
+
+/// Groups an edit script into hunks, merging changes whose context would
+/// overlap.
+pub fn hunks(old: &[&[u8]], new: &[&[u8]], edits: &[Edit], context: usize) -> Vec<Hunk> {
+    // Line positions before each edit
+    let mut positions = Vec::with_capacity(edits.len() + 1);
+    let (mut i, mut j) = (0, 0);
+    for edit in edits {
+        positions.push((i, j));
+        match edit {
+            Edit::Equal => {
+                i += 1;
+                j += 1;
+            }
+            Edit::Delete => i += 1,
+            Edit::Insert => j += 1,
+        }
+    }
+    positions.push((i, j));
+
+    let changes: Vec<usize> = (0..edits.len())
+        .filter(|&k| edits[k] != Edit::Equal)
+        .collect();
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    for &k in &changes {
+        match groups.last_mut() {
+            Some((_, end)) if k - *end <= 2 * context + 1 => *end = k,
+            _ => groups.push((k, k)),
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(first, last)| {
+            let start = first.saturating_sub(context);
+            let end = (last + 1 + context).min(edits.len());
+            let (old_from, new_from) = positions[start];
+            let (old_to, new_to) = positions[end];
+
+            let mut lines = Vec::new();
+            let (mut i, mut j) = (old_from, new_from);
+            for edit in &edits[start..end] {
+                match edit {
+                    Edit::Equal => {
+                        lines.push((LineKind::Context, old[i].to_vec()));
+                        i += 1;
+                        j += 1;
+                    }
+                    Edit::Delete => {
+                        lines.push((LineKind::Removed, old[i].to_vec()));
+                        i += 1;
+                    }
+                    Edit::Insert => {
+                        lines.push((LineKind::Added, new[j].to_vec()));
+                        j += 1;
+                    }
+                }
+            }
+
+            Hunk {
+                old_start: old_from + 1,
+                old_len: old_to - old_from,
+                new_start: new_from + 1,
+                new_len: new_to - new_from,
+                lines,
+            }
+        })
+        .collect()
 }
 
 pub struct Differ;
@@ -24,7 +453,7 @@ pub struct Differ;
 impl Differ {
     pub fn diff(
         old_tree: &HashMap<PathBuf, Vec<u8>>,
-        new_tree: &HashMap<PathBuf, Vec<u8>>
+        new_tree: &HashMap<PathBuf, Vec<u8>>,
     ) -> Vec<DiffResult> {
         let mut diffs = Vec::new();
 
@@ -38,6 +467,8 @@ impl Differ {
                         diff_type: DiffType::Modified,
                         old_content: Some(old_content.clone()),
                         new_content: Some(new_content.clone()),
+                        old_mode: None,
+                        new_mode: None,
                     });
                 }
             } else {
@@ -47,36 +478,207 @@ impl Differ {
                     diff_type: DiffType::Added,
                     old_content: None,
                     new_content: Some(new_content.clone()),
+                    old_mode: None,
+                    new_mode: None,
                 });
             }
         }
 
         // Check for deleted files
-        for (path, _) in old_tree {
+        for path in old_tree.keys() {
             if !new_tree.contains_key(path) {
                 diffs.push(DiffResult {
                     file_path: path.clone(),
                     diff_type: DiffType::Deleted,
                     old_content: old_tree.get(path).cloned(),
                     new_content: None,
+                    old_mode: None,
+                    new_mode: None,
                 });
             }
         }
 
+        diffs.sort_by(|a, b| a.file_path.cmp(&b.file_path));
         diffs
     }
 
-    pub fn render_diff(diff: &DiffResult) -> String {
-        match diff.diff_type {
-            DiffType::Added => format!("+ Added: {}", diff.file_path.display()),
-            DiffType::Deleted => format!("- Deleted: {}", diff.file_path.display()),
-            DiffType::Modified => format!(
-                "* Modified: {} (Old size: {}, New size: {})", 
-                diff.file_path.display(),
-                diff.old_content.as_ref().map(|c| c.len()).unwrap_or(0),
-                diff.new_content.as_ref().map(|c| c.len()).unwrap_or(0)
-            ),
-            DiffType::Unchanged => format!("  Unchanged: {}", diff.file_path.display()),
+    /// Renders a file's changes as a Git-style unified diff that `patch -p1`
+    /// and `git apply` accept. Colours are dropped when stdout is not a
+    /// terminal.
+    pub fn render_diff(diff: &DiffResult, options: &DiffOptions) -> String {
+        let path = diff.file_path.display();
+        let old_mode = diff.old_mode.unwrap_or(FileMode::Regular);
+        let new_mode = diff.new_mode.unwrap_or(FileMode::Regular);
+
+        let mut header = format!("diff --git a/{} b/{}\n", path, path);
+        let (old_name, new_name) = match diff.diff_type {
+            DiffType::Added => {
+                header.push_str(&format!("new file mode {}\n", new_mode.as_str()));
+                ("/dev/null".to_string(), format!("b/{}", path))
+            }
+            DiffType::Deleted => {
+                header.push_str(&format!("deleted file mode {}\n", old_mode.as_str()));
+                (format!("a/{}", path), "/dev/null".to_string())
+            }
+            DiffType::Modified | DiffType::Unchanged => {
+                if old_mode != new_mode {
+                    header.push_str(&format!(
+                        "old mode {}\nnew mode {}\n",
+                        old_mode.as_str(),
+                        new_mode.as_str()
+                    ));
+                }
+                (format!("a/{}", path), format!("b/{}", path))
+            }
+        };
+
+        let old_content = diff.old_content.as_deref().unwrap_or_default();
+        let new_content = diff.new_content.as_deref().unwrap_or_default();
+        // A change of mode alone has nothing below its header
+        if old_content == new_content {
+            return header.trim_end().bold().to_string() + "\n";
+        }
+        if is_binary(old_content) || is_binary(new_content) {
+            header.push_str(&format!(
+                "Binary files {} and {} differ",
+                old_name, new_name
+            ));
+            return header.bold().to_string() + "\n";
+        }
+        header.push_str(&format!("--- {}\n+++ {}", old_name, new_name));
+
+        let old_lines = split_lines(old_content);
+        let new_lines = split_lines(new_content);
+        let edits = diff_lines(&old_lines, &new_lines, options.algorithm);
+
+        let mut output = header.bold().to_string() + "\n";
+        for hunk in hunks(&old_lines, &new_lines, &edits, options.context) {
+            output.push_str(&format!("{}\n", hunk.header().cyan()));
+            for (kind, line) in &hunk.lines {
+                let text = String::from_utf8_lossy(line);
+                let text = text.strip_suffix('\n').unwrap_or(&text);
+                let line_output = match kind {
+                    LineKind::Context => format!(" {}", text).normal(),
+                    LineKind::Removed => format!("-{}", text).red(),
+                    LineKind::Added => format!("+{}", text).green(),
+                };
+                output.push_str(&format!("{}\n", line_output));
+                if !line.ends_with(b"\n") {
+                    output.push_str("\\ No newline at end of file\n");
+                }
+            }
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replays an edit script, checking it turns `old` into `new`.
+    fn apply(old: &[&[u8]], new: &[&[u8]], edits: &[Edit]) -> Vec<Vec<u8>> {
+        let (mut i, mut j) = (0, 0);
+        let mut result = Vec::new();
+        for edit in edits {
+            match edit {
+                Edit::Equal => {
+                    assert_eq!(old[i], new[j]);
+                    result.push(old[i].to_vec());
+                    i += 1;
+                    j += 1;
+                }
+                Edit::Delete => i += 1,
+                Edit::Insert => {
+                    result.push(new[j].to_vec());
+                    j += 1;
+                }
+            }
         }
+        assert_eq!((i, j), (old.len(), new.len()));
+        result
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn every_algorithm_produces_a_valid_edit_script() {
+        let old = b"a\nb\nc\nd\ne\nf\n{\n}\n".as_slice();
+        let new = b"a\nc\nd\nx\ne\n{\n}\n{\n}\nf\n".as_slice();
+        let (old, new) = (split_lines(old), split_lines(new));
+        for algorithm in [
+            DiffAlgorithm::Myers,
+            DiffAlgorithm::Patience,
+            DiffAlgorithm::Histogram,
+        ] {
+            let edits = diff_lines(&old, &new, algorithm);
+            let rebuilt = apply(&old, &new, &edits);
+            assert_eq!(
+                rebuilt,
+                new.iter().map(|line| line.to_vec()).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn myers_finds_a_shortest_script() {
+        let old = split_lines(b"a\nb\nc\na\nb\nb\na\n");
+        let new = split_lines(b"c\nb\na\nb\na\nc\n");
+        let edits = diff_lines(&old, &new, DiffAlgorithm::Myers);
+        let changes = edits.iter().filter(|edit| **edit != Edit::Equal).count();
+        assert_eq!(changes, 5);
+    }
+
+    #[test]
+    fn hunks_merge_nearby_changes_and_count_lines() {
+        let old = split_lines(b"1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n");
+        let new = split_lines(b"1\nTWO\n3\n4\n5\n6\nSEVEN\n8\n9\n10\n11\n12\n");
+        let edits = diff_lines(&old, &new, DiffAlgorithm::Myers);
+
+        let merged = hunks(&old, &new, &edits, 3);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].header(), "@@ -1,10 +1,10 @@");
+
+        let separate = hunks(&old, &new, &edits, 1);
+        let headers: Vec<String> = separate.iter().map(Hunk::header).collect();
+        assert_eq!(headers, ["@@ -1,3 +1,3 @@", "@@ -6,3 +6,3 @@"]);
+    }
+
+    #[test]
+    fn empty_ranges_start_at_the_line_before() {
+        let old = split_lines(b"");
+        let new = split_lines(b"a\n");
+        let edits = diff_lines(&old, &new, DiffAlgorithm::Myers);
+        assert_eq!(hunks(&old, &new, &edits, 3)[0].header(), "@@ -0,0 +1 @@");
+    }
+
+    #[test]
+    fn renders_a_mode_change_without_a_patch() {
+        colored::control::set_override(false);
+        let diff = DiffResult {
+            file_path: PathBuf::from("run.sh"),
+            diff_type: DiffType::Modified,
+            old_content: Some(b"echo\n".to_vec()),
+            new_content: Some(b"echo\n".to_vec()),
+            old_mode: Some(FileMode::Regular),
+            new_mode: Some(FileMode::Executable),
+        };
+        assert_eq!(
+            Differ::render_diff(&diff, &DiffOptions::default()),
+            "diff --git a/run.sh b/run.sh\nold mode 100644\nnew mode 100755\n"
+        );
+    }
+
+    #[test]
+    fn marks_a_missing_final_newline() {
+        colored::control::set_override(false);
+        let diff = DiffResult {
+            file_path: PathBuf::from("f"),
+            diff_type: DiffType::Modified,
+            old_content: Some(b"a\n".to_vec()),
+            new_content: Some(b"a\nb".to_vec()),
+            old_mode: None,
+            new_mode: None,
+        };
+        let rendered = Differ::render_diff(&diff, &DiffOptions::default());
+        assert!(rendered.ends_with("@@ -1 +1,2 @@\n a\n+b\n\\ No newline at end of file\n"));
+    }
+}
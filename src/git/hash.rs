@@ -0,0 +1,83 @@
+#![allow(unused)]
+
+use sha1::{Digest, Sha1};
+use sha2::Sha256;
+use std::io::{Error, ErrorKind, Result};
+
+/// Digest used to compute object ids, fixed for the lifetime of a repository.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum HashAlgorithm {
+    /// Git's historical object format, kept as the default for compatibility.
+    #[default]
+    Sha1,
+    Sha256,
+}
+
+impl HashAlgorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            HashAlgorithm::Sha1 => "sha1",
+            HashAlgorithm::Sha256 => "sha256",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "sha1" => Ok(HashAlgorithm::Sha1),
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unknown object format: {}", name),
+            )),
+        }
+    }
+
+    /// Length of a raw digest in bytes.
+    pub fn raw_len(&self) -> usize {
+        match self {
+            HashAlgorithm::Sha1 => 20,
+            HashAlgorithm::Sha256 => 32,
+        }
+    }
+
+    /// Length of an object id written as hexadecimal.
+    pub fn hex_len(&self) -> usize {
+        self.raw_len() * 2
+    }
+
+    pub fn digest(&self, data: &[u8]) -> Vec<u8> {
+        match self {
+            HashAlgorithm::Sha1 => Sha1::digest(data).to_vec(),
+            HashAlgorithm::Sha256 => Sha256::digest(data).to_vec(),
+        }
+    }
+
+    pub fn hex_digest(&self, data: &[u8]) -> String {
+        to_hex(&self.digest(data))
+    }
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    if !hex.is_ascii() || !hex.len().is_multiple_of(2) {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            format!("Invalid hex string: {}", hex),
+        ));
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Invalid hex string: {}", hex),
+                )
+            })
+        })
+        .collect()
+}
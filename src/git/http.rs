@@ -0,0 +1,295 @@
+#![allow(unused)]
+
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Result, Write};
+use std::net::TcpStream;
+
+use flate2::read::GzDecoder;
+
+/// What this client and server call themselves.
+pub const AGENT: &str = concat!("git-scm/", env!("CARGO_PKG_VERSION"));
+
+/// An `http://` URL. TLS is not supported, so neither is `https://`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Url {
+    pub host: String,
+    pub port: u16,
+    /// Path of the repository, without a trailing slash
+    pub path: String,
+}
+
+impl Url {
+    pub fn parse(url: &str) -> Result<Self> {
+        let invalid = |why: &str| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid URL {}: {}", url, why),
+            )
+        };
+
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| invalid("only http:// is supported"))?;
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => rest.split_at(slash),
+            None => (rest, ""),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| invalid("bad port number"))?),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(invalid("missing host"));
+        }
+        Ok(Url {
+            host: host.to_string(),
+            port,
+            path: path.trim_end_matches('/').to_string(),
+        })
+    }
+
+    /// Whether `url` is one this client can reach over HTTP.
+    pub fn is_http(url: &str) -> bool {
+        url.starts_with("http://") || url.starts_with("https://")
+    }
+}
+
+/// A request or response: its first line, headers and body.
+#[derive(Debug, Default)]
+pub struct Message {
+    /// `POST /path HTTP/1.1` or `HTTP/1.1 200 OK`
+    pub start: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Message {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Reads a message, with a body of the length it declares, sent in
+    /// chunks or lasting until the connection closes. Gzipped bodies, as Git
+    /// sends larger requests, are inflated.
+    ///
+    /// A body longer than `limit`, before or after inflating, fails with
+    /// [`ErrorKind::FileTooLarge`] before more of it is read.
+    pub fn read(stream: impl Read, until_close: bool, limit: Option<usize>) -> Result<Self> {
+        let limit = limit.unwrap_or(usize::MAX);
+        let mut reader = BufReader::new(stream);
+        let mut start = String::new();
+        reader.read_line(&mut start)?;
+        if start.is_empty() {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "Connection closed"));
+        }
+
+        let mut message = Message {
+            start: start.trim_end().to_string(),
+            ..Message::default()
+        };
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            let (name, value) = line
+                .split_once(':')
+                .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Malformed HTTP header"))?;
+            message
+                .headers
+                .push((name.trim().to_string(), value.trim().to_string()));
+        }
+
+        if message
+            .header("Transfer-Encoding")
+            .is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"))
+        {
+            message.body = read_chunked(&mut reader, limit)?;
+        } else if let Some(length) = message.header("Content-Length") {
+            let length: usize = length
+                .parse()
+                .map_err(|_| Error::new(ErrorKind::InvalidData, "Malformed Content-Length"))?;
+            if length > limit {
+                return Err(too_large(limit));
+            }
+            // The body grows as it arrives rather than by what was declared
+            reader.take(length as u64).read_to_end(&mut message.body)?;
+            if message.body.len() != length {
+                return Err(Error::new(ErrorKind::UnexpectedEof, "Truncated body"));
+            }
+        } else if until_close {
+            read_limited(reader, &mut message.body, limit)?;
+        }
+
+        if message.header("Content-Encoding") == Some("gzip") {
+            let mut inflated = Vec::new();
+            read_limited(
+                GzDecoder::new(message.body.as_slice()),
+                &mut inflated,
+                limit,
+            )?;
+            message.body = inflated;
+        }
+        Ok(message)
+    }
+
+    /// Sends the message with its length, on a connection closed after it.
+    pub fn write(&self, mut stream: impl Write) -> Result<()> {
+        let mut head = format!("{}\r\n", self.start);
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        head.push_str(&format!("Content-Length: {}\r\n", self.body.len()));
+        head.push_str("Connection: close\r\n\r\n");
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(&self.body)?;
+        stream.flush()
+    }
+}
+
+fn too_large(limit: usize) -> Error {
+    Error::new(
+        ErrorKind::FileTooLarge,
+        format!("Body larger than {} bytes", limit),
+    )
+}
+
+/// Reads `reader` to its end into `body`, failing once it holds more than
+/// `limit` bytes.
+fn read_limited(reader: impl Read, body: &mut Vec<u8>, limit: usize) -> Result<()> {
+    reader
+        .take((limit as u64).saturating_add(1))
+        .read_to_end(body)?;
+    if body.len() > limit {
+        return Err(too_large(limit));
+    }
+    Ok(())
+}
+
+fn read_chunked(reader: &mut impl BufRead, limit: usize) -> Result<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let size = line.trim().split(';').next().unwrap_or_default();
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "Malformed chunk size"))?;
+        if size > limit - body.len() {
+            return Err(too_large(limit));
+        }
+
+        let start = body.len();
+        reader.take(size as u64).read_to_end(&mut body)?;
+        if body.len() != start + size {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "Truncated chunk"));
+        }
+        // Each chunk, the last empty one too, ends with a blank line
+        let mut end = String::new();
+        reader.read_line(&mut end)?;
+        if size == 0 {
+            return Ok(body);
+        }
+    }
+}
+
+/// Sends a request for `path` below `url` and returns the response, failing
+/// unless it is `200 OK`.
+pub fn request(
+    url: &Url,
+    method: &str,
+    path: &str,
+    headers: &[(&str, &str)],
+    body: Vec<u8>,
+) -> Result<Message> {
+    let stream = TcpStream::connect((url.host.as_str(), url.port))?;
+    let mut request = Message {
+        start: format!("{} {}/{} HTTP/1.1", method, url.path, path),
+        headers: vec![
+            ("Host".to_string(), format!("{}:{}", url.host, url.port)),
+            ("User-Agent".to_string(), AGENT.to_string()),
+        ],
+        body,
+    };
+    for (name, value) in headers {
+        request.headers.push((name.to_string(), value.to_string()));
+    }
+    request.write(&stream)?;
+
+    let response = Message::read(&stream, true, None)?;
+    let status = response.start.split(' ').nth(1).unwrap_or_default();
+    if status != "200" {
+        return Err(Error::other(format!(
+            "{} {}/{} failed: {}",
+            method, url.path, path, response.start
+        )));
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    fn request(headers: &str, body: &[u8]) -> Vec<u8> {
+        let mut data =
+            format!("POST /repo/git-receive-pack HTTP/1.1\r\n{}\r\n", headers).into_bytes();
+        data.extend_from_slice(body);
+        data
+    }
+
+    fn read(data: &[u8], limit: usize) -> Result<Message> {
+        Message::read(data, false, Some(limit))
+    }
+
+    #[test]
+    fn reads_bodies_within_the_limit() {
+        let data = request("Content-Length: 5\r\n", b"hello");
+        assert_eq!(read(&data, 5).unwrap().body, b"hello");
+
+        let data = request(
+            "Transfer-Encoding: chunked\r\n",
+            b"3\r\nhel\r\n2;ext\r\nlo\r\n0\r\n\r\n",
+        );
+        assert_eq!(read(&data, 5).unwrap().body, b"hello");
+    }
+
+    #[test]
+    fn rejects_bodies_over_the_limit() {
+        let declared = request("Content-Length: 1000000000000\r\n", b"short");
+        let chunk = request("Transfer-Encoding: chunked\r\n", b"ffffffffffff\r\nx");
+        let chunks = request(
+            "Transfer-Encoding: chunked\r\n",
+            b"3\r\nhel\r\n3\r\nlo!\r\n0\r\n\r\n",
+        );
+
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[0; 4096]).unwrap();
+        let gzip = encoder.finish().unwrap();
+        let inflated = request(
+            &format!(
+                "Content-Encoding: gzip\r\nContent-Length: {}\r\n",
+                gzip.len()
+            ),
+            &gzip,
+        );
+
+        for (data, limit) in [(declared, 100), (chunk, 100), (chunks, 5), (inflated, 1024)] {
+            let error = read(&data, limit).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::FileTooLarge);
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_bodies() {
+        let data = request("Content-Length: 10\r\n", b"short");
+        assert!(read(&data, 100).is_err());
+        let data = request("Transfer-Encoding: chunked\r\n", b"a\r\nshort");
+        assert!(read(&data, 100).is_err());
+    }
+}
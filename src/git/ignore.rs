@@ -0,0 +1,452 @@
+#![allow(unused)]
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Component, Path, PathBuf};
+
+use globset::{GlobBuilder, GlobMatcher};
+
+use crate::git::config::Config;
+
+/// One line of an ignore file.
+#[derive(Clone, Debug)]
+pub struct IgnorePattern {
+    /// The line as written, for `check-ignore -v`
+    pub text: String,
+    /// The file the pattern comes from, relative to the work tree when it
+    /// is inside it
+    pub source: PathBuf,
+    /// Line number in `source`, from 1
+    pub line: usize,
+    /// `!pattern`: re-include what an earlier pattern excluded
+    pub negated: bool,
+    /// `pattern/`: only match directories
+    dir_only: bool,
+    /// Directory, relative to the work tree, the pattern applies below
+    base: PathBuf,
+    matcher: GlobMatcher,
+}
+
+impl IgnorePattern {
+    /// Parses a line of an ignore file whose patterns apply below `base`.
+    /// Blank lines and comments give `None`.
+    pub fn parse(text: &str, source: &Path, line: usize, base: &Path) -> Result<Option<Self>> {
+        // Trailing spaces are dropped unless escaped
+        let mut pattern = text.trim_end_matches('\r');
+        while pattern.ends_with(' ') && !pattern.ends_with("\\ ") {
+            pattern = &pattern[..pattern.len() - 1];
+        }
+        if pattern.is_empty() || pattern.starts_with('#') {
+            return Ok(None);
+        }
+
+        // `!` negates, while `\!` and `\#` start a pattern with those characters
+        let negated = pattern.starts_with('!');
+        if negated || pattern.starts_with("\\!") || pattern.starts_with("\\#") {
+            pattern = &pattern[1..];
+        }
+
+        let dir_only = pattern.ends_with('/');
+        let pattern = pattern.trim_end_matches('/');
+        if pattern.is_empty() {
+            return Ok(None);
+        }
+
+        // A slash anywhere but at the end anchors the pattern to `base`;
+        // otherwise it matches a name at any depth
+        let glob = match pattern.strip_prefix('/') {
+            Some(anchored) => anchored.to_string(),
+            None if pattern.contains('/') => pattern.to_string(),
+            None => format!("**/{}", pattern),
+        };
+        // Braces are not special in ignore files
+        let glob = glob.replace('{', "\\{").replace('}', "\\}");
+
+        let matcher = GlobBuilder::new(&glob)
+            .literal_separator(true)
+            .backslash_escape(true)
+            .build()
+            .map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("{}:{}: {}", source.display(), line, e),
+                )
+            })?
+            .compile_matcher();
+
+        Ok(Some(IgnorePattern {
+            text: text.trim_end().to_string(),
+            source: source.to_path_buf(),
+            line,
+            negated,
+            dir_only,
+            base: base.to_path_buf(),
+            matcher,
+        }))
+    }
+
+    /// Whether the pattern matches `path`, relative to the work tree.
+    fn matches(&self, path: &Path, is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        match path.strip_prefix(&self.base) {
+            Ok(relative) => self.matcher.is_match(relative),
+            Err(_) => false,
+        }
+    }
+}
+
+/// Decides which paths of a work tree are ignored, following Git's rules.
+///
+/// Patterns come from, in increasing priority: `core.excludesFile`,
+/// `info/exclude` in the git directory, then the `.gitignore` of every directory from
+/// the root down to the path. Within those the last matching pattern wins,
+/// and nothing below an ignored directory can be re-included.
+#[derive(Debug)]
+pub struct Ignore {
+    root: PathBuf,
+    /// The global excludes file and `info/exclude`, lowest priority first
+    global: Vec<IgnorePattern>,
+    /// Patterns of each directory's `.gitignore`, read when first needed
+    per_directory: RefCell<HashMap<PathBuf, Vec<IgnorePattern>>>,
+}
+
+impl Ignore {
+    pub fn new(root: &Path, git_dir: &Path, config: &Config) -> Result<Self> {
+        let mut global = Vec::new();
+        if let Some(path) = Self::excludes_file(config) {
+            global.extend(read_patterns(&path, &path, Path::new(""))?);
+        }
+        let exclude = git_dir.join("info").join("exclude");
+        let source = exclude.strip_prefix(root).unwrap_or(&exclude);
+        global.extend(read_patterns(&exclude, source, Path::new(""))?);
+
+        Ok(Ignore {
+            root: root.to_path_buf(),
+            global,
+            per_directory: RefCell::new(HashMap::new()),
+        })
+    }
+
+    /// `core.excludesFile`, or `git-scm/ignore` in the user's configuration
+    /// directory.
+    fn excludes_file(config: &Config) -> Option<PathBuf> {
+        let home = env::var_os("HOME").map(PathBuf::from);
+        if let Some(path) = config.get("core.excludesFile") {
+            return Some(match (path.strip_prefix("~/"), home) {
+                (Some(rest), Some(home)) => home.join(rest),
+                _ => PathBuf::from(path),
+            });
+        }
+
+        let config_dir = env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| home.map(|home| home.join(".config")))?;
+        Some(config_dir.join("git-scm").join("ignore"))
+    }
+
+    /// Whether `path`, relative to the work tree or below its root, is
+    /// excluded. Git's own directory always is.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> Result<bool> {
+        if is_git_dir(&self.relative(path)) {
+            return Ok(true);
+        }
+        Ok(self
+            .matching_pattern(path, is_dir)?
+            .is_some_and(|pattern| !pattern.negated))
+    }
+
+    /// The pattern that decides whether `path` is ignored, if any. A negated
+    /// pattern means the path is explicitly not ignored.
+    pub fn matching_pattern(&self, path: &Path, is_dir: bool) -> Result<Option<IgnorePattern>> {
+        let path = self.relative(path);
+
+        // Git's own directory is excluded without any pattern
+        if is_git_dir(&path) {
+            return Ok(None);
+        }
+
+        // Once a directory is excluded, nothing inside can be re-included
+        let ancestors: Vec<&Path> = path.ancestors().skip(1).collect();
+        for dir in ancestors
+            .iter()
+            .rev()
+            .filter(|dir| !dir.as_os_str().is_empty())
+        {
+            if let Some(pattern) = self.last_match(dir, true)? {
+                if !pattern.negated {
+                    return Ok(Some(pattern));
+                }
+            }
+        }
+        self.last_match(&path, is_dir)
+    }
+
+    fn last_match(&self, path: &Path, is_dir: bool) -> Result<Option<IgnorePattern>> {
+        let mut found = self
+            .global
+            .iter()
+            .rev()
+            .find(|pattern| pattern.matches(path, is_dir))
+            .cloned();
+
+        // Deeper ignore files take precedence, so check them last
+        let dirs: Vec<&Path> = path.ancestors().skip(1).collect();
+        for dir in dirs.into_iter().rev() {
+            let patterns = self.directory_patterns(dir)?;
+            if let Some(pattern) = patterns.iter().rev().find(|p| p.matches(path, is_dir)) {
+                found = Some(pattern.clone());
+            }
+        }
+        Ok(found)
+    }
+
+    fn directory_patterns(&self, dir: &Path) -> Result<Vec<IgnorePattern>> {
+        if let Some(patterns) = self.per_directory.borrow().get(dir) {
+            return Ok(patterns.clone());
+        }
+        let source = dir.join(".gitignore");
+        let patterns = read_patterns(&self.root.join(&source), &source, dir)?;
+        self.per_directory
+            .borrow_mut()
+            .insert(dir.to_path_buf(), patterns.clone());
+        Ok(patterns)
+    }
+
+    /// `path` relative to the work tree, without `.` components.
+    fn relative(&self, path: &Path) -> PathBuf {
+        let path = path.strip_prefix(&self.root).unwrap_or(path);
+        path.components()
+            .filter(|c| matches!(c, Component::Normal(_)))
+            .collect()
+    }
+}
+
+/// Whether `path` is inside a `.git-scm` or `.git` directory at the root.
+fn is_git_dir(path: &Path) -> bool {
+    path.starts_with(".git-scm") || path.starts_with(".git")
+}
+
+/// Reads the patterns of the ignore file at `path`, if there is one,
+/// naming it `source`.
+fn read_patterns(path: &Path, source: &Path, base: &Path) -> Result<Vec<IgnorePattern>> {
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+
+    let mut patterns = Vec::new();
+    for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+        patterns.extend(IgnorePattern::parse(line, source, number + 1, base)?);
+    }
+    Ok(patterns)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::config::ConfigScope;
+
+    /// A work tree with the given `.gitignore` files, `info/exclude` and
+    /// excludes file, as `(path, contents)` relative to a scratch directory.
+    struct Fixture {
+        dir: PathBuf,
+        ignore: Ignore,
+    }
+
+    impl Fixture {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "git-scm-ignore-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            for (path, contents) in files {
+                let path = dir.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, contents).unwrap();
+            }
+
+            let root = dir.join("work");
+            let git_dir = root.join(".git");
+            fs::create_dir_all(&git_dir).unwrap();
+            let config_path = dir.join("config");
+            fs::write(
+                &config_path,
+                format!(
+                    "[core]\n\texcludesFile = {}\n",
+                    dir.join("excludes").display()
+                ),
+            )
+            .unwrap();
+            let mut config = Config::default();
+            config.read_file(&config_path, ConfigScope::Local).unwrap();
+
+            let ignore = Ignore::new(&root, &git_dir, &config).unwrap();
+            Fixture { dir, ignore }
+        }
+
+        fn ignored(&self, path: &str) -> bool {
+            self.ignore.is_ignored(Path::new(path), false).unwrap()
+        }
+
+        fn ignored_dir(&self, path: &str) -> bool {
+            self.ignore.is_ignored(Path::new(path), true).unwrap()
+        }
+
+        /// The file and line of the pattern deciding `path`.
+        fn decided_by(&self, path: &str) -> Option<(String, usize)> {
+            self.ignore
+                .matching_pattern(Path::new(path), false)
+                .unwrap()
+                .map(|pattern| (pattern.source.display().to_string(), pattern.line))
+        }
+    }
+
+    impl Drop for Fixture {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn negation_re_includes_files() {
+        let fixture = Fixture::new("negation", &[("work/.gitignore", "*.log\n!keep.log\n")]);
+        assert!(fixture.ignored("debug.log"));
+        assert!(fixture.ignored("sub/debug.log"));
+        assert!(!fixture.ignored("keep.log"));
+        assert!(!fixture.ignored("sub/keep.log"));
+        assert_eq!(
+            fixture.decided_by("keep.log"),
+            Some((".gitignore".to_string(), 2))
+        );
+        assert_eq!(fixture.decided_by("notes.txt"), None);
+    }
+
+    #[test]
+    fn trailing_slash_only_matches_directories() {
+        let fixture = Fixture::new("dir-only", &[("work/.gitignore", "build/\n")]);
+        assert!(fixture.ignored_dir("build"));
+        assert!(fixture.ignored_dir("sub/build"));
+        assert!(!fixture.ignored("build"));
+        // Files below an ignored directory are ignored with it
+        assert!(fixture.ignored("build/out.o"));
+        assert!(fixture.ignored("sub/build/deep/out.o"));
+    }
+
+    #[test]
+    fn slashes_anchor_patterns() {
+        let fixture = Fixture::new(
+            "anchored",
+            &[("work/.gitignore", "/root.txt\ndoc/*.txt\n*.tmp\n")],
+        );
+        assert!(fixture.ignored("root.txt"));
+        assert!(!fixture.ignored("sub/root.txt"));
+
+        assert!(fixture.ignored("doc/a.txt"));
+        assert!(!fixture.ignored("sub/doc/a.txt"));
+        // A single star does not cross directories
+        assert!(!fixture.ignored("doc/deeper/a.txt"));
+
+        // Without a slash the name matches at any depth
+        assert!(fixture.ignored("a.tmp"));
+        assert!(fixture.ignored("sub/dir/a.tmp"));
+    }
+
+    #[test]
+    fn anchors_patterns_to_their_gitignore() {
+        let fixture = Fixture::new("nested", &[("work/sub/.gitignore", "/local.txt\n*.bak\n")]);
+        assert!(fixture.ignored("sub/local.txt"));
+        assert!(!fixture.ignored("local.txt"));
+        assert!(!fixture.ignored("sub/deeper/local.txt"));
+        assert!(fixture.ignored("sub/deeper/a.bak"));
+        assert!(!fixture.ignored("a.bak"));
+    }
+
+    #[test]
+    fn double_stars_match_any_depth() {
+        let fixture = Fixture::new(
+            "double-star",
+            &[("work/.gitignore", "**/logs\na/**/b\ncache/**\n")],
+        );
+        assert!(fixture.ignored("logs"));
+        assert!(fixture.ignored("x/y/logs"));
+
+        assert!(fixture.ignored("a/b"));
+        assert!(fixture.ignored("a/x/y/b"));
+        assert!(!fixture.ignored("x/a/b"));
+
+        assert!(fixture.ignored("cache/file"));
+        assert!(fixture.ignored("cache/deep/file"));
+        assert!(!fixture.ignored_dir("cache"));
+    }
+
+    #[test]
+    fn deeper_files_take_precedence() {
+        let fixture = Fixture::new(
+            "precedence",
+            &[
+                ("excludes", "*.dat\n*.md\n"),
+                ("work/.git/info/exclude", "!a.dat\nb.dat\n"),
+                ("work/.gitignore", "!*.md\n"),
+                ("work/sub/.gitignore", "!b.dat\n*.md\n"),
+            ],
+        );
+        let excludes = fixture.dir.join("excludes").display().to_string();
+
+        // The excludes file applies unless something overrides it
+        assert!(fixture.ignored("x.dat"));
+        assert_eq!(fixture.decided_by("x.dat"), Some((excludes, 1)));
+        // info/exclude overrides the excludes file
+        assert!(!fixture.ignored("a.dat"));
+        assert_eq!(
+            fixture.decided_by("a.dat"),
+            Some((".git/info/exclude".to_string(), 1))
+        );
+        assert!(fixture.ignored("b.dat"));
+        // A .gitignore overrides both, and a deeper one the one above it
+        assert!(!fixture.ignored("README.md"));
+        assert!(!fixture.ignored("sub/b.dat"));
+        assert!(fixture.ignored("sub/README.md"));
+        assert_eq!(
+            fixture.decided_by("sub/README.md"),
+            Some(("sub/.gitignore".to_string(), 2))
+        );
+    }
+
+    #[test]
+    fn cannot_re_include_files_of_an_excluded_directory() {
+        let fixture = Fixture::new(
+            "excluded-dir",
+            &[
+                (
+                    "work/.gitignore",
+                    "vendor/\n!vendor/keep.txt\nlib/*\n!lib/keep.txt\n",
+                ),
+                ("work/vendor/.gitignore", "!keep.txt\n"),
+            ],
+        );
+        assert!(fixture.ignored("vendor/keep.txt"));
+        assert_eq!(
+            fixture.decided_by("vendor/keep.txt"),
+            Some((".gitignore".to_string(), 1))
+        );
+
+        // Excluding what is inside a directory, rather than the directory
+        // itself, leaves room for exceptions
+        assert!(fixture.ignored("lib/other.txt"));
+        assert!(!fixture.ignored("lib/keep.txt"));
+    }
+
+    #[test]
+    fn always_ignores_the_git_directory() {
+        let fixture = Fixture::new("git-dir", &[("work/.gitignore", "!*\n")]);
+        assert!(fixture.ignored_dir(".git"));
+        assert!(fixture.ignored(".git/config"));
+        assert!(fixture.ignored(".git-scm/index"));
+    }
+}
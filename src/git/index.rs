@@ -2,55 +2,491 @@
 
 use serde::{Deserialize, Serialize};
 
-use crate::git::blob::Blob;
-use std::collections::HashMap;
-use std::fs;
-use std::io::Result;
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, Metadata};
+use std::io::{Error, ErrorKind, Result};
 use std::path::{Path, PathBuf};
 
+use crate::git::blob::Blob;
+use crate::git::hash::{from_hex, to_hex, HashAlgorithm};
+use crate::git::object_store::{hash_object, ObjectKind};
+use crate::git::tree::FileMode;
+
+/// A staged file: the id of its blob in the object store and its file mode.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct IndexEntry {
+    pub id: String,
+    pub mode: FileMode,
+}
+
+/// The `stat` fields Git records per index entry to notice changes to a
+/// file without hashing it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct FileStat {
+    ctime: (u32, u32),
+    mtime: (u32, u32),
+    dev: u32,
+    ino: u32,
+    uid: u32,
+    gid: u32,
+    size: u32,
+}
+
+impl FileStat {
+    /// Git truncates every field to 32 bits.
+    #[cfg(unix)]
+    fn from_metadata(metadata: &Metadata) -> Self {
+        use std::os::unix::fs::MetadataExt;
+        FileStat {
+            ctime: (metadata.ctime() as u32, metadata.ctime_nsec() as u32),
+            mtime: (metadata.mtime() as u32, metadata.mtime_nsec() as u32),
+            dev: metadata.dev() as u32,
+            ino: metadata.ino() as u32,
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            size: metadata.size() as u32,
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn from_metadata(metadata: &Metadata) -> Self {
+        FileStat {
+            size: metadata.len() as u32,
+            ..FileStat::default()
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Index {
-    entries: HashMap<PathBuf, Blob>,
+    entries: HashMap<PathBuf, IndexEntry>,
+    /// Paths a merge left with conflict markers, which must be staged again
+    /// before the merge can be committed.
+    conflicts: BTreeSet<PathBuf>,
+    /// Stat data read from a DIRC index, kept for entries that have not been
+    /// staged again since
+    #[serde(skip)]
+    stats: HashMap<PathBuf, FileStat>,
+}
+
+/// The index as written before merges recorded their conflicts. Bincode
+/// has no field names, so a missing trailing field cannot be defaulted.
+#[derive(Deserialize)]
+struct EntriesOnlyIndex {
+    entries: HashMap<PathBuf, IndexEntry>,
 }
 
 impl Index {
     pub fn new() -> Self {
         Index {
             entries: HashMap::new(),
+            conflicts: BTreeSet::new(),
+            stats: HashMap::new(),
         }
     }
 
-    pub fn add(&mut self, path: PathBuf, blob: Blob) {
-        self.entries.insert(path, blob);
+    pub fn add(&mut self, path: PathBuf, blob_id: String, mode: FileMode) {
+        self.conflicts.remove(&path);
+        self.stats.remove(&path);
+        self.entries.insert(path, IndexEntry { id: blob_id, mode });
     }
 
     pub fn remove(&mut self, path: &PathBuf) {
+        self.conflicts.remove(path);
+        self.stats.remove(path);
         self.entries.remove(path);
     }
 
-    pub fn get_entries(&self) -> &HashMap<PathBuf, Blob> {
+    pub fn mark_conflicted(&mut self, path: PathBuf) {
+        self.conflicts.insert(path);
+    }
+
+    pub fn conflicts(&self) -> &BTreeSet<PathBuf> {
+        &self.conflicts
+    }
+
+    pub fn get_entries(&self) -> &HashMap<PathBuf, IndexEntry> {
         &self.entries
     }
 
-    pub fn save_to_disk(&self, path: &Path) -> Result<()> {
-        let index_path = path.join(".git-scm").join("index");
-        let serialized = bincode::serialize(self).map_err(|e| {
-            std::io::Error::new(std::io::ErrorKind::Other, format!("Serialization error: {}", e))
-        })?;
+    /// Writes the index into the git directory `git_dir`.
+    pub fn save_to_disk(&self, git_dir: &Path) -> Result<()> {
+        let index_path = git_dir.join("index");
+        let serialized = bincode::serialize(self)
+            .map_err(|e| std::io::Error::other(format!("Serialization error: {}", e)))?;
         fs::write(index_path, serialized)?;
         Ok(())
     }
 
-    pub fn load_from_disk(path: &Path) -> Result<Self> {
-        let index_path = path.join(".git-scm").join("index");
+    pub fn load_from_disk(git_dir: &Path) -> Result<Self> {
+        let index_path = git_dir.join("index");
         if index_path.exists() {
             let data = fs::read(&index_path)?;
-            let index: Index = bincode::deserialize(&data).map_err(|e| {
-                std::io::Error::new(std::io::ErrorKind::Other, format!("Deserialization error: {}", e))
-            })?;
+            let index: Index = match bincode::deserialize(&data) {
+                Ok(index) => index,
+                Err(e) => match bincode::deserialize::<EntriesOnlyIndex>(&data) {
+                    Ok(old) => Index {
+                        entries: old.entries,
+                        ..Index::new()
+                    },
+                    Err(_) => {
+                        return Err(std::io::Error::other(format!(
+                            "Deserialization error: {}",
+                            e
+                        )))
+                    }
+                },
+            };
             Ok(index)
         } else {
             Ok(Index::new())
         }
     }
+
+    /// Writes the index into `git_dir` in Git's DIRC format, version 2.
+    ///
+    /// Stat data is only recorded for files in `work_tree` whose content
+    /// matches the staged blob; other entries get zeroed stat data, which
+    /// makes Git compare their content.
+    pub fn save_dirc(
+        &self,
+        git_dir: &Path,
+        work_tree: &Path,
+        algorithm: HashAlgorithm,
+    ) -> Result<()> {
+        let mut paths: Vec<(&PathBuf, String)> = self
+            .entries
+            .keys()
+            .map(|path| (path, path.to_string_lossy().to_string()))
+            .collect();
+        paths.sort_by(|a, b| a.1.as_bytes().cmp(b.1.as_bytes()));
+
+        let mut data = b"DIRC".to_vec();
+        data.extend_from_slice(&2u32.to_be_bytes());
+        data.extend_from_slice(&(paths.len() as u32).to_be_bytes());
+
+        for (path, name) in paths {
+            let entry = &self.entries[path];
+            let stat = self.current_stat(path, entry, work_tree, algorithm);
+            let start = data.len();
+
+            for field in [
+                stat.ctime.0,
+                stat.ctime.1,
+                stat.mtime.0,
+                stat.mtime.1,
+                stat.dev,
+                stat.ino,
+                mode_bits(entry.mode)?,
+                stat.uid,
+                stat.gid,
+                stat.size,
+            ] {
+                data.extend_from_slice(&field.to_be_bytes());
+            }
+            data.extend_from_slice(&from_hex(&entry.id)?);
+
+            // A conflicted path is written as our side, stage 2
+            let stage: u16 = if self.conflicts.contains(path) { 2 } else { 0 };
+            let flags = (stage << 12) | name.len().min(0xfff) as u16;
+            data.extend_from_slice(&flags.to_be_bytes());
+            data.extend_from_slice(name.as_bytes());
+
+            // One to eight NULs pad each entry to a multiple of eight bytes
+            let padding = 8 - (data.len() - start) % 8;
+            data.extend(std::iter::repeat_n(0, padding));
+        }
+
+        let checksum = algorithm.digest(&data);
+        data.extend_from_slice(&checksum);
+        fs::write(git_dir.join("index"), data)
+    }
+
+    /// Stat data to record for `path`, zeroed unless the file on disk is
+    /// known to hold the staged content.
+    fn current_stat(
+        &self,
+        path: &Path,
+        entry: &IndexEntry,
+        work_tree: &Path,
+        algorithm: HashAlgorithm,
+    ) -> FileStat {
+        let full_path = work_tree.join(path);
+        let Ok(metadata) = fs::symlink_metadata(&full_path) else {
+            return FileStat::default();
+        };
+        let stat = FileStat::from_metadata(&metadata);
+        if self.stats.get(path) == Some(&stat) {
+            return stat;
+        }
+
+        let content = if metadata.file_type().is_symlink() {
+            fs::read_link(&full_path).map(|target| target.to_string_lossy().as_bytes().to_vec())
+        } else {
+            fs::read(&full_path)
+        };
+        match content {
+            Ok(content) if hash_object(algorithm, ObjectKind::Blob, &content) == entry.id => stat,
+            _ => FileStat::default(),
+        }
+    }
+
+    /// Reads a DIRC index, versions 2 and 3, from `git_dir`. Extensions are
+    /// skipped, and unmerged paths keep our side, stage 2, when there is one.
+    pub fn load_dirc(git_dir: &Path, algorithm: HashAlgorithm) -> Result<Self> {
+        let index_path = git_dir.join("index");
+        if !index_path.exists() {
+            return Ok(Index::new());
+        }
+
+        let data = fs::read(&index_path)?;
+        let malformed =
+            |what: &str| Error::new(ErrorKind::InvalidData, format!("Malformed index: {}", what));
+        let id_len = algorithm.raw_len();
+        if data.len() < 12 + id_len || &data[..4] != b"DIRC" {
+            return Err(malformed("missing DIRC header"));
+        }
+        let (body, checksum) = data.split_at(data.len() - id_len);
+        if algorithm.digest(body) != checksum {
+            return Err(malformed("checksum mismatch"));
+        }
+
+        let word = |at: usize| -> Result<u32> {
+            body.get(at..at + 4)
+                .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+                .ok_or_else(|| malformed("truncated entry"))
+        };
+        let version = word(4)?;
+        if version != 2 && version != 3 {
+            return Err(malformed(&format!("unsupported version {}", version)));
+        }
+
+        let mut index = Index::new();
+        let mut ours = BTreeSet::new();
+        let mut at = 12;
+        for _ in 0..word(8)? {
+            let start = at;
+            let stat = FileStat {
+                ctime: (word(at)?, word(at + 4)?),
+                mtime: (word(at + 8)?, word(at + 12)?),
+                dev: word(at + 16)?,
+                ino: word(at + 20)?,
+                uid: word(at + 28)?,
+                gid: word(at + 32)?,
+                size: word(at + 36)?,
+            };
+            let mode = mode_from_bits(word(at + 24)?)?;
+            at += 40;
+            let id = to_hex(
+                body.get(at..at + id_len)
+                    .ok_or_else(|| malformed("truncated id"))?,
+            );
+            at += id_len;
+            let flags = body
+                .get(at..at + 2)
+                .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+                .ok_or_else(|| malformed("truncated flags"))?;
+            at += 2;
+            // Version 3 entries with the extended bit carry two more flag bytes
+            if flags & 0x4000 != 0 {
+                at += 2;
+            }
+
+            let name_end = body
+                .get(at..)
+                .ok_or_else(|| malformed("truncated entry"))?
+                .iter()
+                .position(|&b| b == 0)
+                .map(|end| at + end)
+                .ok_or_else(|| malformed("unterminated path"))?;
+            let path = PathBuf::from(String::from_utf8_lossy(&body[at..name_end]).to_string());
+            at = start + (name_end - start + 8) / 8 * 8;
+
+            let stage = (flags >> 12) & 0x3;
+            if stage == 0 {
+                index.add(path.clone(), id, mode);
+                index.stats.insert(path, stat);
+            } else if stage == 2 || !ours.contains(&path) {
+                index.add(path.clone(), id, mode);
+                index.mark_conflicted(path.clone());
+                if stage == 2 {
+                    ours.insert(path);
+                }
+            }
+        }
+        Ok(index)
+    }
+}
+
+/// The mode field of a DIRC entry.
+fn mode_bits(mode: FileMode) -> Result<u32> {
+    u32::from_str_radix(mode.as_str(), 8)
+        .map_err(|_| Error::new(ErrorKind::InvalidData, "Invalid file mode"))
+}
+
+fn mode_from_bits(bits: u32) -> Result<FileMode> {
+    FileMode::parse(&format!("{:o}", bits)).ok_or_else(|| {
+        Error::new(
+            ErrorKind::InvalidData,
+            format!("Unsupported index entry mode {:o}", bits),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_an_index_written_without_conflicts() {
+        #[derive(Serialize)]
+        struct Old {
+            entries: HashMap<PathBuf, IndexEntry>,
+        }
+        let entry = IndexEntry {
+            id: "e69de29bb2d1d6434b8b29ae775ad8c2e48c5391".to_string(),
+            mode: FileMode::Regular,
+        };
+        let old = Old {
+            entries: HashMap::from([(PathBuf::from("a"), entry.clone())]),
+        };
+
+        let dir = std::env::temp_dir().join(format!("git-scm-index-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("index"), bincode::serialize(&old).unwrap()).unwrap();
+        let index = Index::load_from_disk(&dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(index.get_entries().get(Path::new("a")), Some(&entry));
+        assert!(index.conflicts().is_empty());
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("git-scm-index-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn blob_id(content: &[u8]) -> String {
+        hash_object(HashAlgorithm::Sha1, ObjectKind::Blob, content)
+    }
+
+    /// An index of an executable script that is on disk, a file that is not
+    /// and a conflicted one.
+    fn sample(work_tree: &Path) -> Index {
+        fs::create_dir_all(work_tree.join("a")).unwrap();
+        fs::write(work_tree.join("a/run.sh"), "#!/bin/sh\n").unwrap();
+
+        let mut index = Index::new();
+        index.add(PathBuf::from("b.txt"), blob_id(b"b\n"), FileMode::Regular);
+        index.add(
+            PathBuf::from("a/run.sh"),
+            blob_id(b"#!/bin/sh\n"),
+            FileMode::Executable,
+        );
+        index.add(PathBuf::from("c"), blob_id(b"ours\n"), FileMode::Regular);
+        index.mark_conflicted(PathBuf::from("c"));
+        index
+    }
+
+    #[test]
+    fn writes_the_dirc_layout() {
+        let dir = temp_dir("dirc-layout");
+        sample(&dir)
+            .save_dirc(&dir, &dir, HashAlgorithm::Sha1)
+            .unwrap();
+        let data = fs::read(dir.join("index")).unwrap();
+        let word = |at: usize| u32::from_be_bytes(data[at..at + 4].try_into().unwrap());
+
+        assert_eq!(&data[..4], b"DIRC");
+        assert_eq!(word(4), 2);
+        assert_eq!(word(8), 3);
+
+        // Entries in byte order of their paths, each padded with one to eight
+        // NULs to a multiple of eight bytes
+        let mut at = 12;
+        let expected = [
+            ("a/run.sh", 0o100755, blob_id(b"#!/bin/sh\n"), 0),
+            ("b.txt", 0o100644, blob_id(b"b\n"), 0),
+            ("c", 0o100644, blob_id(b"ours\n"), 2),
+        ];
+        for (name, mode, id, stage) in expected {
+            assert_eq!(word(at + 24), mode, "{}", name);
+            assert_eq!(to_hex(&data[at + 40..at + 60]), id, "{}", name);
+            let flags = u16::from_be_bytes([data[at + 60], data[at + 61]]);
+            assert_eq!(flags, (stage << 12) | name.len() as u16, "{}", name);
+            assert_eq!(&data[at + 62..at + 62 + name.len()], name.as_bytes());
+
+            let length = (62 + name.len() + 8) / 8 * 8;
+            assert!(data[at + 62 + name.len()..at + length]
+                .iter()
+                .all(|&b| b == 0));
+            // Stat data only for the file known to match what is staged
+            let size = word(at + 36);
+            assert_eq!(size, if name == "a/run.sh" { 10 } else { 0 }, "{}", name);
+            at += length;
+        }
+
+        // Then the checksum of everything before it
+        assert_eq!(data.len(), at + 20);
+        assert_eq!(data[at..], HashAlgorithm::Sha1.digest(&data[..at])[..]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reads_back_a_dirc_index() {
+        let dir = temp_dir("dirc-round-trip");
+        let index = sample(&dir);
+        index.save_dirc(&dir, &dir, HashAlgorithm::Sha1).unwrap();
+
+        let loaded = Index::load_dirc(&dir, HashAlgorithm::Sha1).unwrap();
+        assert_eq!(loaded.get_entries(), index.get_entries());
+        assert_eq!(loaded.conflicts(), index.conflicts());
+        assert_eq!(
+            loaded
+                .stats
+                .get(Path::new("a/run.sh"))
+                .map(|stat| stat.size),
+            Some(10)
+        );
+
+        // Writing what was read gives the same bytes
+        let written = fs::read(dir.join("index")).unwrap();
+        loaded.save_dirc(&dir, &dir, HashAlgorithm::Sha1).unwrap();
+        assert_eq!(fs::read(dir.join("index")).unwrap(), written);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_a_corrupt_dirc_index() {
+        let dir = temp_dir("dirc-corrupt");
+        sample(&dir)
+            .save_dirc(&dir, &dir, HashAlgorithm::Sha1)
+            .unwrap();
+        let data = fs::read(dir.join("index")).unwrap();
+
+        let mut flipped = data.clone();
+        flipped[20] ^= 1;
+        fs::write(dir.join("index"), &flipped).unwrap();
+        assert!(Index::load_dirc(&dir, HashAlgorithm::Sha1).is_err());
+
+        fs::write(dir.join("index"), &data[..10]).unwrap();
+        assert!(Index::load_dirc(&dir, HashAlgorithm::Sha1).is_err());
+
+        // A truncated entry with a checksum that matches
+        let mut truncated = data[..40].to_vec();
+        truncated.extend(HashAlgorithm::Sha1.digest(&truncated));
+        fs::write(dir.join("index"), &truncated).unwrap();
+        assert!(Index::load_dirc(&dir, HashAlgorithm::Sha1).is_err());
+
+        // Cut right after flags claiming two more bytes of them
+        let mut extended = data[..12 + 62].to_vec();
+        extended[12 + 60] |= 0x40;
+        extended.extend(HashAlgorithm::Sha1.digest(&extended));
+        fs::write(dir.join("index"), &extended).unwrap();
+        assert!(Index::load_dirc(&dir, HashAlgorithm::Sha1).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -0,0 +1,418 @@
+#![allow(unused)]
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Component, Path, PathBuf};
+
+use colored::Colorize;
+
+use crate::git::commit::Commit;
+
+/// Which commits `log` shows. Every filter that is set must match.
+#[derive(Clone, Debug, Default)]
+pub struct LogOptions {
+    pub max_count: Option<usize>,
+    /// Substring of the author identity
+    pub author: Option<String>,
+    /// Substring of the commit message
+    pub grep: Option<String>,
+    /// Oldest commit timestamp shown, inclusive
+    pub since: Option<u64>,
+    /// Newest commit timestamp shown, inclusive
+    pub until: Option<u64>,
+    /// Only commits changing one of these files or directories
+    pub paths: Vec<PathBuf>,
+}
+
+impl LogOptions {
+    /// Checks the filters that only need the commit itself; paths are
+    /// checked against the trees by the repository.
+    pub fn matches(&self, commit: &Commit) -> bool {
+        self.author
+            .as_ref()
+            .is_none_or(|author| commit.author.identity().contains(author.as_str()))
+            && self
+                .grep
+                .as_ref()
+                .is_none_or(|pattern| commit.message.contains(pattern.as_str()))
+            && self.since.is_none_or(|since| commit.timestamp() >= since)
+            && self.until.is_none_or(|until| commit.timestamp() <= until)
+    }
+
+    /// Whether `path` lies in one of the filtered paths.
+    pub fn matches_path(&self, path: &Path) -> bool {
+        self.paths.is_empty()
+            || self.paths.iter().any(|filter| {
+                // Compare named components only, so `./src` matches `src/x.rs`
+                let filter: PathBuf = filter
+                    .components()
+                    .filter(|c| matches!(c, Component::Normal(_)))
+                    .collect();
+                path.starts_with(filter)
+            })
+    }
+}
+
+/// A commit picked by `log`, with its parents rewritten to the nearest
+/// commits that are also shown so the graph skips over the hidden ones.
+#[derive(Clone, Debug)]
+pub struct LogEntry {
+    pub commit: Commit,
+    pub parents: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Header lines followed by the indented message, as `git log` prints
+    #[default]
+    Medium,
+    /// Abbreviated id and subject on a single line
+    Oneline,
+}
+
+/// Renders entries newest first, optionally next to an ASCII history graph.
+pub fn render(entries: &[LogEntry], format: LogFormat, graph: bool) -> String {
+    let mut output = String::new();
+    let mut lanes = Graph::default();
+
+    for (index, entry) in entries.iter().enumerate() {
+        let commit = &entry.commit;
+        let mut lines = match format {
+            LogFormat::Oneline => vec![format!(
+                "{} {}",
+                commit.get_id()[..7].yellow(),
+                commit.message.lines().next().unwrap_or_default()
+            )],
+            LogFormat::Medium => {
+                let mut lines = vec![format!(
+                    "{}",
+                    format!("commit {}", commit.get_id()).yellow()
+                )];
+                if commit.is_merge() {
+                    let short: Vec<&str> = commit.parents().iter().map(|p| &p[..7]).collect();
+                    lines.push(format!("Merge: {}", short.join(" ")));
+                }
+                let author = &commit.author;
+                lines.push(format!("Author: {}", author.identity()));
+                lines.push(format!(
+                    "Date:   {}",
+                    format_timestamp(author.timestamp, author.offset)
+                ));
+                lines.push(String::new());
+                lines.extend(commit.message.lines().map(|line| format!("    {}", line)));
+                // A blank line separates commits, except after the last one
+                if index + 1 < entries.len() {
+                    lines.push(String::new());
+                }
+                lines
+            }
+        };
+
+        if !graph {
+            for line in lines {
+                output.push_str(&line);
+                output.push('\n');
+            }
+            continue;
+        }
+
+        // Rows joining the lanes double as prefixes for the lines that follow
+        // the commit line, as `git log --graph` draws them
+        let (commit_row, transitions) = lanes.advance(commit.get_id(), &entry.parents);
+        let continuation = lanes.continuation();
+        let mut prefixes = vec![commit_row];
+        prefixes.extend(transitions);
+        let width = prefixes
+            .iter()
+            .chain([&continuation])
+            .map(String::len)
+            .max();
+        let width = width.unwrap_or_default();
+
+        for i in 0..prefixes.len().max(lines.len()) {
+            let prefix = prefixes.get(i).unwrap_or(&continuation);
+            let line = lines.get(i).map(String::as_str).unwrap_or_default();
+            output.push_str(format!("{:width$} {}", prefix, line, width = width).trim_end());
+            output.push('\n');
+        }
+    }
+    output
+}
+
+/// The lanes of `log --graph`: each column waits for the commit it will
+/// show next. Lane `i` is drawn at character `2 * i`, and the characters in
+/// between hold the `/` and `\` that move lanes around.
+#[derive(Debug, Default)]
+struct Graph {
+    columns: Vec<String>,
+}
+
+impl Graph {
+    /// Places `id` in its lane, replaces it there by its parents and returns
+    /// the row for the commit itself followed by the rows connecting the
+    /// lanes before and after.
+    fn advance(&mut self, id: &str, parents: &[String]) -> (String, Vec<String>) {
+        let column = match self.columns.iter().position(|c| c == id) {
+            Some(column) => column,
+            None => {
+                self.columns.push(id.to_string());
+                self.columns.len() - 1
+            }
+        };
+        let mut commit_row = lane_row(self.columns.len());
+        commit_row[2 * column] = '*';
+
+        let mut transitions = Vec::new();
+        let width = self.columns.len();
+        match parents.first() {
+            Some(first) => self.columns[column] = first.clone(),
+            None => {
+                // The lane ends here and the ones to its right move left
+                self.columns.remove(column);
+                if column < self.columns.len() {
+                    let mut row: Vec<char> = shift_row(width, column + 1, '/').chars().collect();
+                    row[2 * column] = ' ';
+                    transitions.push(row.into_iter().collect());
+                }
+            }
+        }
+
+        // Other parents of a merge open new lanes next to this one
+        let mut opened = 0;
+        for parent in parents.iter().skip(1) {
+            if !self.columns.contains(parent) {
+                opened += 1;
+                self.columns.insert(column + opened, parent.clone());
+            }
+        }
+        if opened > 0 {
+            transitions.push(shift_row(self.columns.len(), column + 1, '\\'));
+        }
+
+        // Two lanes waiting for the same commit join into the leftmost one
+        while let Some(duplicate) =
+            (0..self.columns.len()).find(|&i| self.columns[..i].contains(&self.columns[i]))
+        {
+            let width = self.columns.len();
+            self.columns.remove(duplicate);
+            transitions.push(shift_row(width, duplicate, '/'));
+        }
+
+        (commit_row.into_iter().collect(), transitions)
+    }
+
+    /// Prefix for the extra lines of a commit: every lane carries on.
+    fn continuation(&self) -> String {
+        lane_row(self.columns.len().max(1)).into_iter().collect()
+    }
+}
+
+/// `| | |` for `width` lanes.
+fn lane_row(width: usize) -> Vec<char> {
+    let mut row = vec![' '; (2 * width).saturating_sub(1)];
+    for i in 0..width {
+        row[2 * i] = '|';
+    }
+    row
+}
+
+/// Keeps the lanes left of `from` straight and draws `slant` where every
+/// lane from `from` on moves one step sideways. `from` is at least 1.
+fn shift_row(width: usize, from: usize, slant: char) -> String {
+    let mut row = vec![' '; 2 * width];
+    for i in 0..width {
+        if i < from {
+            row[2 * i] = '|';
+        } else {
+            row[2 * i - 1] = slant;
+        }
+    }
+    row.into_iter().collect::<String>().trim_end().to_string()
+}
+
+/// Days since 1970-01-01 for a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Inverse of `days_from_civil`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// Formats a timestamp like Git's default date format, in the zone `offset`
+/// minutes east of UTC: `Sat Oct 17 15:59:27 2026 +0200`.
+pub fn format_timestamp(timestamp: u64, offset: i32) -> String {
+    const WEEKDAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let local = timestamp as i64 + offset as i64 * 60;
+    let days = local.div_euclid(86400);
+    let seconds = local.rem_euclid(86400);
+    let (year, month, day) = civil_from_days(days);
+    let sign = if offset < 0 { '-' } else { '+' };
+    format!(
+        "{} {} {} {:02}:{:02}:{:02} {} {}{:02}{:02}",
+        WEEKDAYS[days.rem_euclid(7) as usize],
+        MONTHS[month as usize - 1],
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        year,
+        sign,
+        offset.abs() / 60,
+        offset.abs() % 60
+    )
+}
+
+/// Minutes east of UTC of the local time zone at `timestamp`, read from the
+/// zoneinfo file named by `TZ` or from `/etc/localtime`. UTC if unknown.
+pub fn local_utc_offset(timestamp: u64) -> i32 {
+    let path = match env::var("TZ") {
+        Ok(zone) if !zone.is_empty() => {
+            let zone = zone.trim_start_matches(':');
+            Path::new("/usr/share/zoneinfo").join(zone)
+        }
+        _ => PathBuf::from("/etc/localtime"),
+    };
+    fs::read(path)
+        .ok()
+        .and_then(|data| zoneinfo_offset(&data, timestamp as i64))
+        .unwrap_or(0)
+}
+
+/// Looks `timestamp` up in a TZif file: the offset of the last transition
+/// before it, or of the first local time type if there is none.
+fn zoneinfo_offset(data: &[u8], timestamp: i64) -> Option<i32> {
+    let read_u32 = |at: usize| -> Option<usize> {
+        Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?) as usize)
+    };
+    if data.get(..4)? != b"TZif" {
+        return None;
+    }
+
+    // Version 2 files repeat the data with 64-bit times after the 32-bit block
+    let mut header = 0;
+    let mut time_size = 4;
+    if *data.get(4)? >= b'2' {
+        let counts: Vec<usize> = (0..6)
+            .map(|i| read_u32(20 + 4 * i))
+            .collect::<Option<_>>()?;
+        let [utc, std, leap, times, types, chars] = counts[..] else {
+            return None;
+        };
+        header = 44 + times * 5 + types * 6 + chars + leap * 8 + std + utc;
+        time_size = 8;
+    }
+
+    let count = |i: usize| read_u32(header + 20 + 4 * i);
+    let (transitions, types) = (count(3)?, count(4)?);
+    let times = header + 44;
+    let indices = times + transitions * time_size;
+    let infos = indices + transitions;
+
+    let mut local_type = 0;
+    for i in 0..transitions {
+        let at = times + i * time_size;
+        let time = if time_size == 8 {
+            i64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?)
+        } else {
+            i32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?) as i64
+        };
+        if time > timestamp {
+            break;
+        }
+        local_type = *data.get(indices + i)? as usize;
+    }
+    if local_type >= types {
+        return None;
+    }
+
+    let at = infos + local_type * 6;
+    let seconds = i32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?);
+    Some(seconds / 60)
+}
+
+/// Parses the dates `--since` and `--until` accept, in UTC: a unix
+/// timestamp (optionally prefixed with `@`), `YYYY-MM-DD`,
+/// `YYYY-MM-DD HH:MM[:SS]`, `now`, `yesterday` or `<n> <unit>s ago`.
+pub fn parse_date(text: &str, now: u64) -> Result<u64> {
+    let invalid = || Error::new(ErrorKind::InvalidInput, format!("Invalid date: {}", text));
+    let text = text.trim();
+
+    if let Ok(timestamp) = text.strip_prefix('@').unwrap_or(text).parse::<u64>() {
+        return Ok(timestamp);
+    }
+    match text {
+        "now" => return Ok(now),
+        "yesterday" => return Ok(now.saturating_sub(86400)),
+        _ => {}
+    }
+
+    // Relative dates such as `2 weeks ago` or `3.days.ago`
+    let words: Vec<&str> = text.split([' ', '.']).filter(|w| !w.is_empty()).collect();
+    if let [count, unit, "ago"] = words.as_slice() {
+        let count: u64 = count.parse().map_err(|_| invalid())?;
+        let seconds = match unit.trim_end_matches('s') {
+            "second" => 1,
+            "minute" => 60,
+            "hour" => 3600,
+            "day" => 86400,
+            "week" => 7 * 86400,
+            "month" => 30 * 86400,
+            "year" => 365 * 86400,
+            _ => return Err(invalid()),
+        };
+        return Ok(now.saturating_sub(count * seconds));
+    }
+
+    // Absolute dates, with an optional time of day
+    let (date, time) = text.split_once([' ', 'T']).unwrap_or((text, "00:00:00"));
+    let date: Vec<u32> = date
+        .split('-')
+        .map(|part| part.parse().map_err(|_| invalid()))
+        .collect::<Result<_>>()?;
+    let time: Vec<u64> = time
+        .split(':')
+        .map(|part| part.parse().map_err(|_| invalid()))
+        .collect::<Result<_>>()?;
+    let ([year, month, day], [hours, minutes, rest @ ..]) = (date.as_slice(), time.as_slice())
+    else {
+        return Err(invalid());
+    };
+    if !(1..=12).contains(month) || !(1..=31).contains(day) || rest.len() > 1 {
+        return Err(invalid());
+    }
+
+    let days = days_from_civil(*year as i64, *month, *day);
+    let seconds = hours * 3600 + minutes * 60 + rest.first().copied().unwrap_or(0);
+    u64::try_from(days * 86400)
+        .map(|start| start + seconds)
+        .map_err(|_| invalid())
+}
@@ -1,9 +1,10 @@
 #![allow(unused)]
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::path::PathBuf;
 
 use super::blob::Blob;
+use super::diff::{diff_lines, split_lines, DiffAlgorithm, Edit};
 
 #[derive(Debug)]
 pub enum MergeConflict {
@@ -19,12 +20,203 @@ pub enum MergeConflict {
     },
 }
 
+impl MergeConflict {
+    pub fn file_path(&self) -> &PathBuf {
+        match self {
+            MergeConflict::ContentConflict { file_path, .. } => file_path,
+            MergeConflict::FileConflict { file_path, .. } => file_path,
+        }
+    }
+
+    /// One-line summary in the form Git prints while merging.
+    pub fn describe(&self) -> String {
+        match self {
+            MergeConflict::ContentConflict { file_path, .. } => {
+                format!(
+                    "CONFLICT (content): Merge conflict in {}",
+                    file_path.display()
+                )
+            }
+            MergeConflict::FileConflict {
+                file_path,
+                conflict_type: FileConflictType::DeletedInOneBranch,
+            } => format!(
+                "CONFLICT (modify/delete): {} deleted in one branch and modified in the other",
+                file_path.display()
+            ),
+            MergeConflict::FileConflict {
+                file_path,
+                conflict_type: FileConflictType::AddedInBothBranches,
+            } => format!(
+                "CONFLICT (add/add): Merge conflict in {}",
+                file_path.display()
+            ),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum FileConflictType {
     DeletedInOneBranch,
     AddedInBothBranches,
 }
 
+/// Names written after the conflict markers of a merged file.
+#[derive(Clone, Copy, Debug)]
+pub struct MergeLabels<'a> {
+    pub base: &'a str,
+    pub branch1: &'a str,
+    pub branch2: &'a str,
+}
+
+/// The merged files, including conflicted ones. A conflicted file holds the
+/// conflict markers, or the surviving version when it was deleted on one side.
+#[derive(Debug)]
+pub struct MergeOutcome {
+    pub tree: HashMap<PathBuf, Vec<u8>>,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+/// Whether a merge may just move the current branch forward.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FastForwardMode {
+    /// Fast-forward when possible, otherwise create a merge commit
+    #[default]
+    Allow,
+    /// Always create a merge commit (`--no-ff`)
+    Never,
+    /// Refuse anything but a fast-forward (`--ff-only`)
+    Only,
+}
+
+/// What a merge did to the current branch.
+#[derive(Debug, PartialEq, Eq)]
+pub enum MergeStatus {
+    /// The other branch was already part of the current one
+    UpToDate,
+    /// The current branch was moved forward to this commit
+    FastForward(String),
+    /// A merge commit with this id was created
+    Merged(String),
+}
+
+/// Result of merging the lines of a single file.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LineMerge {
+    pub content: Vec<u8>,
+    /// Number of overlapping changes written out between conflict markers
+    pub conflicts: usize,
+}
+
+/// diff3-style merge of two versions of a file against their common base.
+///
+/// Both versions are diffed against the base. Runs of base lines that both
+/// diffs keep split the file into chunks; a chunk changed on one side only
+/// takes that side, and a chunk changed differently on both sides becomes a
+/// conflict.
+pub fn merge_lines(base: &[u8], branch1: &[u8], branch2: &[u8], labels: MergeLabels) -> LineMerge {
+    // Nothing to line up when at most one side changed
+    if branch1 == base || branch1 == branch2 {
+        return LineMerge {
+            content: branch2.to_vec(),
+            conflicts: 0,
+        };
+    }
+    if branch2 == base {
+        return LineMerge {
+            content: branch1.to_vec(),
+            conflicts: 0,
+        };
+    }
+
+    let base_lines = split_lines(base);
+    let lines1 = split_lines(branch1);
+    let lines2 = split_lines(branch2);
+    let matches1 = base_matches(&base_lines, &lines1);
+    let matches2 = base_matches(&base_lines, &lines2);
+
+    let mut content = Vec::new();
+    let mut conflicts = 0;
+    let (mut o, mut a, mut b) = (0, 0, 0);
+    loop {
+        // Lines unchanged on both sides
+        while o < base_lines.len() && matches1[o] == Some(a) && matches2[o] == Some(b) {
+            content.extend_from_slice(base_lines[o]);
+            o += 1;
+            a += 1;
+            b += 1;
+        }
+        if o == base_lines.len() && a == lines1.len() && b == lines2.len() {
+            break;
+        }
+
+        // The changed chunk ends at the next base line both sides kept
+        let next = (o..base_lines.len()).find_map(|i| Some((i, matches1[i]?, matches2[i]?)));
+        let (end_o, end_a, end_b) = next.unwrap_or((base_lines.len(), lines1.len(), lines2.len()));
+
+        let chunk_base = &base_lines[o..end_o];
+        let chunk1 = &lines1[a..end_a];
+        let chunk2 = &lines2[b..end_b];
+        if chunk1 == chunk_base || chunk1 == chunk2 {
+            content.extend(chunk2.concat());
+        } else if chunk2 == chunk_base {
+            content.extend(chunk1.concat());
+        } else {
+            conflicts += 1;
+            write_conflict(&mut content, chunk_base, chunk1, chunk2, labels);
+        }
+
+        o = end_o;
+        a = end_a;
+        b = end_b;
+    }
+
+    LineMerge { content, conflicts }
+}
+
+/// For each base line, the line of `other` it was kept as, if any.
+fn base_matches(base: &[&[u8]], other: &[&[u8]]) -> Vec<Option<usize>> {
+    let mut matches = vec![None; base.len()];
+    let (mut i, mut j) = (0, 0);
+    for edit in diff_lines(base, other, DiffAlgorithm::Myers) {
+        match edit {
+            Edit::Equal => {
+                matches[i] = Some(j);
+                i += 1;
+                j += 1;
+            }
+            Edit::Delete => i += 1,
+            Edit::Insert => j += 1,
+        }
+    }
+    matches
+}
+
+fn write_conflict(
+    content: &mut Vec<u8>,
+    base: &[&[u8]],
+    branch1: &[&[u8]],
+    branch2: &[&[u8]],
+    labels: MergeLabels,
+) {
+    let mut section = |marker: &str, lines: &[&[u8]]| {
+        content.extend_from_slice(marker.as_bytes());
+        content.push(b'\n');
+        for line in lines {
+            content.extend_from_slice(line);
+        }
+        // Keep the next marker on its own line
+        if lines.last().is_some_and(|line| !line.ends_with(b"\n")) {
+            content.push(b'\n');
+        }
+    };
+
+    section(&format!("<<<<<<< {}", labels.branch1), branch1);
+    section(&format!("||||||| {}", labels.base), base);
+    section("=======", branch2);
+    content.extend_from_slice(format!(">>>>>>> {}\n", labels.branch2).as_bytes());
+}
+
 pub struct Merger;
 
 impl Merger {
@@ -32,13 +224,17 @@ impl Merger {
         base_tree: &HashMap<PathBuf, Vec<u8>>,
         branch1_tree: &HashMap<PathBuf, Vec<u8>>,
         branch2_tree: &HashMap<PathBuf, Vec<u8>>,
-    ) -> Result<HashMap<PathBuf, Vec<u8>>, Vec<MergeConflict>> {
-        let mut merged_tree = base_tree.clone();
+        labels: MergeLabels,
+    ) -> MergeOutcome {
+        let mut merged_tree = HashMap::new();
         let mut conflicts = Vec::new();
 
-        // Check all files in both branches
-        let all_paths: std::collections::HashSet<_> =
-            branch1_tree.keys().chain(branch2_tree.keys()).collect();
+        // Check every path known to any of the three trees
+        let all_paths: BTreeSet<_> = base_tree
+            .keys()
+            .chain(branch1_tree.keys())
+            .chain(branch2_tree.keys())
+            .collect();
 
         for path in all_paths {
             match (
@@ -46,7 +242,7 @@ impl Merger {
                 branch1_tree.get(path),
                 branch2_tree.get(path),
             ) {
-                // File deleted in base, added in one branch
+                // File added in one branch
                 (None, Some(b1_content), None) => {
                     merged_tree.insert(path.clone(), b1_content.clone());
                 }
@@ -54,54 +250,49 @@ impl Merger {
                     merged_tree.insert(path.clone(), b2_content.clone());
                 }
 
-                // File exists in base and both branches
-                (Some(base_content), Some(b1_content), Some(b2_content)) => {
-                    if b1_content == b2_content {
-                        // Same content in both branches
-                        merged_tree.insert(path.clone(), b1_content.clone());
-                    } else if b1_content == base_content {
-                        // No change in first branch, use second branch content
-                        merged_tree.insert(path.clone(), b2_content.clone());
-                    } else if b2_content == base_content {
-                        // No change in second branch, use first branch content
-                        merged_tree.insert(path.clone(), b1_content.clone());
-                    } else {
-                        // Conflicting modifications
-                        conflicts.push(MergeConflict::ContentConflict {
-                            file_path: path.clone(),
-                            base_content: base_content.clone(),
-                            branch1_content: b1_content.clone(),
-                            branch2_content: b2_content.clone(),
+                // File exists in both branches, merged line by line. A file
+                // added on both sides is merged against an empty base.
+                (base_content, Some(b1_content), Some(b2_content)) => {
+                    let base_content = base_content.map(Vec::as_slice).unwrap_or_default();
+                    let merged = merge_lines(base_content, b1_content, b2_content, labels);
+                    if merged.conflicts > 0 {
+                        conflicts.push(if base_tree.contains_key(path) {
+                            MergeConflict::ContentConflict {
+                                file_path: path.clone(),
+                                base_content: base_content.to_vec(),
+                                branch1_content: b1_content.clone(),
+                                branch2_content: b2_content.clone(),
+                            }
+                        } else {
+                            MergeConflict::FileConflict {
+                                file_path: path.clone(),
+                                conflict_type: FileConflictType::AddedInBothBranches,
+                            }
                         });
                     }
+                    merged_tree.insert(path.clone(), merged.content);
                 }
 
-                // Conflict scenarios
-                (Some(_), Some(_), None) | (Some(_), None, Some(_)) => {
-                    conflicts.push(MergeConflict::FileConflict {
-                        file_path: path.clone(),
-                        conflict_type: FileConflictType::DeletedInOneBranch,
-                    });
-                }
-                (None, Some(_), Some(_)) => {
-                    conflicts.push(MergeConflict::FileConflict {
-                        file_path: path.clone(),
-                        conflict_type: FileConflictType::AddedInBothBranches,
-                    });
-                }
-                (None, None, None) => {
-                    println!("Triple None case")
+                // Deleted in one branch: clean unless the other one changed it
+                (Some(base_content), Some(kept), None) | (Some(base_content), None, Some(kept)) => {
+                    if kept != base_content {
+                        conflicts.push(MergeConflict::FileConflict {
+                            file_path: path.clone(),
+                            conflict_type: FileConflictType::DeletedInOneBranch,
+                        });
+                        merged_tree.insert(path.clone(), kept.clone());
+                    }
                 }
-                (Some(_), None, None) => {
-                    println!("Impossible case")
-                }, // Impossible case
+
+                // Deleted in both branches
+                (Some(_), None, None) => {}
+                (None, None, None) => unreachable!("path comes from one of the trees"),
             }
         }
 
-        if conflicts.is_empty() {
-            Ok(merged_tree)
-        } else {
-            Err(conflicts)
+        MergeOutcome {
+            tree: merged_tree,
+            conflicts,
         }
     }
 }
@@ -187,4 +378,59 @@ impl Merger {
 //             Err(conflicts)
 //         }
 //     }
-// }
\ No newline at end of file
+// }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LABELS: MergeLabels = MergeLabels {
+        base: "base",
+        branch1: "ours",
+        branch2: "theirs",
+    };
+
+    #[test]
+    fn takes_the_only_side_that_changed() {
+        let merged = merge_lines(b"a\nb\n", b"a\nb\n", b"a\nB\n", LABELS);
+        assert_eq!(merged.content, b"a\nB\n");
+        assert_eq!(merged.conflicts, 0);
+
+        let merged = merge_lines(b"a\nb\n", b"A\nb\n", b"a\nb\n", LABELS);
+        assert_eq!(merged.content, b"A\nb\n");
+    }
+
+    #[test]
+    fn combines_changes_to_separate_lines() {
+        let base = b"1\n2\n3\n4\n5\n";
+        let merged = merge_lines(base, b"ONE\n2\n3\n4\n5\n", b"1\n2\n3\n4\nFIVE\n", LABELS);
+        assert_eq!(merged.content, b"ONE\n2\n3\n4\nFIVE\n");
+        assert_eq!(merged.conflicts, 0);
+    }
+
+    #[test]
+    fn accepts_the_same_change_on_both_sides() {
+        let merged = merge_lines(b"a\nb\nc\n", b"a\nX\nc\nd\n", b"a\nX\nc\n", LABELS);
+        assert_eq!(merged.content, b"a\nX\nc\nd\n");
+        assert_eq!(merged.conflicts, 0);
+    }
+
+    #[test]
+    fn marks_overlapping_changes_as_a_conflict() {
+        let merged = merge_lines(b"a\nb\nc\n", b"a\nours\nc\n", b"a\ntheirs\nc\n", LABELS);
+        assert_eq!(merged.conflicts, 1);
+        assert_eq!(
+            String::from_utf8(merged.content).unwrap(),
+            "a\n<<<<<<< ours\nours\n||||||| base\nb\n=======\ntheirs\n>>>>>>> theirs\nc\n"
+        );
+    }
+
+    #[test]
+    fn keeps_conflict_markers_on_their_own_lines() {
+        let merged = merge_lines(b"a", b"b", b"c", LABELS);
+        assert_eq!(
+            String::from_utf8(merged.content).unwrap(),
+            "<<<<<<< ours\nb\n||||||| base\na\n=======\nc\n>>>>>>> theirs\n"
+        );
+    }
+}
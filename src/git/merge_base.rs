@@ -0,0 +1,215 @@
+#![allow(unused)]
+
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::io::Result;
+
+use crate::git::commit::Commit;
+use crate::git::object_store::ObjectStore;
+
+/// The parents of the commits a walk has visited, so that each commit is read
+/// from the store once however many walks reach it.
+struct Parents<'a> {
+    store: &'a ObjectStore,
+    cache: HashMap<String, Vec<String>>,
+}
+
+impl<'a> Parents<'a> {
+    fn new(store: &'a ObjectStore) -> Self {
+        Parents {
+            store,
+            cache: HashMap::new(),
+        }
+    }
+
+    fn of(&mut self, id: &str) -> Result<&[String]> {
+        if !self.cache.contains_key(id) {
+            let parents = Commit::read(self.store, id)?.parents().to_vec();
+            self.cache.insert(id.to_string(), parents);
+        }
+        Ok(&self.cache[id])
+    }
+
+    /// Every commit reachable from `starts`, stopping early once `until`
+    /// returns true for one of them.
+    fn walk(
+        &mut self,
+        starts: impl IntoIterator<Item = String>,
+        until: impl Fn(&str) -> bool,
+    ) -> Result<HashSet<String>> {
+        let mut seen = HashSet::new();
+        let mut queue: VecDeque<String> = starts.into_iter().collect();
+        while let Some(id) = queue.pop_front() {
+            if !seen.insert(id.clone()) {
+                continue;
+            }
+            if until(&id) {
+                break;
+            }
+            queue.extend(self.of(&id)?.iter().cloned());
+        }
+        Ok(seen)
+    }
+}
+
+/// Every commit reachable from `start` through parent links, `start` included.
+pub fn ancestors(store: &ObjectStore, start: &str) -> Result<HashSet<String>> {
+    Parents::new(store).walk([start.to_string()], |_| false)
+}
+
+/// Finds the best common ancestors of two commits: the commits reachable
+/// from both that are not an ancestor of another such commit.
+///
+/// There is usually one. Criss-cross histories, where each side merged the
+/// other, can have several equally good ones.
+pub fn merge_bases(store: &ObjectStore, a: &str, b: &str) -> Result<Vec<String>> {
+    let mut parents = Parents::new(store);
+    let from_a = parents.walk([a.to_string()], |_| false)?;
+    let common: BTreeSet<String> = parents
+        .walk([b.to_string()], |_| false)?
+        .into_iter()
+        .filter(|id| from_a.contains(id))
+        .collect();
+
+    // Anything below another common ancestor is not a best one
+    let mut below = Vec::new();
+    for id in &common {
+        below.extend(parents.of(id)?.iter().cloned());
+    }
+    let redundant = parents.walk(below, |_| false)?;
+
+    Ok(common
+        .into_iter()
+        .filter(|id| !redundant.contains(id))
+        .collect())
+}
+
+/// Whether `ancestor` can be reached from `descendant`, counting a commit as
+/// its own ancestor.
+pub fn is_ancestor(store: &ObjectStore, ancestor: &str, descendant: &str) -> Result<bool> {
+    let reached = Parents::new(store).walk([descendant.to_string()], |id| id == ancestor)?;
+    Ok(reached.contains(ancestor))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::git::commit::Signature;
+    use crate::git::hash::HashAlgorithm;
+    use crate::git::object_store::ObjectKind;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// A scratch store to build commit graphs in.
+    struct Graph {
+        dir: PathBuf,
+        store: ObjectStore,
+        tree: String,
+    }
+
+    impl Graph {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "git-scm-merge-base-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = fs::remove_dir_all(&dir);
+            fs::create_dir_all(&dir).unwrap();
+            let store = ObjectStore::new(&dir, HashAlgorithm::Sha1);
+            let tree = store.write(ObjectKind::Tree, &[]).unwrap();
+            Graph { dir, store, tree }
+        }
+
+        fn commit(&self, message: &str, parents: &[&str]) -> String {
+            let signature = Signature::new("Test".into(), "test@example.com".into(), 0, 0);
+            let commit = Commit::new(
+                parents.iter().map(|id| id.to_string()).collect(),
+                self.tree.clone(),
+                format!("{}\n", message),
+                signature.clone(),
+                signature,
+                HashAlgorithm::Sha1,
+            );
+            self.store
+                .write(ObjectKind::Commit, &commit.serialize())
+                .unwrap()
+        }
+
+        fn bases(&self, a: &str, b: &str) -> Vec<String> {
+            let mut bases = merge_bases(&self.store, a, b).unwrap();
+            bases.sort();
+            bases
+        }
+    }
+
+    impl Drop for Graph {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn finds_the_older_commit_of_a_linear_history() {
+        let graph = Graph::new("linear");
+        let a = graph.commit("a", &[]);
+        let b = graph.commit("b", &[&a]);
+        let c = graph.commit("c", &[&b]);
+
+        assert_eq!(graph.bases(&a, &c), vec![a.clone()]);
+        assert_eq!(graph.bases(&c, &b), vec![b.clone()]);
+        assert_eq!(graph.bases(&c, &c), vec![c.clone()]);
+        assert!(is_ancestor(&graph.store, &a, &c).unwrap());
+        assert!(is_ancestor(&graph.store, &c, &c).unwrap());
+        assert!(!is_ancestor(&graph.store, &c, &a).unwrap());
+        assert_eq!(
+            ancestors(&graph.store, &c).unwrap(),
+            HashSet::from([a, b, c])
+        );
+    }
+
+    #[test]
+    fn finds_the_fork_point_of_two_branches() {
+        let graph = Graph::new("fork");
+        let root = graph.commit("root", &[]);
+        let fork = graph.commit("fork", &[&root]);
+        let left = graph.commit("left", &[&fork]);
+        let left = graph.commit("left again", &[&left]);
+        let right = graph.commit("right", &[&fork]);
+
+        assert_eq!(graph.bases(&left, &right), vec![fork.clone()]);
+        assert_eq!(graph.bases(&right, &left), vec![fork.clone()]);
+        assert!(!is_ancestor(&graph.store, &left, &right).unwrap());
+        assert!(!is_ancestor(&graph.store, &right, &left).unwrap());
+
+        // Once merged, the side merged in is the base
+        let merge = graph.commit("merge", &[&left, &right]);
+        assert_eq!(graph.bases(&merge, &right), vec![right]);
+    }
+
+    #[test]
+    fn finds_both_bases_of_a_criss_cross_merge() {
+        let graph = Graph::new("criss-cross");
+        let root = graph.commit("root", &[]);
+        let x = graph.commit("x", &[&root]);
+        let y = graph.commit("y", &[&root]);
+        // Each side merges the other
+        let left = graph.commit("left", &[&x, &y]);
+        let right = graph.commit("right", &[&y, &x]);
+
+        let mut expected = vec![x, y];
+        expected.sort();
+        assert_eq!(graph.bases(&left, &right), expected);
+    }
+
+    #[test]
+    fn finds_no_base_for_unrelated_histories() {
+        let graph = Graph::new("unrelated");
+        let a = graph.commit("a", &[]);
+        let a = graph.commit("a again", &[&a]);
+        let b = graph.commit("b", &[]);
+
+        assert!(graph.bases(&a, &b).is_empty());
+        assert!(!is_ancestor(&graph.store, &a, &b).unwrap());
+        assert!(!is_ancestor(&graph.store, &b, &a).unwrap());
+    }
+}
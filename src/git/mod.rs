@@ -1,7 +1,25 @@
 pub(crate) mod blob;
 pub(crate) mod branch;
 pub(crate) mod commit;
+pub(crate) mod config;
+pub(crate) mod delta;
 pub(crate) mod diff;
+pub(crate) mod hash;
+pub(crate) mod http;
+pub(crate) mod ignore;
 pub(crate) mod index;
+pub(crate) mod log;
 pub(crate) mod merge;
+pub(crate) mod merge_base;
+pub(crate) mod object_store;
+pub(crate) mod pack;
+pub(crate) mod pkt_line;
+pub(crate) mod protocol;
+pub(crate) mod remote;
 pub(crate) mod repository;
+pub(crate) mod revision;
+pub(crate) mod service;
+pub(crate) mod status;
+pub(crate) mod storage;
+pub(crate) mod tree;
+pub(crate) mod worktree;
@@ -0,0 +1,503 @@
+#![allow(unused)]
+
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::git::hash::HashAlgorithm;
+use crate::git::pack::{pack_data, unpack, write_pack, Pack, PackObject};
+use crate::git::tree::Tree;
+
+/// Received packs with fewer objects than this are stored as loose objects.
+const UNPACK_LIMIT: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ObjectKind {
+    Blob,
+    Tree,
+    Commit,
+    /// An annotated tag, only read from repositories Git wrote
+    Tag,
+}
+
+impl ObjectKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ObjectKind::Blob => "blob",
+            ObjectKind::Tree => "tree",
+            ObjectKind::Commit => "commit",
+            ObjectKind::Tag => "tag",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "blob" => Some(ObjectKind::Blob),
+            "tree" => Some(ObjectKind::Tree),
+            "commit" => Some(ObjectKind::Commit),
+            "tag" => Some(ObjectKind::Tag),
+            _ => None,
+        }
+    }
+}
+
+/// Frames an object the way Git does: `<kind> <length>\0<data>`.
+pub fn frame_object(kind: ObjectKind, data: &[u8]) -> Vec<u8> {
+    let mut framed = format!("{} {}\0", kind.as_str(), data.len()).into_bytes();
+    framed.extend_from_slice(data);
+    framed
+}
+
+/// Computes the id an object of the given kind and content is stored under.
+pub fn hash_object(algorithm: HashAlgorithm, kind: ObjectKind, data: &[u8]) -> String {
+    algorithm.hex_digest(&frame_object(kind, data))
+}
+
+/// Loose object database living in `.git-scm/objects`.
+///
+/// Every object is written once under `objects/<first two chars>/<rest of id>`
+/// and prefixed with a `<kind> <length>\0` header. The id is the digest of
+/// that framed content, so it only depends on what is stored.
+///
+/// Git zlib-compresses every loose object; stores created with
+/// [`ObjectStore::compressed`] do too, and compressed objects are read back
+/// whichever way the store writes.
+///
+/// Objects not found loose are looked up in the packs under `objects/pack`,
+/// which [`ObjectStore::repack`] writes.
+#[derive(Debug)]
+pub struct ObjectStore {
+    objects_dir: PathBuf,
+    algorithm: HashAlgorithm,
+    compress: bool,
+    /// The packs' indexes, read when first needed
+    packs: RefCell<Option<Vec<Pack>>>,
+}
+
+/// What a repack did.
+#[derive(Debug)]
+pub struct RepackSummary {
+    /// Checksum naming the new pack, if there was anything to pack
+    pub pack: Option<String>,
+    pub objects: usize,
+    /// Loose objects and old packs removed because the new pack holds them
+    pub removed_loose: usize,
+    pub removed_packs: usize,
+}
+
+impl ObjectStore {
+    pub fn new(git_dir: &Path, algorithm: HashAlgorithm) -> Self {
+        ObjectStore {
+            objects_dir: git_dir.join("objects"),
+            algorithm,
+            compress: false,
+            packs: RefCell::new(None),
+        }
+    }
+
+    /// Makes the store write zlib-compressed objects, as Git does.
+    pub fn compressed(mut self) -> Self {
+        self.compress = true;
+        self
+    }
+
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    fn object_path(&self, id: &str) -> Result<PathBuf> {
+        if id.len() != self.algorithm.hex_len() || !id.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid object id: {}", id),
+            ));
+        }
+        let (dir, file) = id.split_at(2);
+        Ok(self.objects_dir.join(dir).join(file))
+    }
+
+    pub fn exists(&self, id: &str) -> bool {
+        self.object_path(id).map(|p| p.is_file()).unwrap_or(false)
+            || self
+                .with_packs(|packs| packs.iter().any(|pack| pack.contains(id)))
+                .unwrap_or(false)
+    }
+
+    /// Runs `f` on the packs, reading their indexes on first use.
+    fn with_packs<T>(&self, f: impl FnOnce(&[Pack]) -> T) -> Result<T> {
+        if self.packs.borrow().is_none() {
+            let mut packs = Vec::new();
+            let pack_dir = self.objects_dir.join("pack");
+            if pack_dir.is_dir() {
+                let mut idx_paths: Vec<PathBuf> = fs::read_dir(&pack_dir)?
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .collect::<Result<_>>()?;
+                idx_paths.retain(|path| path.extension().is_some_and(|ext| ext == "idx"));
+                idx_paths.sort();
+                for idx_path in idx_paths {
+                    packs.push(Pack::open(&idx_path, self.algorithm)?);
+                }
+            }
+            *self.packs.borrow_mut() = Some(packs);
+        }
+        Ok(f(self.packs.borrow().as_deref().unwrap_or_default()))
+    }
+
+    pub fn write(&self, kind: ObjectKind, data: &[u8]) -> Result<String> {
+        let contents = frame_object(kind, data);
+        let id = self.algorithm.hex_digest(&contents);
+        let object_path = self.object_path(&id)?;
+
+        // Identical content is already stored under the same id
+        if self.exists(&id) {
+            return Ok(id);
+        }
+
+        if let Some(parent) = object_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Write to a temporary file first so readers never see a partial object
+        let tmp_path = object_path.with_extension("tmp");
+        if self.compress {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&contents)?;
+            fs::write(&tmp_path, encoder.finish()?)?;
+        } else {
+            fs::write(&tmp_path, contents)?;
+        }
+        fs::rename(&tmp_path, &object_path)?;
+
+        Ok(id)
+    }
+
+    /// Lists the ids of stored objects starting with `prefix`, which needs at
+    /// least the two characters naming the fan-out directory.
+    pub fn find_by_prefix(&self, prefix: &str) -> Result<Vec<String>> {
+        let prefix = prefix.to_ascii_lowercase();
+        if prefix.len() < 2 || !prefix.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Ok(Vec::new());
+        }
+
+        let (dir, rest) = prefix.split_at(2);
+        let dir_path = self.objects_dir.join(dir);
+        let mut ids = Vec::new();
+        if dir_path.is_dir() {
+            for entry in fs::read_dir(dir_path)? {
+                let name = entry?.file_name().to_string_lossy().to_string();
+                let id = format!("{}{}", dir, name);
+                if name.starts_with(rest) && id.len() == self.algorithm.hex_len() {
+                    ids.push(id);
+                }
+            }
+        }
+        self.with_packs(|packs| {
+            for pack in packs {
+                ids.extend(pack.find_by_prefix(&prefix));
+            }
+        })?;
+        ids.sort();
+        ids.dedup();
+        Ok(ids)
+    }
+
+    pub fn read(&self, id: &str) -> Result<(ObjectKind, Vec<u8>)> {
+        let object_path = self.object_path(id)?;
+        if !object_path.is_file() {
+            return self.read_packed(id)?.ok_or_else(|| {
+                Error::new(ErrorKind::NotFound, format!("Object not found: {}", id))
+            });
+        }
+
+        let mut contents = fs::read(&object_path)?;
+        // A framed object starts with its kind, a zlib stream with 0x78
+        if contents.first() == Some(&0x78) {
+            let mut inflated = Vec::new();
+            ZlibDecoder::new(contents.as_slice()).read_to_end(&mut inflated)?;
+            contents = inflated;
+        }
+        let header_end = contents
+            .iter()
+            .position(|&b| b == 0)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Malformed object header"))?;
+        let header = String::from_utf8_lossy(&contents[..header_end]).to_string();
+
+        let (kind, len) = header
+            .split_once(' ')
+            .and_then(|(kind, len)| Some((ObjectKind::parse(kind)?, len.parse::<usize>().ok()?)))
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Malformed object header"))?;
+
+        let data = contents.split_off(header_end + 1);
+        if data.len() != len {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Object {} is truncated", id),
+            ));
+        }
+
+        Ok((kind, data))
+    }
+
+    /// Reads an object and checks that it has the expected kind.
+    pub fn read_kind(&self, id: &str, expected: ObjectKind) -> Result<Vec<u8>> {
+        let (kind, data) = self.read(id)?;
+        if kind != expected {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "Object {} is a {}, not a {}",
+                    id,
+                    kind.as_str(),
+                    expected.as_str()
+                ),
+            ));
+        }
+        Ok(data)
+    }
+
+    fn read_packed(&self, id: &str) -> Result<Option<(ObjectKind, Vec<u8>)>> {
+        self.with_packs(|packs| {
+            for pack in packs {
+                if let Some(object) = pack.read(id, &|base| self.read(base))? {
+                    return Ok(Some(object));
+                }
+            }
+            Ok(None)
+        })?
+    }
+
+    /// Ids of the objects stored loose, in sorted order.
+    pub fn loose_ids(&self) -> Result<Vec<String>> {
+        let mut ids = Vec::new();
+        if !self.objects_dir.is_dir() {
+            return Ok(ids);
+        }
+        for dir in fs::read_dir(&self.objects_dir)? {
+            let dir = dir?;
+            let dir_name = dir.file_name().to_string_lossy().to_string();
+            if dir_name.len() != 2 || !dir.file_type()?.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(dir.path())? {
+                let id = format!("{}{}", dir_name, entry?.file_name().to_string_lossy());
+                if self.object_path(&id).is_ok() {
+                    ids.push(id);
+                }
+            }
+        }
+        ids.sort();
+        Ok(ids)
+    }
+
+    /// Packs the loose objects, and with `all` the objects of existing packs
+    /// too, into a single new pack, then removes what it made redundant.
+    pub fn repack(&self, all: bool) -> Result<RepackSummary> {
+        let loose = self.loose_ids()?;
+        let mut ids: BTreeSet<String> = loose.iter().cloned().collect();
+        let old_packs: Vec<PathBuf> = if all {
+            self.with_packs(|packs| {
+                for pack in packs {
+                    ids.extend(pack.ids());
+                }
+                packs.iter().map(|pack| pack.path().to_path_buf()).collect()
+            })?
+        } else {
+            Vec::new()
+        };
+
+        let objects = self.pack_objects(ids)?;
+        let mut summary = RepackSummary {
+            pack: None,
+            objects: objects.len(),
+            removed_loose: 0,
+            removed_packs: 0,
+        };
+        if objects.is_empty() {
+            return Ok(summary);
+        }
+        let name = write_pack(&self.objects_dir.join("pack"), objects, self.algorithm)?;
+
+        // Only now that the pack is complete is anything removed
+        for id in &loose {
+            fs::remove_file(self.object_path(id)?)?;
+            summary.removed_loose += 1;
+        }
+        for pack_path in old_packs {
+            if pack_path
+                .file_stem()
+                .is_some_and(|stem| *stem == *format!("pack-{}", name))
+            {
+                continue;
+            }
+            fs::remove_file(pack_path.with_extension("idx"))?;
+            fs::remove_file(&pack_path)?;
+            summary.removed_packs += 1;
+        }
+        // Drop now-empty fan-out directories
+        for dir in fs::read_dir(&self.objects_dir)? {
+            let dir = dir?.path();
+            if dir.file_name().is_some_and(|name| name.len() == 2) {
+                let _ = fs::remove_dir(dir);
+            }
+        }
+
+        *self.packs.borrow_mut() = None;
+        summary.pack = Some(name);
+        Ok(summary)
+    }
+
+    /// Reads the objects `ids` names, ready to be packed.
+    fn pack_objects(&self, ids: impl IntoIterator<Item = String>) -> Result<Vec<PackObject>> {
+        let mut objects = Vec::new();
+        for id in ids {
+            let (kind, data) = self.read(&id)?;
+            objects.push(PackObject {
+                id,
+                kind,
+                data,
+                name: None,
+            });
+        }
+
+        // Name blobs and trees after the entries pointing at them, so the
+        // versions of each file are tried as deltas of one another
+        let mut names = HashMap::new();
+        for object in objects
+            .iter()
+            .filter(|object| object.kind == ObjectKind::Tree)
+        {
+            for entry in Tree::deserialize(&object.data, self.algorithm)?.entries() {
+                names.insert(entry.id.clone(), entry.name.clone());
+            }
+        }
+        for object in &mut objects {
+            object.name = names.get(&object.id).cloned();
+        }
+        Ok(objects)
+    }
+
+    /// A pack of the objects `ids` names, to send to another repository.
+    pub fn pack_data(&self, ids: &[String]) -> Result<Vec<u8>> {
+        pack_data(self.pack_objects(ids.iter().cloned())?, self.algorithm)
+    }
+
+    /// Stores the objects of a pack received from another repository and
+    /// returns how many there were. Like Git, small packs are exploded into
+    /// loose objects and larger ones kept as a pack.
+    pub fn unpack(&self, data: &[u8]) -> Result<usize> {
+        let objects = unpack(data, self.algorithm, &|base| self.read(base))?;
+        let count = objects.len();
+        if count < UNPACK_LIMIT {
+            for (kind, data) in objects {
+                self.write(kind, &data)?;
+            }
+            return Ok(count);
+        }
+
+        let objects = objects
+            .into_iter()
+            .map(|(kind, data)| PackObject {
+                id: hash_object(self.algorithm, kind, &data),
+                kind,
+                data,
+                name: None,
+            })
+            .collect();
+        write_pack(&self.objects_dir.join("pack"), objects, self.algorithm)?;
+        *self.packs.borrow_mut() = None;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store(name: &str) -> (PathBuf, ObjectStore) {
+        let dir =
+            std::env::temp_dir().join(format!("git-scm-objects-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let store = ObjectStore::new(&dir, HashAlgorithm::Sha1).compressed();
+        (dir, store)
+    }
+
+    fn version(n: usize) -> Vec<u8> {
+        (0..100)
+            .map(|line| {
+                format!(
+                    "line {} of version {}\n",
+                    line,
+                    if line == 50 { n } else { 0 }
+                )
+            })
+            .collect::<String>()
+            .into_bytes()
+    }
+
+    #[test]
+    fn reads_objects_back_after_repacking() {
+        let (dir, store) = temp_store("repack");
+        let ids: Vec<String> = (0..10)
+            .map(|n| store.write(ObjectKind::Blob, &version(n)).unwrap())
+            .collect();
+
+        let summary = store.repack(false).unwrap();
+        assert_eq!(summary.objects, 10);
+        assert_eq!(summary.removed_loose, 10);
+        assert!(store.loose_ids().unwrap().is_empty());
+
+        for (n, id) in ids.iter().enumerate() {
+            assert!(store.exists(id));
+            assert_eq!(store.read(id).unwrap(), (ObjectKind::Blob, version(n)));
+            assert_eq!(store.find_by_prefix(&id[..7]).unwrap(), vec![id.clone()]);
+        }
+
+        // A second pack, then both consolidated into one
+        let extra = store.write(ObjectKind::Blob, &version(10)).unwrap();
+        store.repack(false).unwrap();
+        let summary = store.repack(true).unwrap();
+        assert_eq!(summary.objects, 11);
+        assert_eq!(summary.removed_packs, 2);
+        assert_eq!(store.read(&extra).unwrap(), (ObjectKind::Blob, version(10)));
+        assert_eq!(fs::read_dir(dir.join("objects/pack")).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn unpacks_received_packs() {
+        let (source_dir, source) = temp_store("unpack-source");
+        let few: Vec<String> = (0..3)
+            .map(|n| source.write(ObjectKind::Blob, &version(n)).unwrap())
+            .collect();
+        let many: Vec<String> = (0..UNPACK_LIMIT)
+            .map(|n| source.write(ObjectKind::Blob, &version(n + 3)).unwrap())
+            .collect();
+
+        // Small packs become loose objects, larger ones stay packed
+        let (target_dir, target) = temp_store("unpack-target");
+        assert_eq!(target.unpack(&source.pack_data(&few).unwrap()).unwrap(), 3);
+        assert_eq!(target.loose_ids().unwrap().len(), 3);
+        assert_eq!(
+            target.unpack(&source.pack_data(&many).unwrap()).unwrap(),
+            UNPACK_LIMIT
+        );
+        assert_eq!(target.loose_ids().unwrap().len(), 3);
+
+        for id in few.iter().chain(&many) {
+            assert_eq!(target.read(id).unwrap(), source.read(id).unwrap());
+        }
+
+        let mut truncated = source.pack_data(&few).unwrap();
+        truncated.truncate(truncated.len() / 2);
+        assert!(target.unpack(&truncated).is_err());
+        fs::remove_dir_all(&source_dir).unwrap();
+        fs::remove_dir_all(&target_dir).unwrap();
+    }
+}
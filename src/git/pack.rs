@@ -0,0 +1,864 @@
+#![allow(unused)]
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{BufReader, Error, ErrorKind, Read, Result, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::{Compression, Crc};
+
+use crate::git::delta;
+use crate::git::hash::{from_hex, to_hex, HashAlgorithm};
+use crate::git::object_store::{hash_object, ObjectKind};
+
+/// Objects compared with each one when looking for a delta base.
+const WINDOW: usize = 10;
+/// Longest chain of deltas an object may sit at the end of.
+const MAX_DEPTH: usize = 50;
+
+/// Finds a delta base by id anywhere in the object store.
+pub type BaseLookup<'a> = dyn Fn(&str) -> Result<(ObjectKind, Vec<u8>)> + 'a;
+
+const OFS_DELTA: u8 = 6;
+const REF_DELTA: u8 = 7;
+
+fn type_code(kind: ObjectKind) -> u8 {
+    match kind {
+        ObjectKind::Commit => 1,
+        ObjectKind::Tree => 2,
+        ObjectKind::Blob => 3,
+        ObjectKind::Tag => 4,
+    }
+}
+
+fn kind_from_code(code: u8) -> Option<ObjectKind> {
+    match code {
+        1 => Some(ObjectKind::Commit),
+        2 => Some(ObjectKind::Tree),
+        3 => Some(ObjectKind::Blob),
+        4 => Some(ObjectKind::Tag),
+        _ => None,
+    }
+}
+
+fn malformed(path: &Path, what: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("Malformed pack {}: {}", path.display(), what),
+    )
+}
+
+/// A `.pack` file and its version 2 `.idx`, which maps every object id in
+/// the pack to the offset its entry starts at.
+#[derive(Debug)]
+pub struct Pack {
+    pack_path: PathBuf,
+    /// The whole `.idx` file
+    index: Vec<u8>,
+    count: usize,
+    algorithm: HashAlgorithm,
+}
+
+impl Pack {
+    /// Opens the pack whose index is at `idx_path`.
+    pub fn open(idx_path: &Path, algorithm: HashAlgorithm) -> Result<Self> {
+        let index = fs::read(idx_path)?;
+        if index.len() < 8 + 256 * 4 || index[..4] != [0xff, b't', b'O', b'c'] {
+            return Err(malformed(idx_path, "not a version 2 index"));
+        }
+        let version = u32::from_be_bytes(index[4..8].try_into().unwrap());
+        if version != 2 {
+            return Err(malformed(
+                idx_path,
+                &format!("unsupported index version {}", version),
+            ));
+        }
+
+        let pack = Pack {
+            pack_path: idx_path.with_extension("pack"),
+            count: 0,
+            index,
+            algorithm,
+        };
+        // Each fanout count includes the ones before it
+        if (1..=255).any(|byte| pack.fanout(byte - 1) > pack.fanout(byte)) {
+            return Err(malformed(idx_path, "fanout table out of order"));
+        }
+        let count = pack.fanout(255) as usize;
+        let needed = 8 + 256 * 4 + count * (algorithm.raw_len() + 8) + 2 * algorithm.raw_len();
+        if pack.index.len() < needed {
+            return Err(malformed(idx_path, "truncated index"));
+        }
+        Ok(Pack { count, ..pack })
+    }
+
+    /// How many objects have ids whose first byte is at most `byte`.
+    fn fanout(&self, byte: u8) -> u32 {
+        let at = 8 + byte as usize * 4;
+        u32::from_be_bytes(self.index[at..at + 4].try_into().unwrap())
+    }
+
+    fn raw_id(&self, position: usize) -> &[u8] {
+        let len = self.algorithm.raw_len();
+        let at = 8 + 256 * 4 + position * len;
+        &self.index[at..at + len]
+    }
+
+    fn offset(&self, position: usize) -> Result<u64> {
+        let len = self.algorithm.raw_len();
+        let offsets = 8 + 256 * 4 + self.count * (len + 4);
+        let at = offsets + position * 4;
+        let offset = u32::from_be_bytes(self.index[at..at + 4].try_into().unwrap());
+
+        // Offsets past 2 GiB live in a table of eight-byte offsets after this
+        // one, which `open` cannot check the length of without reading them all
+        if offset & 0x8000_0000 == 0 {
+            return Ok(offset as u64);
+        }
+        let large = offsets + self.count * 4 + (offset & 0x7fff_ffff) as usize * 8;
+        self.index
+            .get(large..large + 8)
+            .map(|bytes| u64::from_be_bytes(bytes.try_into().unwrap()))
+            .ok_or_else(|| malformed(&self.pack_path, "large offset outside index"))
+    }
+
+    /// The positions of ids whose first byte is `first`, as the fanout
+    /// table narrows them down.
+    fn bucket(&self, first: u8) -> std::ops::Range<usize> {
+        let start = match first {
+            0 => 0,
+            byte => self.fanout(byte - 1) as usize,
+        };
+        start..self.fanout(first) as usize
+    }
+
+    fn position(&self, id: &str) -> Option<usize> {
+        let raw = from_hex(id).ok()?;
+        let bucket = self.bucket(*raw.first()?);
+        let (mut low, mut high) = (bucket.start, bucket.end);
+        while low < high {
+            let middle = (low + high) / 2;
+            match self.raw_id(middle).cmp(&raw) {
+                std::cmp::Ordering::Equal => return Some(middle),
+                std::cmp::Ordering::Less => low = middle + 1,
+                std::cmp::Ordering::Greater => high = middle,
+            }
+        }
+        None
+    }
+
+    /// The `.pack` file.
+    pub fn path(&self) -> &Path {
+        &self.pack_path
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.position(id).is_some()
+    }
+
+    /// Every object id in the pack, in sorted order.
+    pub fn ids(&self) -> Vec<String> {
+        (0..self.count)
+            .map(|position| to_hex(self.raw_id(position)))
+            .collect()
+    }
+
+    /// Ids in the pack starting with the hexadecimal `prefix`.
+    pub fn find_by_prefix(&self, prefix: &str) -> Vec<String> {
+        let Some(first) = prefix
+            .get(..2)
+            .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+        else {
+            return Vec::new();
+        };
+        self.bucket(first)
+            .map(|position| to_hex(self.raw_id(position)))
+            .filter(|id| id.starts_with(prefix))
+            .collect()
+    }
+
+    /// Reads an object out of the pack, resolving deltas. Bases named by id
+    /// rather than by offset are looked up with `lookup`, as they may live
+    /// anywhere in the object store.
+    pub fn read(&self, id: &str, lookup: &BaseLookup) -> Result<Option<(ObjectKind, Vec<u8>)>> {
+        match self.position(id) {
+            Some(position) => {
+                let mut file = File::open(&self.pack_path)?;
+                self.read_at(&mut file, self.offset(position)?, lookup)
+                    .map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn read_at(
+        &self,
+        file: &mut File,
+        offset: u64,
+        lookup: &BaseLookup,
+    ) -> Result<(ObjectKind, Vec<u8>)> {
+        file.seek(SeekFrom::Start(offset))?;
+        let mut reader = BufReader::new(&mut *file);
+
+        // Type and inflated size: four bits of size in the first byte, then
+        // seven per byte while the high bit is set
+        let mut byte = read_byte(&mut reader)?;
+        let code = (byte >> 4) & 0x7;
+        let mut size = (byte & 0x0f) as usize;
+        let mut shift = 4;
+        while byte & 0x80 != 0 {
+            byte = read_byte(&mut reader)?;
+            if shift >= usize::BITS {
+                return Err(malformed(&self.pack_path, "object size too large"));
+            }
+            size |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+        }
+
+        match code {
+            OFS_DELTA => {
+                // The base's distance back, in a big-endian variant of the
+                // size encoding where each continuation adds one
+                let mut byte = read_byte(&mut reader)?;
+                let mut distance = (byte & 0x7f) as u64;
+                while byte & 0x80 != 0 {
+                    byte = read_byte(&mut reader)?;
+                    distance = next_distance(distance, byte)
+                        .ok_or_else(|| malformed(&self.pack_path, "delta base too far back"))?;
+                }
+                let data = inflate(reader, size, &self.pack_path)?;
+                let base_offset = offset
+                    .checked_sub(distance)
+                    .ok_or_else(|| malformed(&self.pack_path, "delta base before pack start"))?;
+                let (kind, base) = self.read_at(file, base_offset, lookup)?;
+                Ok((kind, delta::apply(&base, &data)?))
+            }
+            REF_DELTA => {
+                let mut raw = vec![0; self.algorithm.raw_len()];
+                reader.read_exact(&mut raw)?;
+                let data = inflate(reader, size, &self.pack_path)?;
+                let (kind, base) = lookup(&to_hex(&raw))?;
+                Ok((kind, delta::apply(&base, &data)?))
+            }
+            code => {
+                let kind = kind_from_code(code)
+                    .ok_or_else(|| malformed(&self.pack_path, &format!("object type {}", code)))?;
+                Ok((kind, inflate(reader, size, &self.pack_path)?))
+            }
+        }
+    }
+}
+
+fn read_byte(reader: &mut impl Read) -> Result<u8> {
+    let mut byte = [0];
+    reader.read_exact(&mut byte)?;
+    Ok(byte[0])
+}
+
+/// Adds the next byte of an offset delta's distance, or `None` when the
+/// distance no longer fits in a `u64`.
+fn next_distance(distance: u64, byte: u8) -> Option<u64> {
+    if distance >= 1 << (u64::BITS - 7) {
+        return None;
+    }
+    Some(((distance + 1) << 7) | (byte & 0x7f) as u64)
+}
+
+/// Inflates an entry that should hold `size` bytes. The size comes from the
+/// pack, so nothing is allocated up front and inflating stops one byte past
+/// it.
+fn inflate(reader: impl Read, size: usize, path: &Path) -> Result<Vec<u8>> {
+    let mut data = Vec::new();
+    ZlibDecoder::new(reader)
+        .take(size as u64 + 1)
+        .read_to_end(&mut data)?;
+    if data.len() != size {
+        return Err(malformed(path, "object size mismatch"));
+    }
+    Ok(data)
+}
+
+/// An object to write into a pack.
+pub struct PackObject {
+    pub id: String,
+    pub kind: ObjectKind,
+    pub data: Vec<u8>,
+    /// File name the object was found under, which groups versions of the
+    /// same file together when choosing delta bases
+    pub name: Option<String>,
+}
+
+/// Writes `objects` as `pack-<checksum>.pack` and `.idx` into `dir`, storing
+/// objects as deltas against similar ones where that saves space. Returns the
+/// checksum naming the pack.
+pub fn write_pack(
+    dir: &Path,
+    objects: Vec<PackObject>,
+    algorithm: HashAlgorithm,
+) -> Result<String> {
+    let (pack, index) = encode(objects, algorithm)?;
+
+    // Write the pack before its index, so an index never names a missing pack
+    let name = to_hex(&pack[pack.len() - algorithm.raw_len()..]);
+    fs::create_dir_all(dir)?;
+    let pack_path = dir.join(format!("pack-{}.pack", name));
+    let idx_path = dir.join(format!("pack-{}.idx", name));
+    for (path, contents) in [(&pack_path, pack), (&idx_path, index)] {
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, contents)?;
+        fs::rename(&tmp_path, path)?;
+    }
+    Ok(name)
+}
+
+/// The contents of a pack holding `objects`, as sent over the network.
+pub fn pack_data(objects: Vec<PackObject>, algorithm: HashAlgorithm) -> Result<Vec<u8>> {
+    Ok(encode(objects, algorithm)?.0)
+}
+
+/// Builds a pack of `objects` and its index.
+fn encode(mut objects: Vec<PackObject>, algorithm: HashAlgorithm) -> Result<(Vec<u8>, Vec<u8>)> {
+    // Like Git, try objects of the same kind and file name as bases for each
+    // other, largest first so deltas mostly remove data
+    objects.sort_by(|a, b| {
+        (type_code(a.kind), &a.name, std::cmp::Reverse(a.data.len())).cmp(&(
+            type_code(b.kind),
+            &b.name,
+            std::cmp::Reverse(b.data.len()),
+        ))
+    });
+
+    let mut pack = b"PACK".to_vec();
+    pack.extend_from_slice(&2u32.to_be_bytes());
+    pack.extend_from_slice(&(objects.len() as u32).to_be_bytes());
+
+    // Offset, CRC-32 and delta depth of each object written so far
+    let mut written: Vec<(u64, u32, usize)> = Vec::with_capacity(objects.len());
+    for (position, object) in objects.iter().enumerate() {
+        let best = (position.saturating_sub(WINDOW)..position)
+            .filter(|&base| objects[base].kind == object.kind && written[base].2 < MAX_DEPTH)
+            .map(|base| (base, delta::compute(&objects[base].data, &object.data)))
+            .min_by_key(|(_, delta)| delta.len())
+            .filter(|(_, delta)| delta.len() < object.data.len() / 2);
+
+        let offset = pack.len() as u64;
+        let mut entry = Vec::new();
+        let depth = match best {
+            Some((base, delta)) => {
+                write_entry_header(&mut entry, OFS_DELTA, delta.len());
+                write_distance(&mut entry, offset - written[base].0);
+                entry.extend(deflate(&delta)?);
+                written[base].2 + 1
+            }
+            None => {
+                write_entry_header(&mut entry, type_code(object.kind), object.data.len());
+                entry.extend(deflate(&object.data)?);
+                0
+            }
+        };
+
+        let mut crc = Crc::new();
+        crc.update(&entry);
+        written.push((offset, crc.sum(), depth));
+        pack.extend(entry);
+    }
+    let checksum = algorithm.digest(&pack);
+    pack.extend_from_slice(&checksum);
+
+    // The index lists ids in sorted order, each table in that same order
+    let mut order: Vec<usize> = (0..objects.len()).collect();
+    order.sort_by(|&a, &b| objects[a].id.cmp(&objects[b].id));
+    let raw_ids = order
+        .iter()
+        .map(|&position| from_hex(&objects[position].id))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut index = vec![0xff, b't', b'O', b'c'];
+    index.extend_from_slice(&2u32.to_be_bytes());
+    for byte in 0..=255u8 {
+        let count = raw_ids.iter().filter(|raw| raw[0] <= byte).count() as u32;
+        index.extend_from_slice(&count.to_be_bytes());
+    }
+    for raw in &raw_ids {
+        index.extend_from_slice(raw);
+    }
+    for &position in &order {
+        index.extend_from_slice(&written[position].1.to_be_bytes());
+    }
+    let mut large = Vec::new();
+    for &position in &order {
+        let offset = written[position].0;
+        if offset < 0x8000_0000 {
+            index.extend_from_slice(&(offset as u32).to_be_bytes());
+        } else {
+            index.extend_from_slice(&(0x8000_0000 | large.len() as u32).to_be_bytes());
+            large.push(offset);
+        }
+    }
+    for offset in large {
+        index.extend_from_slice(&offset.to_be_bytes());
+    }
+    index.extend_from_slice(&checksum);
+    let index_checksum = algorithm.digest(&index);
+    index.extend_from_slice(&index_checksum);
+    Ok((pack, index))
+}
+
+/// An entry of a received pack before its delta, if any, is resolved.
+enum Entry {
+    Whole(ObjectKind, Vec<u8>),
+    /// A delta against the entry starting at the given offset
+    OffsetDelta(u64, Vec<u8>),
+    /// A delta against the object with the given id
+    RefDelta(String, Vec<u8>),
+}
+
+/// Reads every object out of `data`, a whole pack as received from another
+/// repository, in the order the pack holds them.
+///
+/// Deltas against objects outside the pack, as in the thin packs Git sends
+/// when pushing, find their bases with `lookup`.
+pub fn unpack(
+    data: &[u8],
+    algorithm: HashAlgorithm,
+    lookup: &BaseLookup,
+) -> Result<Vec<(ObjectKind, Vec<u8>)>> {
+    let path = Path::new("stream");
+    let raw_len = algorithm.raw_len();
+    if data.len() < 12 + raw_len || &data[..4] != b"PACK" {
+        return Err(malformed(path, "missing header"));
+    }
+    let version = u32::from_be_bytes(data[4..8].try_into().unwrap());
+    if version != 2 && version != 3 {
+        return Err(malformed(path, &format!("unsupported version {}", version)));
+    }
+    let count = u32::from_be_bytes(data[8..12].try_into().unwrap()) as usize;
+    let (body, checksum) = data.split_at(data.len() - raw_len);
+    if algorithm.digest(body) != checksum {
+        return Err(malformed(path, "checksum mismatch"));
+    }
+
+    let mut entries = Vec::new();
+    let mut at = 12;
+    for _ in 0..count {
+        let offset = at as u64;
+        let next = |at: &mut usize| -> Result<u8> {
+            let byte = *body
+                .get(*at)
+                .ok_or_else(|| malformed(path, "truncated entry"))?;
+            *at += 1;
+            Ok(byte)
+        };
+
+        let mut byte = next(&mut at)?;
+        let code = (byte >> 4) & 0x7;
+        let mut size = (byte & 0x0f) as usize;
+        let mut shift = 4;
+        while byte & 0x80 != 0 {
+            byte = next(&mut at)?;
+            if shift >= usize::BITS {
+                return Err(malformed(path, "object size too large"));
+            }
+            size |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+        }
+
+        let entry = match code {
+            OFS_DELTA => {
+                let mut byte = next(&mut at)?;
+                let mut distance = (byte & 0x7f) as u64;
+                while byte & 0x80 != 0 {
+                    byte = next(&mut at)?;
+                    distance = next_distance(distance, byte)
+                        .ok_or_else(|| malformed(path, "delta base too far back"))?;
+                }
+                let base = offset
+                    .checked_sub(distance)
+                    .ok_or_else(|| malformed(path, "delta base before pack start"))?;
+                Entry::OffsetDelta(base, inflate_at(body, &mut at, size, path)?)
+            }
+            REF_DELTA => {
+                let raw = body
+                    .get(at..at + raw_len)
+                    .ok_or_else(|| malformed(path, "truncated entry"))?;
+                at += raw_len;
+                Entry::RefDelta(to_hex(raw), inflate_at(body, &mut at, size, path)?)
+            }
+            code => {
+                let kind = kind_from_code(code)
+                    .ok_or_else(|| malformed(path, &format!("object type {}", code)))?;
+                Entry::Whole(kind, inflate_at(body, &mut at, size, path)?)
+            }
+        };
+        entries.push((offset, entry));
+    }
+
+    // Resolve deltas whose bases are resolved until nothing changes, as a
+    // base may come after the deltas against it
+    let mut resolved: Vec<Option<(ObjectKind, Vec<u8>)>> = vec![None; entries.len()];
+    let positions: HashMap<u64, usize> = entries
+        .iter()
+        .enumerate()
+        .map(|(position, (offset, _))| (*offset, position))
+        .collect();
+    let mut by_id: HashMap<String, usize> = HashMap::new();
+    let mut remaining = entries.len();
+    while remaining > 0 {
+        let before = remaining;
+        for (position, (_, entry)) in entries.iter().enumerate() {
+            if resolved[position].is_some() {
+                continue;
+            }
+            let object = match entry {
+                Entry::Whole(kind, data) => Some((*kind, data.clone())),
+                Entry::OffsetDelta(base, delta) => {
+                    let base = positions
+                        .get(base)
+                        .ok_or_else(|| malformed(path, "delta base is not an entry"))?;
+                    match &resolved[*base] {
+                        Some((kind, base)) => Some((*kind, delta::apply(base, delta)?)),
+                        None => None,
+                    }
+                }
+                Entry::RefDelta(id, delta) => {
+                    let base = match by_id.get(id) {
+                        Some(&base) => resolved[base].clone(),
+                        None => lookup(id).ok(),
+                    };
+                    match base {
+                        Some((kind, base)) => Some((kind, delta::apply(&base, delta)?)),
+                        None => None,
+                    }
+                }
+            };
+            if let Some((kind, data)) = object {
+                by_id.insert(hash_object(algorithm, kind, &data), position);
+                resolved[position] = Some((kind, data));
+                remaining -= 1;
+            }
+        }
+        if remaining == before {
+            return Err(malformed(path, "delta base not found"));
+        }
+    }
+    Ok(resolved.into_iter().flatten().collect())
+}
+
+/// Inflates the zlib stream starting at `at`, moving `at` past it.
+fn inflate_at(data: &[u8], at: &mut usize, size: usize, path: &Path) -> Result<Vec<u8>> {
+    let mut decoder = ZlibDecoder::new(&data[*at..]);
+    let mut inflated = Vec::new();
+    (&mut decoder)
+        .take(size as u64 + 1)
+        .read_to_end(&mut inflated)?;
+    if inflated.len() != size {
+        return Err(malformed(path, "object size mismatch"));
+    }
+    *at += decoder.total_in() as usize;
+    Ok(inflated)
+}
+
+fn write_entry_header(out: &mut Vec<u8>, code: u8, size: usize) {
+    let mut byte = (code << 4) | (size & 0x0f) as u8;
+    let mut rest = size >> 4;
+    while rest != 0 {
+        out.push(byte | 0x80);
+        byte = (rest & 0x7f) as u8;
+        rest >>= 7;
+    }
+    out.push(byte);
+}
+
+fn write_distance(out: &mut Vec<u8>, mut distance: u64) {
+    let mut bytes = vec![(distance & 0x7f) as u8];
+    distance >>= 7;
+    while distance != 0 {
+        distance -= 1;
+        bytes.push(0x80 | (distance & 0x7f) as u8);
+        distance >>= 7;
+    }
+    bytes.reverse();
+    out.extend(bytes);
+}
+
+fn deflate(data: &[u8]) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALGORITHM: HashAlgorithm = HashAlgorithm::Sha1;
+
+    fn blob(text: &str) -> (String, Vec<u8>) {
+        let data = text.as_bytes().to_vec();
+        (hash_object(ALGORITHM, ObjectKind::Blob, &data), data)
+    }
+
+    fn version(n: usize) -> String {
+        (0..100)
+            .map(|line| {
+                format!(
+                    "line {} of version {}\n",
+                    line,
+                    if line == 50 { n } else { 0 }
+                )
+            })
+            .collect()
+    }
+
+    /// A pack of `entries`, already encoded, with its header and checksum.
+    fn finish(entries: &[Vec<u8>]) -> Vec<u8> {
+        let mut pack = b"PACK".to_vec();
+        pack.extend_from_slice(&2u32.to_be_bytes());
+        pack.extend_from_slice(&(entries.len() as u32).to_be_bytes());
+        for entry in entries {
+            pack.extend_from_slice(entry);
+        }
+        let checksum = ALGORITHM.digest(&pack);
+        pack.extend(checksum);
+        pack
+    }
+
+    fn whole(data: &[u8]) -> Vec<u8> {
+        let mut entry = Vec::new();
+        write_entry_header(&mut entry, type_code(ObjectKind::Blob), data.len());
+        entry.extend(deflate(data).unwrap());
+        entry
+    }
+
+    fn offset_delta(distance: u64, base: &[u8], data: &[u8]) -> Vec<u8> {
+        let delta = delta::compute(base, data);
+        let mut entry = Vec::new();
+        write_entry_header(&mut entry, OFS_DELTA, delta.len());
+        write_distance(&mut entry, distance);
+        entry.extend(deflate(&delta).unwrap());
+        entry
+    }
+
+    fn ref_delta(base_id: &str, base: &[u8], data: &[u8]) -> Vec<u8> {
+        let delta = delta::compute(base, data);
+        let mut entry = Vec::new();
+        write_entry_header(&mut entry, REF_DELTA, delta.len());
+        entry.extend(from_hex(base_id).unwrap());
+        entry.extend(deflate(&delta).unwrap());
+        entry
+    }
+
+    fn no_lookup(id: &str) -> Result<(ObjectKind, Vec<u8>)> {
+        Err(Error::new(ErrorKind::NotFound, id.to_string()))
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("git-scm-pack-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn unpacks_delta_chains() {
+        let (_, v1) = blob(&version(1));
+        let (v2_id, v2) = blob(&version(2));
+        let (_, v3) = blob(&version(3));
+        let (outside_id, outside) = blob(&version(4));
+        let (_, v5) = blob(&version(5));
+
+        // v2 is whole, v1 an offset delta against it, v3 a ref delta against
+        // v1 and v5 a ref delta against an object outside the pack. The ref
+        // delta comes before the entry it names.
+        let first = whole(&v2);
+        let second = offset_delta(first.len() as u64, &v2, &v1);
+        let (v1_id, _) = blob(&version(1));
+        let entries = [
+            ref_delta(&v1_id, &v1, &v3),
+            first,
+            second,
+            ref_delta(&outside_id, &outside, &v5),
+        ];
+        let data = finish(&entries);
+
+        let lookup = |id: &str| {
+            if id == outside_id {
+                Ok((ObjectKind::Blob, outside.clone()))
+            } else {
+                no_lookup(id)
+            }
+        };
+        let objects = unpack(&data, ALGORITHM, &lookup).unwrap();
+        let contents: Vec<&[u8]> = objects.iter().map(|(_, data)| &data[..]).collect();
+        assert_eq!(contents, [&v3[..], &v2, &v1, &v5]);
+        assert!(objects.iter().all(|(kind, _)| *kind == ObjectKind::Blob));
+
+        // Without the outside object the thin pack cannot be resolved
+        assert!(unpack(&data, ALGORITHM, &no_lookup).is_err());
+    }
+
+    #[test]
+    fn encodes_similar_objects_as_deltas() {
+        let objects: Vec<PackObject> = (0..5)
+            .map(|n| {
+                let (id, data) = blob(&version(n));
+                PackObject {
+                    id,
+                    kind: ObjectKind::Blob,
+                    data,
+                    name: Some("file".to_string()),
+                }
+            })
+            .collect();
+        let total: usize = objects.iter().map(|object| object.data.len()).sum();
+        let expected: Vec<(String, Vec<u8>)> = (0..5).map(|n| blob(&version(n))).collect();
+
+        let data = pack_data(objects, ALGORITHM).unwrap();
+        assert!(data.len() < total / 3, "{} of {} bytes", data.len(), total);
+        let mut unpacked: Vec<(String, Vec<u8>)> = unpack(&data, ALGORITHM, &no_lookup)
+            .unwrap()
+            .into_iter()
+            .map(|(kind, data)| (hash_object(ALGORITHM, kind, &data), data))
+            .collect();
+        unpacked.sort();
+        let mut expected = expected;
+        expected.sort();
+        assert_eq!(unpacked, expected);
+    }
+
+    #[test]
+    fn reads_objects_through_the_index() {
+        let dir = temp_dir("index");
+        let objects: Vec<(String, Vec<u8>)> = (0..20).map(|n| blob(&version(n))).collect();
+        let name = write_pack(
+            &dir,
+            objects
+                .iter()
+                .map(|(id, data)| PackObject {
+                    id: id.clone(),
+                    kind: ObjectKind::Blob,
+                    data: data.clone(),
+                    name: None,
+                })
+                .collect(),
+            ALGORITHM,
+        )
+        .unwrap();
+
+        let pack = Pack::open(&dir.join(format!("pack-{}.idx", name)), ALGORITHM).unwrap();
+        let mut ids: Vec<String> = objects.iter().map(|(id, _)| id.clone()).collect();
+        ids.sort();
+        assert_eq!(pack.ids(), ids);
+        for (id, data) in &objects {
+            assert_eq!(
+                pack.read(id, &no_lookup).unwrap(),
+                Some((ObjectKind::Blob, data.clone()))
+            );
+            assert_eq!(pack.find_by_prefix(&id[..6]), vec![id.clone()]);
+        }
+        let (missing, _) = blob("not packed");
+        assert_eq!(pack.read(&missing, &no_lookup).unwrap(), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_corrupt_indexes() {
+        let dir = temp_dir("corrupt");
+        let (id, data) = blob("packed");
+        let name = write_pack(
+            &dir,
+            vec![PackObject {
+                id: id.clone(),
+                kind: ObjectKind::Blob,
+                data,
+                name: None,
+            }],
+            ALGORITHM,
+        )
+        .unwrap();
+        let idx_path = dir.join(format!("pack-{}.idx", name));
+        let index = fs::read(&idx_path).unwrap();
+
+        // Cut short
+        fs::write(&idx_path, &index[..index.len() - 30]).unwrap();
+        assert!(Pack::open(&idx_path, ALGORITHM).is_err());
+
+        // A large offset with no table of large offsets
+        let mut large = index.clone();
+        let at = 8 + 256 * 4 + ALGORITHM.raw_len() + 4;
+        large[at..at + 4].copy_from_slice(&0x8000_0005u32.to_be_bytes());
+        fs::write(&idx_path, &large).unwrap();
+        let pack = Pack::open(&idx_path, ALGORITHM).unwrap();
+        let error = pack.read(&id, &no_lookup).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidData);
+
+        // A fanout count beyond the ids the index holds
+        let mut fanout = index.clone();
+        fanout[8..12].copy_from_slice(&1000u32.to_be_bytes());
+        fs::write(&idx_path, &fanout).unwrap();
+        assert!(Pack::open(&idx_path, ALGORITHM).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rejects_truncated_packs() {
+        let (_, v1) = blob(&version(1));
+        let (_, v2) = blob(&version(2));
+        let first = whole(&v1);
+        let second = offset_delta(first.len() as u64, &v1, &v2);
+        let data = finish(&[first, second]);
+
+        for end in 0..data.len() {
+            assert!(
+                unpack(&data[..end], ALGORITHM, &no_lookup).is_err(),
+                "at {}",
+                end
+            );
+        }
+        // Cut short but with a valid checksum over what remains
+        let body = &data[12..data.len() - ALGORITHM.raw_len()];
+        for end in 0..body.len() {
+            let mut cut = data[..12].to_vec();
+            cut.extend_from_slice(&body[..end]);
+            cut.extend(ALGORITHM.digest(&cut));
+            assert!(unpack(&cut, ALGORITHM, &no_lookup).is_err(), "at {}", end);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_entries() {
+        let cases = [
+            // A size that overflows
+            vec![0xff; 12],
+            // A size far beyond the data that follows
+            {
+                let mut entry = Vec::new();
+                write_entry_header(&mut entry, type_code(ObjectKind::Blob), 1 << 40);
+                entry.extend(deflate(b"small").unwrap());
+                entry
+            },
+            // A distance that overflows
+            {
+                let mut entry = vec![OFS_DELTA << 4 | 1];
+                entry.extend([0xff; 10]);
+                entry.push(0x01);
+                entry
+            },
+            // A delta base before the start of the pack
+            {
+                let mut entry = vec![OFS_DELTA << 4 | 1];
+                write_distance(&mut entry, 100);
+                entry.extend(deflate(b"x").unwrap());
+                entry
+            },
+        ];
+        for entry in cases {
+            let data = finish(&[entry]);
+            let error = unpack(&data, ALGORITHM, &no_lookup).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidData);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+#![allow(unused)]
+
+use std::io::{Error, ErrorKind, Result};
+
+/// Largest packet Git sends: four bytes of length and 65516 of data.
+pub const MAX_PACKET: usize = 65520;
+
+/// The framing of Git's wire protocol: each packet is its length, header
+/// included, in four hexadecimal digits followed by its data. Lengths below
+/// four are special packets marking the ends of messages and sections.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Packet<'a> {
+    Data(&'a [u8]),
+    /// `0000`, ending a message
+    Flush,
+    /// `0001`, separating the sections of a message in protocol v2
+    Delimiter,
+    /// `0002`, ending a stateless response in protocol v2
+    ResponseEnd,
+}
+
+impl<'a> Packet<'a> {
+    /// The data of a packet holding a line of text, without its newline.
+    pub fn text(&self) -> Option<&'a str> {
+        match self {
+            Packet::Data(data) => {
+                let text = std::str::from_utf8(data).ok()?;
+                Some(text.strip_suffix('\n').unwrap_or(text))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Appends a data packet.
+pub fn write(out: &mut Vec<u8>, data: &[u8]) {
+    out.extend_from_slice(format!("{:04x}", data.len() + 4).as_bytes());
+    out.extend_from_slice(data);
+}
+
+/// Appends a packet holding `line` and a newline.
+pub fn write_line(out: &mut Vec<u8>, line: &str) {
+    write(out, format!("{}\n", line).as_bytes());
+}
+
+pub fn write_flush(out: &mut Vec<u8>) {
+    out.extend_from_slice(b"0000");
+}
+
+pub fn write_delimiter(out: &mut Vec<u8>) {
+    out.extend_from_slice(b"0001");
+}
+
+/// Reads packets out of a buffer.
+pub struct PacketReader<'a> {
+    data: &'a [u8],
+    at: usize,
+}
+
+impl<'a> PacketReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        PacketReader { data, at: 0 }
+    }
+
+    /// The next packet, or `None` at the end of the buffer.
+    pub fn next_packet(&mut self) -> Result<Option<Packet<'a>>> {
+        if self.at == self.data.len() {
+            return Ok(None);
+        }
+        let malformed = || Error::new(ErrorKind::InvalidData, "Malformed packet line");
+
+        let length = self
+            .data
+            .get(self.at..self.at + 4)
+            .and_then(|digits| std::str::from_utf8(digits).ok())
+            .and_then(|digits| usize::from_str_radix(digits, 16).ok())
+            .ok_or_else(malformed)?;
+        let packet = match length {
+            0 => Packet::Flush,
+            1 => Packet::Delimiter,
+            2 => Packet::ResponseEnd,
+            3 => return Err(malformed()),
+            _ => Packet::Data(
+                self.data
+                    .get(self.at + 4..self.at + length)
+                    .ok_or_else(malformed)?,
+            ),
+        };
+        self.at += length.max(4);
+        Ok(Some(packet))
+    }
+
+    /// The next packet, failing at the end of the buffer.
+    pub fn expect_packet(&mut self) -> Result<Packet<'a>> {
+        self.next_packet()?
+            .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "Unexpected end of packet stream"))
+    }
+
+    /// The text lines up to the next special packet, and that packet.
+    pub fn read_lines(&mut self) -> Result<(Vec<&'a str>, Packet<'a>)> {
+        let mut lines = Vec::new();
+        loop {
+            match self.expect_packet()? {
+                packet @ Packet::Data(_) => lines.push(packet.text().ok_or_else(|| {
+                    Error::new(ErrorKind::InvalidData, "Packet line is not text")
+                })?),
+                end => return Ok((lines, end)),
+            }
+        }
+    }
+
+    /// Whatever follows the packets read so far.
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.data[self.at..]
+    }
+}
@@ -0,0 +1,299 @@
+#![allow(unused)]
+
+use std::collections::BTreeMap;
+use std::io::{Error, ErrorKind, Result};
+
+use crate::git::hash::HashAlgorithm;
+use crate::git::http::{request, Url, AGENT};
+use crate::git::pkt_line::{self, Packet, PacketReader};
+
+/// A ref as another repository advertises it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AdvertisedRef {
+    pub name: String,
+    pub id: String,
+    /// What a symbolic ref such as `HEAD` points at
+    pub target: Option<String>,
+}
+
+/// A repository served over Git's smart HTTP protocol.
+///
+/// Fetching speaks protocol version 2: the server lists its capabilities,
+/// `ls-refs` lists its refs and `fetch` sends a pack of the objects wanted
+/// but not had. Version 2 has no push, so pushing uses `git-receive-pack`
+/// as every version of Git does.
+#[derive(Debug)]
+pub struct HttpRemote {
+    url: Url,
+    algorithm: HashAlgorithm,
+}
+
+impl HttpRemote {
+    /// Reads the capabilities of the repository at `url`, which must speak
+    /// protocol version 2.
+    pub fn connect(url: &str) -> Result<Self> {
+        let url = Url::parse(url)?;
+        let response = request(
+            &url,
+            "GET",
+            "info/refs?service=git-upload-pack",
+            &[("Git-Protocol", "version=2")],
+            Vec::new(),
+        )?;
+
+        let mut reader = PacketReader::new(&response.body);
+        let (mut lines, _) = reader.read_lines()?;
+        // Git's own server leaves the service line out for version 2
+        if lines
+            .first()
+            .is_some_and(|line| line.starts_with("# service="))
+        {
+            lines = reader.read_lines()?.0;
+        }
+        if lines.first() != Some(&"version 2") {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "The server does not speak protocol version 2",
+            ));
+        }
+        for command in ["ls-refs", "fetch"] {
+            if !lines
+                .iter()
+                .any(|line| line.split('=').next() == Some(command))
+            {
+                return Err(Error::new(
+                    ErrorKind::Unsupported,
+                    format!("The server does not support {}", command),
+                ));
+            }
+        }
+        let algorithm = match lines
+            .iter()
+            .find_map(|line| line.strip_prefix("object-format="))
+        {
+            Some(name) => HashAlgorithm::parse(name)?,
+            None => HashAlgorithm::Sha1,
+        };
+        Ok(HttpRemote { url, algorithm })
+    }
+
+    /// How the remote repository hashes objects.
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    /// Sends a protocol version 2 command and returns the response.
+    fn command(&self, command: &str, arguments: &[String]) -> Result<Vec<u8>> {
+        let mut body = Vec::new();
+        pkt_line::write_line(&mut body, &format!("command={}", command));
+        pkt_line::write_line(&mut body, &format!("agent={}", AGENT));
+        pkt_line::write_line(
+            &mut body,
+            &format!("object-format={}", self.algorithm.name()),
+        );
+        pkt_line::write_delimiter(&mut body);
+        for argument in arguments {
+            pkt_line::write_line(&mut body, argument);
+        }
+        pkt_line::write_flush(&mut body);
+
+        let response = request(
+            &self.url,
+            "POST",
+            "git-upload-pack",
+            &[
+                ("Content-Type", "application/x-git-upload-pack-request"),
+                ("Accept", "application/x-git-upload-pack-result"),
+                ("Git-Protocol", "version=2"),
+            ],
+            body,
+        )?;
+
+        // Errors come back as a single `ERR <message>` packet
+        if let Some(message) = PacketReader::new(&response.body)
+            .next_packet()?
+            .and_then(|packet| packet.text()?.strip_prefix("ERR "))
+        {
+            return Err(Error::other(format!("Remote error: {}", message)));
+        }
+        Ok(response.body)
+    }
+
+    /// The refs starting with one of `prefixes`, along with `HEAD` and what
+    /// it points at.
+    pub fn ls_refs(&self, prefixes: &[&str]) -> Result<Vec<AdvertisedRef>> {
+        let mut arguments = vec!["symrefs".to_string(), "ref-prefix HEAD".to_string()];
+        for prefix in prefixes {
+            arguments.push(format!("ref-prefix {}", prefix));
+        }
+        let response = self.command("ls-refs", &arguments)?;
+
+        let mut refs = Vec::new();
+        for line in PacketReader::new(&response).read_lines()?.0 {
+            // `<id> <name>` and attributes such as `symref-target:<target>`
+            let mut fields = line.split(' ');
+            let (Some(id), Some(name)) = (fields.next(), fields.next()) else {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Malformed ref: {}", line),
+                ));
+            };
+            // An unborn HEAD has no id
+            if id == "unborn" {
+                continue;
+            }
+            refs.push(AdvertisedRef {
+                name: name.to_string(),
+                id: id.to_string(),
+                target: fields
+                    .find_map(|field| field.strip_prefix("symref-target:"))
+                    .map(str::to_string),
+            });
+        }
+        Ok(refs)
+    }
+
+    /// A pack of the objects needed for `wants` that are not reachable from
+    /// `haves`, which the server ignores when it does not know them.
+    pub fn fetch_pack(&self, wants: &[String], haves: &[String]) -> Result<Vec<u8>> {
+        let mut arguments = vec!["ofs-delta".to_string(), "no-progress".to_string()];
+        arguments.extend(wants.iter().map(|id| format!("want {}", id)));
+        arguments.extend(haves.iter().map(|id| format!("have {}", id)));
+        // Everything is said in one round, so the server answers with a pack
+        arguments.push("done".to_string());
+        let response = self.command("fetch", &arguments)?;
+
+        let mut reader = PacketReader::new(&response);
+        let mut pack = Vec::new();
+        let mut in_pack = false;
+        while let Some(packet) = reader.next_packet()? {
+            match packet {
+                Packet::Data(data) if in_pack => {
+                    // The first byte tells which band the data belongs to
+                    match data.split_first() {
+                        Some((1, data)) => pack.extend_from_slice(data),
+                        Some((2, _)) => {}
+                        Some((3, message)) => {
+                            return Err(Error::other(format!(
+                                "Remote error: {}",
+                                String::from_utf8_lossy(message).trim_end()
+                            )))
+                        }
+                        _ => {
+                            return Err(Error::new(
+                                ErrorKind::InvalidData,
+                                "Malformed side-band packet",
+                            ))
+                        }
+                    }
+                }
+                Packet::Data(_) if packet.text() == Some("packfile") => in_pack = true,
+                Packet::Flush => break,
+                _ => {}
+            }
+        }
+        if !in_pack {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "The server sent no pack",
+            ));
+        }
+        Ok(pack)
+    }
+
+    /// The refs the repository would update on a push, by name.
+    pub fn receive_pack_refs(&self) -> Result<BTreeMap<String, String>> {
+        let response = request(
+            &self.url,
+            "GET",
+            "info/refs?service=git-receive-pack",
+            &[],
+            Vec::new(),
+        )?;
+
+        let mut reader = PacketReader::new(&response.body);
+        let (mut lines, _) = reader.read_lines()?;
+        if lines
+            .first()
+            .is_some_and(|line| line.starts_with("# service="))
+        {
+            lines = reader.read_lines()?.0;
+        }
+
+        let mut refs = BTreeMap::new();
+        for line in lines {
+            // Capabilities follow the first ref after a NUL
+            let line = line.split('\0').next().unwrap_or_default();
+            if let Some((id, name)) = line.split_once(' ') {
+                // An empty repository advertises only its capabilities
+                if name != "capabilities^{}" {
+                    refs.insert(name.to_string(), id.to_string());
+                }
+            }
+        }
+        Ok(refs)
+    }
+
+    /// Asks the repository to move `name` from `old` to `new`, sending the
+    /// objects `pack` holds, and returns why it refused if it did.
+    pub fn send_pack(
+        &self,
+        name: &str,
+        old: Option<&str>,
+        new: &str,
+        pack: Vec<u8>,
+    ) -> Result<Option<String>> {
+        let zero = "0".repeat(self.algorithm.hex_len());
+        let mut body = Vec::new();
+        let command = format!(
+            "{} {} {}\0report-status object-format={} agent={}",
+            old.unwrap_or(&zero),
+            new,
+            name,
+            self.algorithm.name(),
+            AGENT
+        );
+        pkt_line::write_line(&mut body, &command);
+        pkt_line::write_flush(&mut body);
+        body.extend(pack);
+
+        let response = request(
+            &self.url,
+            "POST",
+            "git-receive-pack",
+            &[
+                ("Content-Type", "application/x-git-receive-pack-request"),
+                ("Accept", "application/x-git-receive-pack-result"),
+            ],
+            body,
+        )?;
+
+        // `unpack ok`, then `ok <ref>` or `ng <ref> <reason>` for each ref
+        let (lines, _) = PacketReader::new(&response.body).read_lines()?;
+        match lines.first().and_then(|line| line.strip_prefix("unpack ")) {
+            Some("ok") => {}
+            Some(reason) => return Err(Error::other(format!("Remote unpack failed: {}", reason))),
+            None => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "Malformed push status report",
+                ))
+            }
+        }
+        for line in &lines[1..] {
+            if line.strip_prefix("ok ") == Some(name) {
+                return Ok(None);
+            }
+            if let Some(reason) = line
+                .strip_prefix("ng ")
+                .and_then(|rest| rest.strip_prefix(name))
+            {
+                return Ok(Some(reason.trim().to_string()));
+            }
+        }
+        Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("No push status reported for {}", name),
+        ))
+    }
+}
@@ -0,0 +1,314 @@
+#![allow(unused)]
+
+use std::collections::{HashSet, VecDeque};
+use std::io::{Error, ErrorKind, Result};
+use std::path::PathBuf;
+
+use crate::git::commit::Commit;
+use crate::git::config::Config;
+use crate::git::merge_base::is_ancestor;
+use crate::git::object_store::{ObjectKind, ObjectStore};
+use crate::git::tree::Tree;
+
+/// Another repository this one exchanges commits with, configured in a
+/// `[remote "<name>"]` section.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Remote {
+    pub name: String,
+    pub url: String,
+    /// Which of its refs `fetch` copies, and where to
+    pub fetch: Vec<RefSpec>,
+}
+
+impl Remote {
+    /// The remote called `name`, if it has a URL configured.
+    pub fn from_config(config: &Config, name: &str) -> Result<Option<Self>> {
+        let Some(url) = config.get(&format!("remote.{}.url", name)) else {
+            return Ok(None);
+        };
+
+        let key = format!("remote.{}.fetch", name);
+        let mut fetch = Vec::new();
+        for entry in config.entries().iter().filter(|entry| entry.key == key) {
+            fetch.push(RefSpec::parse(&entry.value)?);
+        }
+        Ok(Some(Remote {
+            name: name.to_string(),
+            url: url.to_string(),
+            fetch,
+        }))
+    }
+
+    /// Every configured remote, in the order the configuration names them.
+    pub fn list(config: &Config) -> Result<Vec<Self>> {
+        let mut names: Vec<&str> = Vec::new();
+        for entry in config.entries() {
+            let name = entry
+                .key
+                .strip_prefix("remote.")
+                .and_then(|rest| rest.strip_suffix(".url"));
+            if let Some(name) = name {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+
+        let mut remotes = Vec::new();
+        for name in names {
+            remotes.extend(Self::from_config(config, name)?);
+        }
+        Ok(remotes)
+    }
+
+    /// The refspec `remote add` configures: every branch tracked under
+    /// `refs/remotes/<name>/`.
+    pub fn default_fetch(name: &str) -> String {
+        format!("+refs/heads/*:refs/remotes/{}/*", name)
+    }
+
+    /// Where the repository lives; only local paths and `file://` URLs are
+    /// supported.
+    pub fn path(&self) -> Result<PathBuf> {
+        if let Some(path) = self.url.strip_prefix("file://") {
+            return Ok(PathBuf::from(path));
+        }
+        if self.url.contains("://") {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("Unsupported URL for remote {}: {}", self.name, self.url),
+            ));
+        }
+        Ok(PathBuf::from(&self.url))
+    }
+
+    /// The local ref tracking the remote's ref `name`, and whether it may be
+    /// updated when that is not a fast-forward.
+    pub fn tracking_ref(&self, name: &str) -> Option<(String, bool)> {
+        self.fetch
+            .iter()
+            .find_map(|spec| spec.map(name).map(|target| (target, spec.force)))
+    }
+}
+
+/// A mapping from refs of one repository to refs of another, such as
+/// `+refs/heads/*:refs/remotes/origin/*`. A `*` in the source stands for
+/// the same text in the destination.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RefSpec {
+    /// A leading `+`: update even when it is not a fast-forward
+    pub force: bool,
+    pub source: String,
+    pub destination: String,
+}
+
+impl RefSpec {
+    pub fn parse(text: &str) -> Result<Self> {
+        let (force, spec) = match text.strip_prefix('+') {
+            Some(spec) => (true, spec),
+            None => (false, text),
+        };
+        let invalid = || {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid refspec: {}", text),
+            )
+        };
+
+        let (source, destination) = spec.split_once(':').ok_or_else(invalid)?;
+        if source.matches('*').count() != destination.matches('*').count()
+            || source.matches('*').count() > 1
+        {
+            return Err(invalid());
+        }
+        Ok(RefSpec {
+            force,
+            source: source.to_string(),
+            destination: destination.to_string(),
+        })
+    }
+
+    /// Where `name` ends up, if the source side matches it.
+    pub fn map(&self, name: &str) -> Option<String> {
+        match self.source.split_once('*') {
+            Some((prefix, suffix)) => {
+                let matched = name.strip_prefix(prefix)?.strip_suffix(suffix)?;
+                Some(self.destination.replacen('*', matched, 1))
+            }
+            None => (name == self.source).then(|| self.destination.clone()),
+        }
+    }
+}
+
+/// How a ref was moved by a fetch or push.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RefUpdateStatus {
+    UpToDate,
+    Created,
+    FastForward,
+    /// Moved to a commit not descending from where it was
+    Forced,
+    /// Left alone because it is not a fast-forward
+    Rejected,
+}
+
+#[derive(Clone, Debug)]
+pub struct RefUpdate {
+    /// Name of the ref on the side sending the commits
+    pub source: String,
+    /// Name of the ref that was updated
+    pub destination: String,
+    pub old: Option<String>,
+    pub new: String,
+    pub status: RefUpdateStatus,
+}
+
+impl RefUpdate {
+    /// A line in the style of Git's fetch and push reports, such as
+    /// `   1a2b3c4..5d6e7f8  main -> origin/main`.
+    pub fn describe(&self) -> String {
+        let short = |id: &str| id[..7.min(id.len())].to_string();
+        let old = self.old.as_deref().map(short).unwrap_or_default();
+        let new = short(&self.new);
+        let (flag, summary, note) = match self.status {
+            RefUpdateStatus::UpToDate => ('=', "[up to date]".to_string(), ""),
+            RefUpdateStatus::Created if self.destination.starts_with("refs/tags/") => {
+                ('*', "[new tag]".to_string(), "")
+            }
+            RefUpdateStatus::Created => ('*', "[new branch]".to_string(), ""),
+            RefUpdateStatus::FastForward => (' ', format!("{}..{}", old, new), ""),
+            RefUpdateStatus::Forced => ('+', format!("{}...{}", old, new), "  (forced update)"),
+            RefUpdateStatus::Rejected => ('!', "[rejected]".to_string(), "  (non-fast-forward)"),
+        };
+        format!(
+            " {} {:<17} {} -> {}{}",
+            flag,
+            summary,
+            short_ref(&self.source),
+            short_ref(&self.destination),
+            note
+        )
+    }
+}
+
+/// `main` for `refs/heads/main`, `origin/main` for
+/// `refs/remotes/origin/main`, `v1` for `refs/tags/v1`.
+fn short_ref(name: &str) -> &str {
+    name.strip_prefix("refs/heads/")
+        .or_else(|| name.strip_prefix("refs/remotes/"))
+        .or_else(|| name.strip_prefix("refs/tags/"))
+        .unwrap_or(name)
+}
+
+/// How moving a ref from `old` to `new` counts, with `force` allowing it
+/// when `old` is not an ancestor of `new`. An `old` missing from `store`
+/// cannot be one, as its history is unknown.
+pub fn update_status(
+    store: &ObjectStore,
+    old: Option<&str>,
+    new: &str,
+    force: bool,
+) -> Result<RefUpdateStatus> {
+    let Some(old) = old else {
+        return Ok(RefUpdateStatus::Created);
+    };
+    if old == new {
+        Ok(RefUpdateStatus::UpToDate)
+    } else if store.exists(old) && is_ancestor(store, old, new)? {
+        Ok(RefUpdateStatus::FastForward)
+    } else if force {
+        Ok(RefUpdateStatus::Forced)
+    } else {
+        Ok(RefUpdateStatus::Rejected)
+    }
+}
+
+/// Copies into `to` every object reachable from `tips` that it lacks.
+///
+/// Each object is written after everything it references, so a repository
+/// holding an object always holds all it needs, even after an interrupted
+/// copy. That lets the walk stop at the first object `to` already has.
+/// Returns how many objects were copied.
+pub fn copy_missing_objects(
+    from: &ObjectStore,
+    to: &ObjectStore,
+    tips: &[String],
+) -> Result<usize> {
+    let missing = reachable_objects(from, tips, &|id| to.exists(id))?;
+    for id in &missing {
+        let (kind, data) = from.read(id)?;
+        to.write(kind, &data)?;
+    }
+    Ok(missing.len())
+}
+
+/// The objects to send a repository that has `theirs` so it gets
+/// everything `tips` needs: those reachable from `tips` but not from the
+/// ones of `theirs` found in `store`.
+pub fn objects_to_send(
+    store: &ObjectStore,
+    tips: &[String],
+    theirs: &[String],
+) -> Result<Vec<String>> {
+    let theirs: Vec<String> = theirs
+        .iter()
+        .filter(|id| store.exists(id))
+        .cloned()
+        .collect();
+    let known: HashSet<String> = reachable_objects(store, &theirs, &|_| false)?
+        .into_iter()
+        .collect();
+    reachable_objects(store, tips, &|id| known.contains(id))
+}
+
+/// Every object reachable from `tips` without passing through one `known`
+/// accepts, each listed after everything it references.
+pub fn reachable_objects(
+    store: &ObjectStore,
+    tips: &[String],
+    known: &dyn Fn(&str) -> bool,
+) -> Result<Vec<String>> {
+    let mut seen = HashSet::new();
+    let mut objects = Vec::new();
+    // Objects still to visit, and visited ones waiting for their references
+    let mut stack: Vec<(String, bool)> = tips.iter().rev().map(|id| (id.clone(), false)).collect();
+
+    while let Some((id, visited)) = stack.pop() {
+        if visited {
+            objects.push(id);
+            continue;
+        }
+        if !seen.insert(id.clone()) || known(&id) {
+            continue;
+        }
+
+        let (kind, data) = store.read(&id)?;
+        let mut referenced = Vec::new();
+        match kind {
+            ObjectKind::Commit => {
+                let commit = Commit::deserialize(&id, &data)?;
+                referenced.push(commit.get_tree().to_string());
+                referenced.extend(commit.parents().iter().cloned());
+            }
+            ObjectKind::Tree => {
+                for entry in Tree::deserialize(&data, store.algorithm())?.entries() {
+                    referenced.push(entry.id.clone());
+                }
+            }
+            ObjectKind::Tag => {
+                let text = String::from_utf8_lossy(&data);
+                if let Some(target) = text.lines().find_map(|line| line.strip_prefix("object ")) {
+                    referenced.push(target.to_string());
+                }
+            }
+            ObjectKind::Blob => {}
+        }
+        stack.push((id, true));
+        for reference in referenced.into_iter().rev() {
+            if !seen.contains(&reference) {
+                stack.push((reference, false));
+            }
+        }
+    }
+    Ok(objects)
+}
@@ -1,161 +1,1491 @@
 #![allow(unused)]
 
-use globset::{Glob, GlobSet, GlobSetBuilder};
-use std::collections::HashMap;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
+use std::env;
 use std::fs;
 use std::io::{Error, ErrorKind, Result};
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use crate::git::blob::Blob;
 use crate::git::branch::Branch;
-use crate::git::commit::Commit;
-use crate::git::diff::{DiffResult, Differ};
-use crate::git::index::Index;
-use crate::git::merge::{MergeConflict, Merger};
+use crate::git::commit::{Commit, Signature};
+use crate::git::config::{remove_section, set_value, unset_value, Config, IdentityRole};
+use crate::git::diff::{DiffResult, DiffType, Differ};
+use crate::git::hash::HashAlgorithm;
+use crate::git::http::Url;
+use crate::git::ignore::{Ignore, IgnorePattern};
+use crate::git::index::{Index, IndexEntry};
+use crate::git::log::{LogEntry, LogOptions};
+use crate::git::merge::{FastForwardMode, MergeConflict, MergeLabels, MergeStatus, Merger};
+use crate::git::merge_base::{ancestors, merge_bases};
+use crate::git::object_store::{ObjectKind, ObjectStore, RepackSummary};
+use crate::git::protocol::HttpRemote;
+use crate::git::remote::{
+    copy_missing_objects, objects_to_send, update_status, RefUpdate, RefUpdateStatus, Remote,
+};
+use crate::git::revision::{AncestryStep, Revision, RevisionBase, RevisionRange};
+use crate::git::status::{FileStatus, StatusEntry};
+use crate::git::storage::{
+    delete_ref, list_refs, load_git_branches, read_ref, save_git_branch, write_ref, StorageFormat,
+};
+use crate::git::tree::{FileMode, Tree};
+use crate::git::worktree::WorkTree;
+
+/// How far `reset` goes beyond moving the current branch.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResetMode {
+    /// Only move the branch, keeping the index and working tree
+    Soft,
+    /// Also reset the index, keeping the working tree
+    #[default]
+    Mixed,
+    /// Also reset the index and the working tree
+    Hard,
+}
 
 #[derive(Debug)]
 pub struct Repository {
+    /// The work tree
     root_path: PathBuf,
+    /// Where refs, objects, the index and config live; `.git-scm` in the work
+    /// tree unless given explicitly
+    git_dir: PathBuf,
+    storage: StorageFormat,
     index: Index,
-    commits: HashMap<String, Commit>,
+    objects: ObjectStore,
+    config: Config,
     branches: HashMap<String, Branch>,
+    /// The checked out branch, `HEAD` when HEAD is detached
     current_branch: String,
+    /// The commit HEAD points at directly, when no branch is checked out
+    detached_head: Option<String>,
 }
 
 impl Repository {
     pub fn init<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::init_with_object_format(path, HashAlgorithm::default())
+    }
+
+    pub fn init_with_object_format<P: AsRef<Path>>(
+        path: P,
+        algorithm: HashAlgorithm,
+    ) -> Result<Self> {
+        Self::init_with(path, algorithm, StorageFormat::Native)
+    }
+
+    /// Creates a repository in `path` laid out as `storage` says, or reopens
+    /// the one already there.
+    pub fn init_with<P: AsRef<Path>>(
+        path: P,
+        algorithm: HashAlgorithm,
+        storage: StorageFormat,
+    ) -> Result<Self> {
         let root_path = path.as_ref().to_path_buf();
 
-        // Create .vcrs directory
-        let git_dir = root_path.join(".git-scm");
+        // Reuse the existing state if the repository was already initialized,
+        // as long as it is stored and hashes objects the same way
+        if Self::is_initialized(&root_path) {
+            return Self::reinit(Self::open(root_path)?, algorithm, storage);
+        }
+
+        let git_dir = root_path.join(storage.dir_name());
+        Self::create(root_path, git_dir, algorithm, storage)
+    }
+
+    /// Creates a bare repository, one without a work tree, directly in
+    /// `path`, or reopens the one already there. Bare repositories are what
+    /// others push to.
+    pub fn init_bare<P: AsRef<Path>>(
+        path: P,
+        algorithm: HashAlgorithm,
+        storage: StorageFormat,
+    ) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if path.join("HEAD").is_file() {
+            return Self::reinit(Self::open_with(&path, &path)?, algorithm, storage);
+        }
+        Self::create(path.clone(), path, algorithm, storage)
+    }
+
+    fn reinit(repo: Self, algorithm: HashAlgorithm, storage: StorageFormat) -> Result<Self> {
+        if repo.objects.algorithm() != algorithm {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Attempt to reinitialize repository with a different object format",
+            ));
+        }
+        if repo.storage != storage {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Attempt to reinitialize repository with a different storage format",
+            ));
+        }
+        Ok(repo)
+    }
+
+    /// Lays out a new repository in `git_dir`, which is `root_path` itself
+    /// for a bare one.
+    fn create(
+        root_path: PathBuf,
+        git_dir: PathBuf,
+        algorithm: HashAlgorithm,
+        storage: StorageFormat,
+    ) -> Result<Self> {
+        let bare = root_path == git_dir;
         fs::create_dir_all(git_dir.join("objects"))?;
-        fs::create_dir_all(git_dir.join("refs"))?;
+        fs::create_dir_all(git_dir.join("refs").join("heads"))?;
+        let root_path = fs::canonicalize(root_path)?;
+        let git_dir = fs::canonicalize(git_dir)?;
+
+        // Record the object format so every later invocation hashes the same way
+        let config_path = git_dir.join("config");
+        if algorithm == HashAlgorithm::Sha1 {
+            set_value(&config_path, "core.repositoryformatversion", "0")?;
+        } else {
+            set_value(&config_path, "core.repositoryformatversion", "1")?;
+            set_value(&config_path, "extensions.objectformat", algorithm.name())?;
+        }
+        // Settings stock Git writes into every new repository
+        if storage == StorageFormat::Git {
+            fs::create_dir_all(git_dir.join("refs").join("tags"))?;
+            set_value(&config_path, "core.filemode", "true")?;
+            set_value(&config_path, "core.bare", &bare.to_string())?;
+        } else if bare {
+            // Only a directory named `.git-scm` is recognized as native
+            set_value(&config_path, "core.storage", storage.name())?;
+            set_value(&config_path, "core.bare", "true")?;
+        }
+        let config = Config::load(Some(&git_dir))?;
 
-        // Create main branch
+        // Create the initial branch, `main` unless configured otherwise
+        let initial_branch = config
+            .get("init.defaultBranch")
+            .unwrap_or("main")
+            .to_string();
         let mut branches = HashMap::new();
-        let main_branch = Branch::new("main".to_string());
-        branches.insert("main".to_string(), main_branch);
+        branches.insert(initial_branch.clone(), Branch::new(initial_branch.clone()));
 
-        Ok(Repository {
+        let repo = Repository {
             root_path,
+            git_dir: git_dir.clone(),
+            storage,
             index: Index::new(),
-            commits: HashMap::new(),
+            objects: Self::object_store(&git_dir, algorithm, storage),
+            config,
+            branches,
+            current_branch: initial_branch,
+            detached_head: None,
+        };
+        repo.save()?;
+
+        Ok(repo)
+    }
+
+    /// Whether `path` already holds a repository in its `.git-scm` or `.git`.
+    pub fn is_initialized<P: AsRef<Path>>(path: P) -> bool {
+        Self::existing_git_dir(path.as_ref()).is_some()
+    }
+
+    /// The git directory of the work tree at `path`, preferring `.git-scm`
+    /// when both exist.
+    fn existing_git_dir(path: &Path) -> Option<PathBuf> {
+        [StorageFormat::Native, StorageFormat::Git]
+            .iter()
+            .map(|storage| path.join(storage.dir_name()))
+            .find(|git_dir| git_dir.join("HEAD").is_file())
+    }
+
+    /// Whether `path` is itself a git directory, as bare repositories are.
+    fn is_git_dir(path: &Path) -> bool {
+        path.join("HEAD").is_file() && path.join("objects").is_dir() && path.join("refs").is_dir()
+    }
+
+    /// Opens the repository whose work tree contains `start`, looking in
+    /// `start` and then each of its parent directories. A bare repository
+    /// is found from inside its git directory.
+    pub fn discover<P: AsRef<Path>>(start: P) -> Result<Self> {
+        let start = fs::canonicalize(start.as_ref())?;
+        let found = start
+            .ancestors()
+            .find(|dir| Self::is_initialized(dir) || Self::is_git_dir(dir));
+        match found {
+            Some(root) if Self::is_initialized(root) => Self::open(root),
+            Some(git_dir) => Self::open_with(git_dir, git_dir),
+            None => Err(Error::new(
+                ErrorKind::NotFound,
+                format!(
+                    "Not a git-scm repository (or any of the parent directories): {}",
+                    start.display()
+                ),
+            )),
+        }
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let git_dir = Self::existing_git_dir(path).unwrap_or_else(|| path.join(".git-scm"));
+        Self::open_with(&git_dir, path)
+    }
+
+    /// Opens the repository stored in `git_dir` with its work tree at
+    /// `work_tree`, which need not contain it. A repository configured as
+    /// bare has no work tree, whatever `work_tree` says.
+    pub fn open_with(git_dir: &Path, work_tree: &Path) -> Result<Self> {
+        if !git_dir.join("HEAD").is_file() {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("Not a git-scm repository: {}", git_dir.display()),
+            ));
+        }
+
+        // Absolute, so paths given relative to any directory can be mapped
+        // into the work tree
+        let git_dir = fs::canonicalize(git_dir)?;
+        let root_path = fs::canonicalize(work_tree)?;
+
+        let config = Config::load(Some(&git_dir))?;
+        let algorithm = match config.get("extensions.objectformat") {
+            Some(name) => HashAlgorithm::parse(name)?,
+            None => HashAlgorithm::default(),
+        };
+        let root_path = match config.get_bool("core.bare")? {
+            Some(true) => git_dir.clone(),
+            _ => root_path,
+        };
+
+        // HEAD holds a symbolic reference to the checked out branch, or a
+        // commit id when it is detached
+        let head = fs::read_to_string(git_dir.join("HEAD"))?;
+        let head = head.trim();
+        let (current_branch, detached_head) = match head.strip_prefix("ref: refs/heads/") {
+            Some(branch) => (branch.to_string(), None),
+            None if head.len() == algorithm.hex_len()
+                && head.bytes().all(|b| b.is_ascii_hexdigit()) =>
+            {
+                ("HEAD".to_string(), Some(head.to_ascii_lowercase()))
+            }
+            None => return Err(Error::new(ErrorKind::InvalidData, "Malformed HEAD")),
+        };
+
+        let storage = StorageFormat::detect(&git_dir, &config)?;
+        let mut branches = match storage {
+            StorageFormat::Native => {
+                let mut branches = HashMap::new();
+                Self::load_branches(&git_dir.join("refs").join("heads"), &mut branches)?;
+                branches
+            }
+            StorageFormat::Git => load_git_branches(&git_dir)?,
+        };
+        // Git has no ref for a branch without commits
+        if detached_head.is_none() {
+            branches
+                .entry(current_branch.clone())
+                .or_insert_with(|| Branch::new(current_branch.clone()));
+        }
+
+        Ok(Repository {
+            index: Self::load_index(&git_dir, algorithm, storage)?,
+            objects: Self::object_store(&git_dir, algorithm, storage),
+            config,
+            root_path,
+            git_dir,
+            storage,
             branches,
-            current_branch: "main".to_string(),
+            current_branch,
+            detached_head,
+        })
+    }
+
+    /// Opens the repository at `path`, either a work tree or a bare git
+    /// directory.
+    pub fn open_source(path: &Path) -> Result<Self> {
+        if Self::is_initialized(path) {
+            Self::open(path)
+        } else if Self::is_git_dir(path) {
+            Self::open_with(path, path)
+        } else {
+            Err(Error::new(
+                ErrorKind::NotFound,
+                format!("Repository {} does not exist", path.display()),
+            ))
+        }
+    }
+
+    /// Clones the repository at `source` into the new directory `target`.
+    ///
+    /// Objects are hardlinked when both are on the same filesystem and
+    /// copied otherwise. The source's branches become remote-tracking
+    /// branches of `origin`, recorded in the configuration, and its checked
+    /// out branch is created locally and checked out.
+    pub fn clone_local(source: &Path, target: &Path) -> Result<Self> {
+        let source = Self::open_source(source)?;
+        Self::check_clone_target(target)?;
+
+        fs::create_dir_all(target)?;
+        let mut repo = Self::init_with(target, source.objects.algorithm(), source.storage)?;
+        link_or_copy_dir(
+            &source.git_dir.join("objects"),
+            &repo.git_dir.join("objects"),
+        )?;
+
+        // The source's branches become `origin/<branch>`, and its tags are
+        // taken over as they are
+        for (name, branch) in &source.branches {
+            if let Some(head) = branch.head_commit_id() {
+                write_ref(
+                    &repo.git_dir,
+                    &format!("refs/remotes/origin/{}", name),
+                    head,
+                )?;
+            }
+        }
+        // A detached source has no branch to follow, so the clone starts on
+        // its own initial branch
+        let default_branch = match source.detached_head {
+            Some(_) => repo.current_branch.clone(),
+            None => source.current_branch.clone(),
+        };
+        write_ref(
+            &repo.git_dir,
+            "refs/remotes/origin/HEAD",
+            &format!("ref: refs/remotes/origin/{}", default_branch),
+        )?;
+        for (name, id) in list_refs(&source.git_dir, "refs/tags/")? {
+            write_ref(&repo.git_dir, &format!("refs/tags/{}", name), &id)?;
+        }
+
+        let config_path = repo.config_path();
+        let url = source.git_dir.to_string_lossy().to_string();
+        let url = match source.git_dir.file_name() {
+            // Point at the work tree when there is one, as Git does
+            Some(name) if name == ".git-scm" || name == ".git" => {
+                source.root_path.to_string_lossy().to_string()
+            }
+            _ => url,
+        };
+        set_value(&config_path, "remote.origin.url", &url)?;
+        set_value(
+            &config_path,
+            "remote.origin.fetch",
+            "+refs/heads/*:refs/remotes/origin/*",
+        )?;
+        repo.check_out_clone(&default_branch, source.branch_head(&default_branch).ok())?;
+        Ok(repo)
+    }
+
+    /// Clones the repository served at the HTTP `url` into the new directory
+    /// `target`, fetching it as the remote `origin` and checking out the
+    /// branch its `HEAD` points at.
+    pub fn clone_url(url: &str, target: &Path) -> Result<Self> {
+        let algorithm = HttpRemote::connect(url)?.algorithm();
+        Self::check_clone_target(target)?;
+
+        fs::create_dir_all(target)?;
+        let mut repo = Self::init_with(target, algorithm, StorageFormat::default())?;
+        repo.add_remote("origin", url)?;
+        let (_, head) = repo.fetch_from(&repo.remote("origin")?)?;
+
+        let default_branch = head.unwrap_or_else(|| repo.current_branch.clone());
+        write_ref(
+            &repo.git_dir,
+            "refs/remotes/origin/HEAD",
+            &format!("ref: refs/remotes/origin/{}", default_branch),
+        )?;
+        let head = read_ref(
+            &repo.git_dir,
+            &format!("refs/remotes/origin/{}", default_branch),
+        )?;
+        repo.check_out_clone(&default_branch, head)?;
+        Ok(repo)
+    }
+
+    fn check_clone_target(target: &Path) -> Result<()> {
+        if target.exists() && fs::read_dir(target)?.next().is_some() {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!(
+                    "Destination path {} already exists and is not an empty directory",
+                    target.display()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Starts the branch `name` of a new clone at `head`, replacing the one
+    /// `init` created, sets it up to track `origin` and checks it out.
+    fn check_out_clone(&mut self, name: &str, head: Option<String>) -> Result<()> {
+        let config_path = self.config_path();
+        set_value(&config_path, &format!("branch.{}.remote", name), "origin")?;
+        set_value(
+            &config_path,
+            &format!("branch.{}.merge", name),
+            &format!("refs/heads/{}", name),
+        )?;
+        self.config = Config::load(Some(&self.git_dir))?;
+
+        let initial_ref = self
+            .git_dir
+            .join("refs")
+            .join("heads")
+            .join(&self.current_branch);
+        if initial_ref.is_file() {
+            fs::remove_file(initial_ref)?;
+        }
+        self.branches.clear();
+        self.branches.insert(
+            name.to_string(),
+            Branch::with_commits(name.to_string(), head.into_iter().collect()),
+        );
+        self.current_branch = name.to_string();
+        self.save()?;
+
+        let target_tree = self.branch_tree(name)?;
+        self.update_worktree(&BTreeMap::new(), &target_tree, true, "clone")
+    }
+
+    /// Whether the repository has no work tree of its own.
+    pub fn is_bare(&self) -> bool {
+        self.root_path == self.git_dir
+    }
+
+    /// The configured remotes.
+    pub fn remotes(&self) -> Result<Vec<Remote>> {
+        Remote::list(&self.config)
+    }
+
+    pub fn remote(&self, name: &str) -> Result<Remote> {
+        Remote::from_config(&self.config, name)?
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, format!("No such remote: {}", name)))
+    }
+
+    /// Records the repository at `url` as the remote `name`, tracking its
+    /// branches under `refs/remotes/<name>/`.
+    pub fn add_remote(&mut self, name: &str, url: &str) -> Result<()> {
+        if Remote::from_config(&self.config, name)?.is_some() {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("Remote {} already exists", name),
+            ));
+        }
+        if name.is_empty() || name.contains(['/', ' ', '*', ':']) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid remote name: {}", name),
+            ));
+        }
+
+        let config_path = self.config_path();
+        set_value(&config_path, &format!("remote.{}.url", name), url)?;
+        set_value(
+            &config_path,
+            &format!("remote.{}.fetch", name),
+            &Remote::default_fetch(name),
+        )?;
+        self.config = Config::load(Some(&self.git_dir))?;
+        Ok(())
+    }
+
+    /// Forgets the remote `name`, its remote-tracking branches and the
+    /// branches set up to track it.
+    pub fn remove_remote(&mut self, name: &str) -> Result<()> {
+        let remote = self.remote(name)?;
+        let config_path = self.config_path();
+        remove_section(&config_path, &format!("remote.{}", name))?;
+
+        for (branch, _) in list_refs(&self.git_dir, &format!("refs/remotes/{}/", name))? {
+            delete_ref(&self.git_dir, &format!("refs/remotes/{}/{}", name, branch))?;
+        }
+        let tracking_dir = self.git_dir.join("refs").join("remotes").join(name);
+        if tracking_dir.is_dir() {
+            fs::remove_dir_all(tracking_dir)?;
+        }
+
+        for branch in self.branches.keys() {
+            let key = format!("branch.{}.remote", branch);
+            if self.config.get(&key) == Some(name) {
+                unset_value(&config_path, &key)?;
+                unset_value(&config_path, &format!("branch.{}.merge", branch))?;
+            }
+        }
+        self.config = Config::load(Some(&self.git_dir))?;
+        Ok(())
+    }
+
+    /// The remote the current branch tracks, `origin` unless configured.
+    pub fn default_remote(&self) -> String {
+        self.config
+            .get(&format!("branch.{}.remote", self.current_branch))
+            .unwrap_or("origin")
+            .to_string()
+    }
+
+    /// Opens the repository a remote points at, which has to hash objects
+    /// the same way as this one.
+    fn open_remote(&self, remote: &Remote) -> Result<Self> {
+        // Relative paths are taken from the top of the work tree, as in Git
+        let repo = Self::open_source(&self.root_path.join(remote.path()?))?;
+        self.check_object_format(remote, repo.objects.algorithm())?;
+        Ok(repo)
+    }
+
+    /// Connects to a remote served over HTTP, which has to hash objects the
+    /// same way as this one.
+    fn connect_remote(&self, remote: &Remote) -> Result<HttpRemote> {
+        let http = HttpRemote::connect(&remote.url)?;
+        self.check_object_format(remote, http.algorithm())?;
+        Ok(http)
+    }
+
+    fn check_object_format(&self, remote: &Remote, algorithm: HashAlgorithm) -> Result<()> {
+        if algorithm == self.objects.algorithm() {
+            return Ok(());
+        }
+        Err(Error::new(
+            ErrorKind::InvalidData,
+            format!(
+                "Remote {} uses {} object ids, not {}",
+                remote.name,
+                algorithm.name(),
+                self.objects.algorithm().name()
+            ),
+        ))
+    }
+
+    /// Copies the branches of the remote `name` into its remote-tracking
+    /// branches, and its tags not known here, along with every object they
+    /// need that is missing here.
+    pub fn fetch(&mut self, name: &str) -> Result<Vec<RefUpdate>> {
+        let remote = self.remote(name)?;
+        Ok(self.fetch_from(&remote)?.0)
+    }
+
+    /// Fetches from `remote`, also returning the branch its `HEAD` points
+    /// at, if it is known.
+    fn fetch_from(&mut self, remote: &Remote) -> Result<(Vec<RefUpdate>, Option<String>)> {
+        let (refs, head) = if Url::is_http(&remote.url) {
+            let http = self.connect_remote(remote)?;
+            let advertised = http.ls_refs(&["refs/heads/", "refs/tags/"])?;
+            let head = advertised
+                .iter()
+                .find(|advertised| advertised.name == "HEAD")
+                .and_then(|head| head.target.as_deref()?.strip_prefix("refs/heads/"))
+                .map(str::to_string);
+            let refs: BTreeMap<String, String> = advertised
+                .into_iter()
+                .filter(|advertised| advertised.name != "HEAD")
+                .map(|advertised| (advertised.name, advertised.id))
+                .collect();
+
+            let mut wants: Vec<String> = refs
+                .values()
+                .filter(|id| !self.objects.exists(id))
+                .cloned()
+                .collect();
+            wants.sort();
+            wants.dedup();
+            if !wants.is_empty() {
+                let pack = http.fetch_pack(&wants, &self.local_tips()?)?;
+                self.objects.unpack(&pack)?;
+            }
+            (refs, head)
+        } else {
+            let source = self.open_remote(remote)?;
+            let mut refs = BTreeMap::new();
+            for (name, branch) in &source.branches {
+                if let Some(head) = branch.head_commit_id() {
+                    refs.insert(format!("refs/heads/{}", name), head.to_string());
+                }
+            }
+            for (name, id) in list_refs(&source.git_dir, "refs/tags/")? {
+                refs.insert(format!("refs/tags/{}", name), id);
+            }
+            let tips: Vec<String> = refs.values().cloned().collect();
+            copy_missing_objects(&source.objects, &self.objects, &tips)?;
+            let head = source
+                .detached_head
+                .is_none()
+                .then_some(source.current_branch);
+            (refs, head)
+        };
+
+        let mut updates = Vec::new();
+        for (source_ref, new) in refs {
+            // Tags are taken over as they are, unless one of the same name
+            // exists here
+            if source_ref.starts_with("refs/tags/") {
+                if read_ref(&self.git_dir, &source_ref)?.is_none() {
+                    write_ref(&self.git_dir, &source_ref, &new)?;
+                    updates.push(RefUpdate {
+                        source: source_ref.clone(),
+                        destination: source_ref,
+                        old: None,
+                        new,
+                        status: RefUpdateStatus::Created,
+                    });
+                }
+                continue;
+            }
+
+            let Some((tracking, force)) = remote.tracking_ref(&source_ref) else {
+                continue;
+            };
+            let old = read_ref(&self.git_dir, &tracking)?;
+            let status = update_status(&self.objects, old.as_deref(), &new, force)?;
+            if status != RefUpdateStatus::Rejected && status != RefUpdateStatus::UpToDate {
+                write_ref(&self.git_dir, &tracking, &new)?;
+            }
+            updates.push(RefUpdate {
+                source: source_ref,
+                destination: tracking,
+                old,
+                new,
+                status,
+            });
+        }
+        Ok((updates, head))
+    }
+
+    /// The commits of every branch, remote-tracking branch and tag, which
+    /// a server can leave out of what it sends.
+    fn local_tips(&self) -> Result<Vec<String>> {
+        let mut tips: Vec<String> = self
+            .branches
+            .values()
+            .filter_map(|branch| branch.head_commit_id())
+            .map(str::to_string)
+            .collect();
+        for prefix in ["refs/remotes/", "refs/tags/"] {
+            tips.extend(
+                list_refs(&self.git_dir, prefix)?
+                    .into_values()
+                    .filter(|value| !value.starts_with("ref: ")),
+            );
+        }
+        tips.sort();
+        tips.dedup();
+        Ok(tips)
+    }
+
+    /// Sends `branch` to the branch of the same name of the remote `name`,
+    /// along with every object it needs that the remote lacks.
+    ///
+    /// Unless `force` is given, the remote branch must be an ancestor of
+    /// `branch`, as it is when everything it has was fetched and merged.
+    /// The checked out branch of a repository with a work tree is never
+    /// updated, since its files would no longer match.
+    pub fn push(&mut self, name: &str, branch: &str, force: bool) -> Result<RefUpdate> {
+        let remote = self.remote(name)?;
+        let new = self.branch_head(branch)?;
+        let target_ref = format!("refs/heads/{}", branch);
+
+        let (old, status) = if Url::is_http(&remote.url) {
+            let http = self.connect_remote(&remote)?;
+            let theirs = http.receive_pack_refs()?;
+            let old = theirs.get(&target_ref).cloned();
+            let status = update_status(&self.objects, old.as_deref(), &new, force)?;
+            if status != RefUpdateStatus::Rejected && status != RefUpdateStatus::UpToDate {
+                let theirs: Vec<String> = theirs.into_values().collect();
+                let ids = objects_to_send(&self.objects, std::slice::from_ref(&new), &theirs)?;
+                let pack = self.objects.pack_data(&ids)?;
+                if let Some(reason) = http.send_pack(&target_ref, old.as_deref(), &new, pack)? {
+                    return Err(Error::other(format!(
+                        "The remote refused to update {}: {}",
+                        branch, reason
+                    )));
+                }
+            }
+            (old, status)
+        } else {
+            let mut destination = self.open_remote(&remote)?;
+            let old = destination
+                .branches
+                .get(branch)
+                .and_then(|branch| branch.head_commit_id())
+                .map(str::to_string);
+            let status = update_status(&self.objects, old.as_deref(), &new, force)?;
+            if status != RefUpdateStatus::Rejected && status != RefUpdateStatus::UpToDate {
+                copy_missing_objects(
+                    &self.objects,
+                    &destination.objects,
+                    std::slice::from_ref(&new),
+                )?;
+                destination
+                    .receive_update(&target_ref, old.as_deref(), &new)
+                    .map_err(|error| {
+                        Error::other(format!(
+                            "The remote refused to update {}: {}",
+                            branch, error
+                        ))
+                    })?;
+            }
+            (old, status)
+        };
+
+        // The remote-tracking branch follows what the remote now has
+        if status != RefUpdateStatus::Rejected {
+            if let Some((tracking, _)) = remote.tracking_ref(&target_ref) {
+                write_ref(&self.git_dir, &tracking, &new)?;
+            }
+        }
+        Ok(RefUpdate {
+            source: target_ref.clone(),
+            destination: target_ref,
+            old,
+            new,
+            status,
         })
     }
 
-    fn parse_gitignore(&self) -> Result<GlobSet> {
-        let gitignore_path = self.root_path.join(".gitignore");
-        let mut builder = GlobSetBuilder::new();
+    /// The branches and tags others can fetch, by full name.
+    pub fn refs(&self) -> Result<BTreeMap<String, String>> {
+        let mut refs = BTreeMap::new();
+        for (name, branch) in &self.branches {
+            if let Some(head) = branch.head_commit_id() {
+                refs.insert(format!("refs/heads/{}", name), head.to_string());
+            }
+        }
+        for (name, id) in list_refs(&self.git_dir, "refs/tags/")? {
+            refs.insert(format!("refs/tags/{}", name), id);
+        }
+        Ok(refs)
+    }
+
+    /// Moves the branch or tag `name`, a full ref name, from `old` to `new`
+    /// for someone pushing to this repository, whose objects must all be
+    /// here already.
+    ///
+    /// The ref must still be at `old`, so concurrent pushes cannot undo one
+    /// another. The checked out branch of a repository with a work tree is
+    /// never updated, since its files would no longer match.
+    pub fn receive_update(&mut self, name: &str, old: Option<&str>, new: &str) -> Result<()> {
+        if !self.objects.exists(new) {
+            return Err(Error::new(ErrorKind::NotFound, "missing necessary objects"));
+        }
+        let current = self.refs()?.remove(name);
+        if current.as_deref() != old {
+            return Err(Error::other("stale info, fetch first"));
+        }
+
+        if let Some(branch) = name.strip_prefix("refs/heads/") {
+            if !self.is_bare() && branch == self.current_branch {
+                return Err(Error::other("branch is currently checked out"));
+            }
+            self.branches
+                .entry(branch.to_string())
+                .or_insert_with(|| Branch::new(branch.to_string()))
+                .add_commit(new.to_string());
+            self.save()
+        } else if name.starts_with("refs/tags/") {
+            write_ref(&self.git_dir, name, new)
+        } else {
+            Err(Error::new(
+                ErrorKind::InvalidInput,
+                "only branches and tags can be pushed",
+            ))
+        }
+    }
+
+    fn object_store(
+        git_dir: &Path,
+        algorithm: HashAlgorithm,
+        storage: StorageFormat,
+    ) -> ObjectStore {
+        let objects = ObjectStore::new(git_dir, algorithm);
+        match storage {
+            StorageFormat::Native => objects,
+            StorageFormat::Git => objects.compressed(),
+        }
+    }
+
+    fn load_index(
+        git_dir: &Path,
+        algorithm: HashAlgorithm,
+        storage: StorageFormat,
+    ) -> Result<Index> {
+        match storage {
+            StorageFormat::Native => Index::load_from_disk(git_dir),
+            StorageFormat::Git => Index::load_dirc(git_dir, algorithm),
+        }
+    }
+
+    fn save_index(&self) -> Result<()> {
+        match self.storage {
+            StorageFormat::Native => self.index.save_to_disk(&self.git_dir),
+            StorageFormat::Git => {
+                self.index
+                    .save_dirc(&self.git_dir, &self.root_path, self.objects.algorithm())
+            }
+        }
+    }
+
+    fn load_branches(dir: &Path, branches: &mut HashMap<String, Branch>) -> Result<()> {
+        if !dir.is_dir() {
+            return Ok(());
+        }
+
+        for entry in fs::read_dir(dir)? {
+            let entry_path = entry?.path();
+
+            // Branch names such as `feature/login` are stored in subdirectories
+            if entry_path.is_dir() {
+                Self::load_branches(&entry_path, branches)?;
+                continue;
+            }
+
+            let branch: Branch = bincode::deserialize(&fs::read(&entry_path)?).map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Deserialization error: {}", e),
+                )
+            })?;
+            branches.insert(branch.name().to_string(), branch);
+        }
+
+        Ok(())
+    }
+
+    /// Writes branch records and HEAD back to the git directory.
+    fn save(&self) -> Result<()> {
+        let git_dir = &self.git_dir;
 
-        if gitignore_path.exists() {
-            let patterns = fs::read_to_string(gitignore_path)?;
-            for line in patterns.lines() {
-                let trimmed = line.trim();
-                if !trimmed.is_empty() && !trimmed.starts_with('#') {
-                    builder.add(Glob::new(trimmed).map_err(|e| {
-                        Error::new(std::io::ErrorKind::InvalidInput, e.to_string())
-                    })?);
+        match self.storage {
+            StorageFormat::Native => {
+                let heads_dir = git_dir.join("refs").join("heads");
+                for (name, branch) in &self.branches {
+                    let branch_path = heads_dir.join(name);
+                    if let Some(parent) = branch_path.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    let serialized = bincode::serialize(branch)
+                        .map_err(|e| Error::other(format!("Serialization error: {}", e)))?;
+                    fs::write(branch_path, serialized)?;
+                }
+            }
+            StorageFormat::Git => {
+                let who = self.reflog_identity()?;
+                for branch in self.branches.values() {
+                    save_git_branch(git_dir, branch, &who)?;
                 }
             }
         }
 
-        builder
-            .build()
-            .map_err(|e| Error::new(ErrorKind::Other, e.to_string()))
+        let head = match &self.detached_head {
+            Some(id) => format!("{}\n", id),
+            None => format!("ref: refs/heads/{}\n", self.current_branch),
+        };
+        fs::write(git_dir.join("HEAD"), head)?;
+
+        Ok(())
+    }
+
+    /// Who signs reflog entries: the committer, or nobody when no identity
+    /// is configured.
+    fn reflog_identity(&self) -> Result<Signature> {
+        if let Ok(committer) = self.config.identity(IdentityRole::Committer) {
+            return Ok(committer);
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_err(Error::other)?
+            .as_secs();
+        Ok(Signature::new(String::new(), String::new(), now, 0))
+    }
+
+    /// The ignore rules of the work tree.
+    fn ignore(&self) -> Result<Ignore> {
+        Ignore::new(&self.root_path, &self.git_dir, &self.config)
     }
 
+    /// The pattern deciding whether `path` is ignored, as `check-ignore`
+    /// reports it. Tracked files are never ignored.
+    pub fn check_ignore(&self, path: &Path) -> Result<Option<IgnorePattern>> {
+        let path = self.repo_path(path)?;
+        if self.index.get_entries().contains_key(&path) {
+            return Ok(None);
+        }
+        let is_dir = self.root_path.join(&path).is_dir();
+        self.ignore()?.matching_pattern(&path, is_dir)
+    }
+
+    /// Turns a path given relative to the current directory, or an absolute
+    /// one, into the repository-relative form the index uses: `/`-separated
+    /// names without `.` or `..`. The root itself is the empty path.
+    pub fn repo_path(&self, path: &Path) -> Result<PathBuf> {
+        let absolute = env::current_dir()?.join(path);
+
+        let mut normalized = PathBuf::new();
+        for component in absolute.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    normalized.pop();
+                }
+                other => normalized.push(other),
+            }
+        }
+
+        let relative = normalized.strip_prefix(&self.root_path).map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!(
+                    "{} is outside repository at {}",
+                    path.display(),
+                    self.root_path.display()
+                ),
+            )
+        })?;
+        let names: Vec<String> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect();
+        Ok(PathBuf::from(names.join("/")))
+    }
+
+    /// Stages `path`, a file or a directory given relative to the current
+    /// directory.
     pub fn add(&mut self, path: &Path) -> Result<()> {
-        let ignore_set = self.parse_gitignore()?;
+        let path = self.repo_path(path)?;
+        let ignore = self.ignore()?;
+        self.add_path(&path, &ignore)?;
+
+        // Persist the index after adding files
+        self.save_index()
+    }
+
+    /// Stages the repository-relative `path`.
+    fn add_path(&mut self, path: &Path, ignore: &Ignore) -> Result<()> {
+        let full_path = self.root_path.join(path);
 
-        if path.is_dir() {
-            for entry in fs::read_dir(path)? {
+        // Adding a tracked file that no longer exists stages its deletion
+        if fs::symlink_metadata(&full_path).is_err() && self.index.get_entries().contains_key(path)
+        {
+            self.index.remove(&path.to_path_buf());
+            return Ok(());
+        }
+
+        // Symlinks are staged as links rather than followed
+        let metadata = fs::symlink_metadata(&full_path)?;
+        if metadata.is_dir() {
+            for entry in fs::read_dir(&full_path)? {
                 let entry = entry?;
-                let entry_path = entry.path();
+                let entry_path = path.join(entry.file_name());
 
-                // Skip ignored files and directories
-                if ignore_set.is_match(&entry_path) {
+                // Skip ignored files and directories, unless already tracked
+                let is_dir = entry.file_type()?.is_dir();
+                let tracked = self.index.get_entries().contains_key(&entry_path);
+                if !tracked && ignore.is_ignored(&entry_path, is_dir)? {
                     continue;
                 }
 
-                if entry_path.is_file() {
-                    let blob = Blob::from_file(&entry_path)?;
-                    self.index.add(entry_path, blob);
-                } else if entry_path.is_dir() {
+                if is_dir {
                     // Recursively add directory contents
-                    self.add(&entry_path)?;
+                    self.add_path(&entry_path, ignore)?;
+                } else {
+                    self.stage_file(&entry_path)?;
                 }
             }
-        } else if path.is_file() {
-            // Skip ignored files
-            if ignore_set.is_match(path) {
+
+            // Tracked files deleted from the directory are staged as deletions
+            let missing: Vec<PathBuf> = self
+                .index
+                .get_entries()
+                .keys()
+                .filter(|tracked| {
+                    tracked.starts_with(path)
+                        && fs::symlink_metadata(self.root_path.join(tracked)).is_err()
+                })
+                .cloned()
+                .collect();
+            for tracked in &missing {
+                self.index.remove(tracked);
+            }
+        } else {
+            // Skip ignored files, unless already tracked
+            let tracked = self.index.get_entries().contains_key(path);
+            if !tracked && ignore.is_ignored(path, false)? {
                 return Ok(());
             }
 
-            let blob = Blob::from_file(path)?;
-            self.index.add(path.to_path_buf(), blob);
+            self.stage_file(path)?;
         }
 
-        // Persist the index after adding files
-        self.index.save_to_disk(&self.root_path)?;
-
         Ok(())
     }
 
-    pub fn commit(&mut self, message: String, author: String) -> Result<String> {
-        // Load the index from disk to ensure it's up to date
-        self.index = Index::load_from_disk(&self.root_path)?;
+    /// Writes a regular file, executable or symlink to the object store and
+    /// records it in the index under its repository-relative `path`.
+    fn stage_file(&mut self, path: &Path) -> Result<()> {
+        let full_path = self.root_path.join(path);
+        let metadata = fs::symlink_metadata(&full_path)?;
+        let mode = FileMode::from_metadata(&metadata);
+
+        let content = if mode == FileMode::Symlink {
+            // A symlink's blob holds the path it points to
+            fs::read_link(&full_path)?
+                .to_string_lossy()
+                .as_bytes()
+                .to_vec()
+        } else {
+            Blob::from_file(&full_path, self.objects.algorithm())?.to_vec()
+        };
 
-        // Convert index entries to tree (HashMap<PathBuf, Blob>)
-        let tree = self
+        let blob_id = self.objects.write(ObjectKind::Blob, &content)?;
+        self.index.add(path.to_path_buf(), blob_id, mode);
+        Ok(())
+    }
+
+    /// Stops tracking `path`, deleting it from the working tree unless
+    /// `cached` is set. Directories are only removed with `recursive`.
+    ///
+    /// The next commit records the deletion.
+    pub fn remove(&mut self, path: &Path, cached: bool, recursive: bool) -> Result<()> {
+        let path = &self.repo_path(path)?;
+        let tracked: Vec<PathBuf> = self
             .index
             .get_entries()
-            .iter()
-            .map(|(path, blob)| (path.clone(), blob.clone().to_vec()))
-            .collect::<HashMap<PathBuf, Vec<u8>>>();
+            .keys()
+            .filter(|tracked| tracked.as_path() == path || (recursive && tracked.starts_with(path)))
+            .cloned()
+            .collect();
 
-        // Get the last commit's ID for the current branch
-        let parent = self
-            .branches
-            .get(&self.current_branch)
-            .and_then(|branch| branch.head_commit_id().map(|id| id.to_string()));
+        if tracked.is_empty() {
+            let is_dir = self.index.get_entries().keys().any(|t| t.starts_with(path));
+            let hint = if is_dir && !recursive {
+                " (use -r to remove a directory)"
+            } else {
+                ""
+            };
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("Path is not tracked: {}{}", path.display(), hint),
+            ));
+        }
+
+        let worktree = WorkTree::new(&self.root_path);
+        for tracked in &tracked {
+            self.index.remove(tracked);
+            if !cached {
+                worktree.remove(tracked)?;
+            }
+        }
+
+        self.save_index()
+    }
+
+    /// Records the index as a new commit on the current branch.
+    ///
+    /// The committer comes from the configuration and the environment, as
+    /// does the author unless `author` gives one as `Name <email>`.
+    pub fn commit(&mut self, message: String, author: Option<&str>) -> Result<String> {
+        // Load the index from disk to ensure it's up to date
+        self.index = Self::load_index(&self.git_dir, self.objects.algorithm(), self.storage)?;
+
+        let conflicts = self.index.conflicts();
+        if !conflicts.is_empty() {
+            let paths: Vec<String> = conflicts.iter().map(|p| p.display().to_string()).collect();
+            return Err(Error::other(format!(
+                "Committing is not possible because you have unmerged files:\n\t{}\nFix them and use `add` to mark them resolved",
+                paths.join("\n\t")
+            )));
+        }
+
+        // The index tracks every file, so its tree is the complete snapshot
+        let tree = Tree::write_from_index(&self.objects, self.index.get_entries())?;
+
+        // Get the last commit's ID for the current branch, followed by the
+        // other side of a merge being concluded
+        let mut parents: Vec<String> = self.head_commit().into_iter().collect();
+        let merge_head_path = self.git_dir.join("MERGE_HEAD");
+        if merge_head_path.is_file() {
+            parents.extend(
+                fs::read_to_string(&merge_head_path)?
+                    .lines()
+                    .map(str::to_string),
+            );
+        }
+
+        let committer = self.config.identity(IdentityRole::Committer)?;
+        let author = match author {
+            Some(identity) => {
+                let (name, email) = identity
+                    .strip_suffix('>')
+                    .and_then(|rest| rest.rsplit_once('<'))
+                    .ok_or_else(|| {
+                        Error::new(
+                            ErrorKind::InvalidInput,
+                            format!("Author must look like 'Name <email>': {}", identity),
+                        )
+                    })?;
+                let mut signature = self
+                    .config
+                    .identity(IdentityRole::Author)
+                    .unwrap_or_else(|_| committer.clone());
+                signature.name = name.trim().to_string();
+                signature.email = email.trim().to_string();
+                signature
+            }
+            None => self.config.identity(IdentityRole::Author)?,
+        };
 
         // Create new commit
-        let commit = Commit::new(parent, tree, message, author);
+        let commit = Commit::new(
+            parents,
+            tree,
+            message,
+            author,
+            committer,
+            self.objects.algorithm(),
+        );
         let commit_id = commit.get_id().to_string();
 
         // Save commit
-        self.commits.insert(commit_id.clone(), commit);
+        self.objects
+            .write(ObjectKind::Commit, &commit.serialize())?;
 
-        // Update current branch
-        if let Some(branch) = self.branches.get_mut(&self.current_branch) {
-            branch.add_commit(commit_id.clone());
-        }
+        // Move HEAD, and the current branch with it
+        self.move_head(commit_id.clone());
 
-        // Save the repository state to disk
-        self.index = Index::new(); // Clear the index
-        self.index.save_to_disk(&self.root_path)?; // Save the cleared index
+        // The index keeps its entries so the next commit starts from this one
+        self.save()?;
+        if merge_head_path.is_file() {
+            fs::remove_file(&merge_head_path)?;
+        }
 
         Ok(commit_id)
     }
 
     pub fn log(&self) {
-        for (id, commit) in &self.commits {
-            println!("Commit ID: {}", id);
+        // Walk back from every branch head through all parents
+        let heads: Vec<&str> = self
+            .branches
+            .values()
+            .filter_map(|branch| branch.head_commit_id())
+            .collect();
+        let history = match self.history(&heads) {
+            Ok(history) => history,
+            Err(_) => return,
+        };
+
+        for commit in history {
+            println!("Commit ID: {}", commit.id);
             println!("Message: {}", commit.message);
-            println!("Author: {}", commit.author);
-            println!("Timestamp: {}", commit.timestamp);
-            println!("Parent: {:?}", commit.parent);
+            println!("Author: {}", commit.author.identity());
+            println!("Timestamp: {}", commit.timestamp());
+            if commit.is_merge() {
+                let short: Vec<&str> = commit.parents().iter().map(|p| &p[..7]).collect();
+                println!("Merge: {}", short.join(" "));
+            }
+            println!("Parents: {:?}", commit.parents());
             println!("------------------------------");
             println!("Commit Tree:");
-            for (path, blob) in &commit.tree {
-                println!("  Path: {:?}, Blob: {:?}", path, blob);
+            if let Ok(tree) = Tree::flatten(&self.objects, &commit.tree) {
+                for (path, entry) in &tree {
+                    println!(
+                        "  Path: {:?}, Mode: {}, Blob: {}",
+                        path,
+                        entry.mode.as_str(),
+                        entry.id
+                    );
+                }
             }
             println!("------------------------------");
         }
     }
 
+    /// Every commit reachable from `heads` through any of their parents,
+    /// each listed once. Commits come newest first but never before one of
+    /// their children, even when the clock says otherwise.
+    pub fn history(&self, heads: &[&str]) -> Result<Vec<Commit>> {
+        let mut commits = HashMap::new();
+        let mut pending: Vec<String> = heads.iter().map(|id| id.to_string()).collect();
+        while let Some(id) = pending.pop() {
+            if commits.contains_key(&id) {
+                continue;
+            }
+            let commit = self.read_commit(&id)?;
+            pending.extend(commit.parents().iter().cloned());
+            commits.insert(id, commit);
+        }
+
+        let mut children: HashMap<&str, usize> = HashMap::new();
+        for commit in commits.values() {
+            for parent in commit.parents() {
+                *children.entry(parent.as_str()).or_default() += 1;
+            }
+        }
+
+        // A commit is ready once all its children are listed. Among ready
+        // commits the newest goes first, and the earliest ready on a tie.
+        let mut ready = BinaryHeap::new();
+        let mut order = 0;
+        for id in heads {
+            let (id, commit) = commits.get_key_value(*id).expect("head was read");
+            if !children.contains_key(id.as_str()) && !ready.iter().any(|(_, _, r)| r == id) {
+                ready.push((commit.timestamp(), Reverse(order), id.as_str()));
+                order += 1;
+            }
+        }
+
+        let mut history = Vec::with_capacity(commits.len());
+        while let Some((_, _, id)) = ready.pop() {
+            let commit = &commits[id];
+            for parent in commit.parents() {
+                let remaining = children
+                    .get_mut(parent.as_str())
+                    .expect("parent is counted");
+                *remaining -= 1;
+                if *remaining == 0 {
+                    ready.push((commits[parent].timestamp(), Reverse(order), parent.as_str()));
+                    order += 1;
+                }
+            }
+            history.push(commit.clone());
+        }
+        Ok(history)
+    }
+
+    /// Commits in `range` that pass the filters of `options`, in the order
+    /// `log` shows them.
+    pub fn log_entries(&self, range: &str, options: &LogOptions) -> Result<Vec<LogEntry>> {
+        let (include, exclude) = self.resolve_range(range)?;
+        let heads: Vec<&str> = include.iter().map(String::as_str).collect();
+        let history = self.history(&heads)?;
+
+        let mut excluded = HashSet::new();
+        for id in &exclude {
+            excluded.extend(ancestors(&self.objects, id)?);
+        }
+
+        let mut shown = HashSet::new();
+        for commit in &history {
+            if !excluded.contains(commit.get_id())
+                && options.matches(commit)
+                && self.touches_paths(commit, options)?
+            {
+                shown.insert(commit.get_id());
+            }
+        }
+
+        // Walking from the oldest commit, map each commit to the nearest shown
+        // ones at or below it, so hidden commits drop out of the parent links
+        let mut nearest: HashMap<&str, Vec<String>> = HashMap::new();
+        let mut parents: HashMap<&str, Vec<String>> = HashMap::new();
+        for commit in history.iter().rev() {
+            let mut below = Vec::new();
+            for parent in commit.parents() {
+                for id in nearest.get(parent.as_str()).into_iter().flatten() {
+                    if !below.contains(id) {
+                        below.push(id.clone());
+                    }
+                }
+            }
+
+            if shown.contains(commit.get_id()) {
+                parents.insert(commit.get_id(), below);
+                nearest.insert(commit.get_id(), vec![commit.get_id().to_string()]);
+            } else {
+                nearest.insert(commit.get_id(), below);
+            }
+        }
+
+        let mut entries: Vec<LogEntry> = history
+            .iter()
+            .filter(|commit| shown.contains(commit.get_id()))
+            .map(|commit| LogEntry {
+                parents: parents.remove(commit.get_id()).unwrap_or_default(),
+                commit: commit.clone(),
+            })
+            .collect();
+        if let Some(max_count) = options.max_count {
+            entries.truncate(max_count);
+        }
+        Ok(entries)
+    }
+
+    /// Whether a commit changes a path `options` filters on. A merge only
+    /// counts when it differs from every parent, as in `git log <path>`.
+    fn touches_paths(&self, commit: &Commit, options: &LogOptions) -> Result<bool> {
+        if options.paths.is_empty() {
+            return Ok(true);
+        }
+
+        let filtered = |tree: &str| -> Result<BTreeMap<PathBuf, IndexEntry>> {
+            let mut entries = Tree::flatten(&self.objects, tree)?;
+            entries.retain(|path, _| options.matches_path(path));
+            Ok(entries)
+        };
+
+        let entries = filtered(commit.get_tree())?;
+        if commit.parents().is_empty() {
+            return Ok(!entries.is_empty());
+        }
+        for parent in commit.parents() {
+            if filtered(self.read_commit(parent)?.get_tree())? == entries {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Turns a revision expression such as `HEAD~2`, `main^2`, `feature@{1}`
+    /// or an abbreviated commit id into a commit id.
+    pub fn resolve_revision(&self, revision: &str) -> Result<String> {
+        self.resolve(&Revision::parse(revision)?)
+    }
+
+    /// Resolves `A`, `A..B` or `A...B` into the commits to walk from and the
+    /// commits whose history is left out.
+    pub fn resolve_range(&self, range: &str) -> Result<(Vec<String>, Vec<String>)> {
+        match RevisionRange::parse(range)? {
+            RevisionRange::Single(revision) => Ok((vec![self.resolve(&revision)?], Vec::new())),
+            RevisionRange::Between(from, to) => {
+                Ok((vec![self.resolve(&to)?], vec![self.resolve(&from)?]))
+            }
+            RevisionRange::Symmetric(a, b) => {
+                let (a, b) = (self.resolve(&a)?, self.resolve(&b)?);
+                let bases = merge_bases(&self.objects, &a, &b)?;
+                Ok((vec![a, b], bases))
+            }
+        }
+    }
+
+    fn resolve(&self, revision: &Revision) -> Result<String> {
+        let mut id = match &revision.base {
+            RevisionBase::Head => match &self.detached_head {
+                Some(id) => id.clone(),
+                None => self.branch_head(&self.current_branch)?,
+            },
+            RevisionBase::Name(name) => self.resolve_name(name)?,
+            RevisionBase::Reflog(name, entry) => {
+                let name = name.as_deref().unwrap_or(&self.current_branch);
+                let branch = self.branches.get(name).ok_or_else(|| {
+                    Error::new(ErrorKind::NotFound, format!("Unknown branch: {}", name))
+                })?;
+
+                // The branch records every commit it pointed to, newest last
+                let commits = branch.commits();
+                commits
+                    .len()
+                    .checked_sub(entry + 1)
+                    .map(|position| commits[position].clone())
+                    .ok_or_else(|| {
+                        Error::new(
+                            ErrorKind::NotFound,
+                            format!("Log for {} only has {} entries", name, commits.len()),
+                        )
+                    })?
+            }
+        };
+
+        for step in &revision.steps {
+            let (parent, count) = match *step {
+                AncestryStep::Parent(0) => continue,
+                AncestryStep::Parent(n) => (n - 1, 1),
+                AncestryStep::Ancestor(n) => (0, n),
+            };
+            for _ in 0..count {
+                let commit = self.read_commit(&id)?;
+                id = commit.parents().get(parent).cloned().ok_or_else(|| {
+                    Error::new(
+                        ErrorKind::NotFound,
+                        format!("Commit {} has no parent {}", &id[..7], parent + 1),
+                    )
+                })?;
+            }
+        }
+        Ok(id)
+    }
+
+    /// The object a tag points at, from `refs/tags` or `packed-refs`, with
+    /// annotated tags peeled down to what they tag.
+    pub fn tag_target(&self, name: &str) -> Result<Option<String>> {
+        let Some(mut id) = read_ref(&self.git_dir, &format!("refs/tags/{}", name))? else {
+            return Ok(None);
+        };
+
+        // An annotated tag names its target on an `object <id>` line
+        while let Ok(data) = self.objects.read_kind(&id, ObjectKind::Tag) {
+            let text = String::from_utf8_lossy(&data);
+            id = text
+                .lines()
+                .find_map(|line| line.strip_prefix("object "))
+                .map(str::to_string)
+                .ok_or_else(|| {
+                    Error::new(ErrorKind::InvalidData, format!("Malformed tag {}", name))
+                })?;
+        }
+        Ok(Some(id))
+    }
+
+    /// Looks a name up as a branch, then a tag, then a remote-tracking
+    /// branch such as `origin/main` or a remote's `HEAD`, then an abbreviated
+    /// commit id. Symbolic refs such as `origin/HEAD` are followed to the
+    /// commit they end at.
+    fn resolve_name(&self, name: &str) -> Result<String> {
+        if self.branches.contains_key(name) {
+            return self.branch_head(name);
+        }
+
+        if let Some(id) = self.tag_target(name)? {
+            return Ok(id);
+        }
+
+        // A remote's name alone stands for its `HEAD`, as in Git
+        for tracking in [
+            format!("refs/remotes/{}", name),
+            format!("refs/remotes/{}/HEAD", name),
+        ] {
+            if let Some(id) = read_ref(&self.git_dir, &tracking)? {
+                return Ok(id);
+            }
+        }
+
+        // Abbreviated ids need at least four characters, as in Git
+        if name.len() >= 4 {
+            let mut matches = self.objects.find_by_prefix(name)?;
+            matches.retain(|id| {
+                self.objects
+                    .read(id)
+                    .is_ok_and(|(kind, _)| kind == ObjectKind::Commit)
+            });
+            match matches.len() {
+                0 => {}
+                1 => return Ok(matches.remove(0)),
+                _ => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        format!(
+                            "Short commit id {} is ambiguous:\n\t{}",
+                            name,
+                            matches.join("\n\t")
+                        ),
+                    ))
+                }
+            }
+        }
+
+        Err(Error::new(
+            ErrorKind::NotFound,
+            format!("Unknown revision: {}", name),
+        ))
+    }
+
+    fn branch_head(&self, name: &str) -> Result<String> {
+        self.branches
+            .get(name)
+            .and_then(|branch| branch.head_commit_id())
+            .map(str::to_string)
+            .ok_or_else(|| {
+                Error::new(
+                    ErrorKind::NotFound,
+                    format!("Branch {} has no commits yet", name),
+                )
+            })
+    }
+
     pub fn create_branch(&mut self, name: String) -> Result<()> {
         if self.branches.contains_key(&name) {
             return Err(Error::new(
@@ -166,152 +1496,687 @@ impl Repository {
 
         let mut new_branch = Branch::new(name.clone());
 
-        // If current branch has a head commit, use it as the starting point
-        if let Some(branch) = self.branches.get(&self.current_branch) {
-            if let Some(head_commit_id) = branch.head_commit_id() {
-                new_branch.add_commit(head_commit_id.to_string());
-            }
+        // If HEAD has a commit, use it as the starting point
+        if let Some(head_commit_id) = self.head_commit() {
+            new_branch.add_commit(head_commit_id);
         }
 
         self.branches.insert(name.clone(), new_branch);
-        Ok(())
+        self.save()
     }
 
-    pub fn switch_branch(&mut self, name: &str) -> Result<()> {
+    /// Switches to `name` and updates the working tree to its head commit.
+    ///
+    /// Files that differ between the two branches are rewritten or deleted.
+    /// Unless `force` is set, the switch is refused when one of those files has
+    /// staged or unstaged changes that would be lost.
+    pub fn switch_branch(&mut self, name: &str, force: bool) -> Result<()> {
         if !self.branches.contains_key(name) {
             return Err(Error::new(ErrorKind::NotFound, "Branch not found"));
         }
 
+        let current_tree = self.head_tree()?;
+        let target_tree = self.branch_tree(name)?;
+        self.update_worktree(&current_tree, &target_tree, force, "checkout")?;
+
         self.current_branch = name.to_string();
-        Ok(())
+        self.detached_head = None;
+        self.save()
     }
 
-    pub fn diff(&self, branch1: &str, branch2: &str) -> Result<Vec<DiffResult>> {
-        let branch1_commits = self
-            .branches
-            .get(branch1)
-            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Branch not found"))?;
-        let branch2_commits = self
-            .branches
-            .get(branch2)
-            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Branch not found"))?;
-
-        // Get the latest commits for both branches
-        let branch1_commit_id = branch1_commits
-            .head_commit_id()
-            .ok_or_else(|| Error::new(ErrorKind::NotFound, "No commits in branch"))?;
-        let branch2_commit_id = branch2_commits
-            .head_commit_id()
-            .ok_or_else(|| Error::new(ErrorKind::NotFound, "No commits in branch"))?;
-
-        let branch1_commit = self
-            .commits
-            .get(branch1_commit_id)
-            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Commit not found"))?;
-        let branch2_commit = self
-            .commits
-            .get(branch2_commit_id)
-            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Commit not found"))?;
-
-        // Perform diff
-        Ok(Differ::diff(
-            &branch1_commit.get_tree(),
-            &branch2_commit.get_tree(),
-        ))
+    /// Detaches HEAD at `revision`, a commit rather than a branch, and
+    /// updates the working tree to it. Commits made from there move HEAD
+    /// alone until a branch is checked out again.
+    pub fn detach_head(&mut self, revision: &str, force: bool) -> Result<String> {
+        let target = self.resolve_revision(revision)?;
+        let current_tree = self.head_tree()?;
+        let target_tree = Tree::flatten(&self.objects, self.read_commit(&target)?.get_tree())?;
+        self.update_worktree(&current_tree, &target_tree, force, "checkout")?;
+
+        self.current_branch = "HEAD".to_string();
+        self.detached_head = Some(target.clone());
+        self.save()?;
+        Ok(target)
     }
 
-    pub fn merge(
+    /// Moves the working tree and index from `current_tree` to `target_tree`.
+    ///
+    /// Files that differ between the two trees are rewritten or deleted.
+    /// Unless `force` is set, nothing is touched when one of those files has
+    /// staged or unstaged changes that would be lost.
+    fn update_worktree(
         &mut self,
-        base_branch: &str,
-        source_branch: &str,
-        target_branch: &str,
+        current_tree: &BTreeMap<PathBuf, IndexEntry>,
+        target_tree: &BTreeMap<PathBuf, IndexEntry>,
+        force: bool,
+        operation: &str,
     ) -> Result<()> {
-        // Get branches
-        let base_branch_obj = self
-            .branches
-            .get(base_branch)
-            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Base branch not found"))?;
-        let source_branch_obj = self
-            .branches
-            .get(source_branch)
-            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Source branch not found"))?;
-        let target_branch_obj = self
-            .branches
-            .get(target_branch)
-            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Target branch not found"))?;
-
-        // Get latest commits
-        let base_commit_id = base_branch_obj
-            .head_commit_id()
-            .ok_or_else(|| Error::new(ErrorKind::NotFound, "No commits in base branch"))?;
-        let source_commit_id = source_branch_obj
-            .head_commit_id()
-            .ok_or_else(|| Error::new(ErrorKind::NotFound, "No commits in source branch"))?;
-        let target_commit_id = target_branch_obj
-            .head_commit_id()
-            .ok_or_else(|| Error::new(ErrorKind::NotFound, "No commits in target branch"))?;
-
-        // Get commit objects
-        let base_commit = self
-            .commits
-            .get(base_commit_id)
-            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Base commit not found"))?;
-        let source_commit = self
-            .commits
-            .get(source_commit_id)
-            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Source commit not found"))?;
-        let target_commit = self
-            .commits
-            .get(target_commit_id)
-            .ok_or_else(|| Error::new(ErrorKind::NotFound, "Target commit not found"))?;
+        let worktree = WorkTree::new(&self.root_path);
 
-        // Perform three-way merge using the Merger module
-        match Merger::merge(
-            &base_commit.get_tree(),
-            &source_commit.get_tree(),
-            &target_commit.get_tree(),
-        ) {
-            Ok(merged_tree) => {
-                // Create a new commit for the merge
-                let parent_ids = vec![base_commit_id, source_commit_id, target_commit_id];
-                let parent_str = parent_ids.join(",");
-
-                let commit = Commit::new(
-                    Some(parent_str),
-                    merged_tree,
+        let mut paths: BTreeSet<&PathBuf> = current_tree.keys().collect();
+        paths.extend(target_tree.keys());
+
+        // Only paths that change between the trees are touched
+        let changed: Vec<&PathBuf> = paths
+            .into_iter()
+            .filter(|path| force || current_tree.get(*path) != target_tree.get(*path))
+            .collect();
+
+        if !force {
+            let mut clobbered = Vec::new();
+            for path in &changed {
+                let committed = current_tree.get(*path);
+                let staged = self
+                    .index
+                    .get_entries()
+                    .get(*path)
+                    .is_some_and(|entry| Some(entry) != committed);
+                let on_disk = worktree.entry(path, &self.objects)?;
+
+                // An untracked file is only at risk if the target would replace it
+                let modified = match committed {
+                    Some(_) => on_disk.as_ref() != committed,
+                    None => on_disk.is_some() && on_disk.as_ref() != target_tree.get(*path),
+                };
+
+                if staged || modified {
+                    clobbered.push(path.display().to_string());
+                }
+            }
+
+            if !clobbered.is_empty() {
+                return Err(Error::other(format!(
+                    "Your local changes to the following files would be overwritten by {}:\n\t{}\nCommit your changes or use --force to discard them",
+                    operation,
+                    clobbered.join("\n\t")
+                )));
+            }
+        }
+
+        for path in &changed {
+            match target_tree.get(*path) {
+                Some(entry) => worktree.write(path, entry, &self.objects)?,
+                None => worktree.remove(path)?,
+            }
+        }
+
+        // The index now describes the target tree, keeping staged changes to
+        // files that were not touched, removals included
+        let mut index = Index::new();
+        for (path, entry) in target_tree {
+            index.add(path.clone(), entry.id.clone(), entry.mode);
+        }
+        if !force {
+            for (path, entry) in self.index.get_entries() {
+                if current_tree.get(path) == target_tree.get(path) {
+                    index.add(path.clone(), entry.id.clone(), entry.mode);
+                }
+            }
+            for (path, entry) in current_tree {
+                if target_tree.get(path) == Some(entry)
+                    && !self.index.get_entries().contains_key(path)
+                {
+                    index.remove(path);
+                }
+            }
+        }
+        self.index = index;
+        self.save_index()
+    }
+
+    /// Copies `paths` from `revision` into the index and the working tree,
+    /// as `checkout <revision> -- <paths>` does. Directories are copied with
+    /// everything below them.
+    pub fn restore(&mut self, revision: &str, paths: &[PathBuf]) -> Result<()> {
+        let commit = self.read_commit(&self.resolve_revision(revision)?)?;
+        let tree = Tree::flatten(&self.objects, commit.get_tree())?;
+        let worktree = WorkTree::new(&self.root_path);
+
+        for path in paths {
+            let filter = self.repo_path(path)?;
+            let matched: Vec<(&PathBuf, &IndexEntry)> = tree
+                .iter()
+                .filter(|(tracked, _)| tracked.starts_with(&filter))
+                .collect();
+            if matched.is_empty() {
+                return Err(Error::new(
+                    ErrorKind::NotFound,
                     format!(
-                        "Merge {} and {} into {}",
-                        source_branch, target_branch, base_branch
+                        "Path {} did not match any file in {}",
+                        path.display(),
+                        revision
                     ),
-                    "merge-tool".to_string(),
-                );
-                let commit_id = commit.get_id().to_string();
-
-                // Save the commit and update the base branch
-                self.commits.insert(commit_id.clone(), commit);
-                if let Some(branch) = self.branches.get_mut(base_branch) {
-                    branch.add_commit(commit_id);
+                ));
+            }
+
+            for (tracked, entry) in matched {
+                worktree.write(tracked, entry, &self.objects)?;
+                self.index
+                    .add(tracked.clone(), entry.id.clone(), entry.mode);
+            }
+        }
+
+        self.save_index()
+    }
+
+    /// Points the current branch at `revision` and returns its id. `Mixed`
+    /// also resets the index to that commit, and `Hard` the working tree as
+    /// well, discarding every local change to tracked files.
+    pub fn reset(&mut self, revision: &str, mode: ResetMode) -> Result<String> {
+        let target = self.resolve_revision(revision)?;
+        let target_tree = Tree::flatten(&self.objects, self.read_commit(&target)?.get_tree())?;
+        let merge_head_path = self.git_dir.join("MERGE_HEAD");
+
+        match mode {
+            ResetMode::Soft => {
+                if merge_head_path.is_file() || !self.index.conflicts().is_empty() {
+                    return Err(Error::other(
+                        "Cannot do a soft reset in the middle of a merge",
+                    ));
+                }
+            }
+            ResetMode::Mixed => {
+                let mut index = Index::new();
+                for (path, entry) in &target_tree {
+                    index.add(path.clone(), entry.id.clone(), entry.mode);
                 }
+                self.index = index;
+                self.save_index()?;
+            }
+            ResetMode::Hard => {
+                // Files staged since the last commit are discarded as well
+                let mut current_tree = self.head_tree()?;
+                for (path, entry) in self.index.get_entries() {
+                    current_tree
+                        .entry(path.clone())
+                        .or_insert_with(|| entry.clone());
+                }
+                self.update_worktree(&current_tree, &target_tree, true, "reset")?;
+            }
+        }
+
+        // Resetting the index abandons a merge in progress
+        if mode != ResetMode::Soft && merge_head_path.is_file() {
+            fs::remove_file(&merge_head_path)?;
+        }
+
+        self.move_head(target.clone());
+        self.save()?;
+        Ok(target)
+    }
+
+    /// Compares HEAD, the index and the working tree, returning one entry per
+    /// difference. A path can be both staged and modified in the working tree.
+    pub fn status(&self) -> Result<Vec<StatusEntry>> {
+        let head_tree = self.head_tree()?;
+        let index = self.index.get_entries();
+        let worktree = WorkTree::new(&self.root_path);
+        let mut entries = Vec::new();
+
+        // Staged changes: HEAD against the index
+        let mut tracked: BTreeSet<&PathBuf> = head_tree.keys().collect();
+        tracked.extend(index.keys());
+        let conflicts = self.index.conflicts();
+        for path in conflicts {
+            entries.push(StatusEntry {
+                path: path.clone(),
+                status: FileStatus::Unmerged,
+            });
+        }
+        for path in tracked
+            .into_iter()
+            .filter(|path| !conflicts.contains(*path))
+        {
+            let status = match (head_tree.get(path), index.get(path)) {
+                (None, Some(_)) => FileStatus::StagedNew,
+                (Some(_), None) => FileStatus::StagedDeleted,
+                (Some(committed), Some(staged)) if committed != staged => {
+                    FileStatus::StagedModified
+                }
+                _ => continue,
+            };
+            entries.push(StatusEntry {
+                path: path.clone(),
+                status,
+            });
+        }
+
+        // Unstaged changes: the index against the working tree
+        for (path, staged) in index.iter().filter(|(path, _)| !conflicts.contains(*path)) {
+            let status = match worktree.entry(path, &self.objects)? {
+                None => FileStatus::Deleted,
+                Some(on_disk) if &on_disk != staged => FileStatus::Modified,
+                _ => continue,
+            };
+            entries.push(StatusEntry {
+                path: path.clone(),
+                status,
+            });
+        }
+
+        // Files the index does not know about
+        let ignore = self.ignore()?;
+        self.collect_untracked(Path::new(""), &ignore, &mut entries)?;
+
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(entries)
+    }
+
+    fn collect_untracked(
+        &self,
+        dir: &Path,
+        ignore: &Ignore,
+        entries: &mut Vec<StatusEntry>,
+    ) -> Result<()> {
+        for entry in fs::read_dir(self.root_path.join(dir))? {
+            let entry = entry?;
+            let path = dir.join(entry.file_name());
+            // Skip the git directory, which may also sit in the work tree
+            // under another name
+            if path == Path::new(".git-scm")
+                || path == Path::new(".git")
+                || self.root_path.join(&path) == self.git_dir
+            {
+                continue;
+            }
+
+            let is_dir = entry.file_type()?.is_dir();
+            let tracked = self.index.get_entries().contains_key(&path);
+            if !tracked && ignore.is_ignored(&path, is_dir)? {
+                // An ignored directory is reported once rather than file by file
+                entries.push(StatusEntry {
+                    path,
+                    status: FileStatus::Ignored,
+                });
+            } else if is_dir {
+                self.collect_untracked(&path, ignore, entries)?;
+            } else if !tracked && !self.index.conflicts().contains(&path) {
+                entries.push(StatusEntry {
+                    path,
+                    status: FileStatus::Untracked,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Compares the trees of two revisions.
+    pub fn diff(&self, from: &str, to: &str) -> Result<Vec<DiffResult>> {
+        let old = self.read_commit(&self.resolve_revision(from)?)?;
+        let new = self.read_commit(&self.resolve_revision(to)?)?;
+        self.diff_trees(Some(old.get_tree()), new.get_tree())
+    }
+
+    /// Compares the ends of `A..B`, or the merge base of A and B with B for
+    /// `A...B`. A single revision is compared with HEAD.
+    pub fn diff_range(&self, range: &str) -> Result<Vec<DiffResult>> {
+        let (from, to) = match RevisionRange::parse(range)? {
+            RevisionRange::Single(revision) => {
+                (self.resolve(&revision)?, self.resolve(&Revision::head())?)
+            }
+            RevisionRange::Between(from, to) => (self.resolve(&from)?, self.resolve(&to)?),
+            RevisionRange::Symmetric(a, b) => {
+                let (a, b) = (self.resolve(&a)?, self.resolve(&b)?);
+                let base = merge_bases(&self.objects, &a, &b)?
+                    .into_iter()
+                    .next()
+                    .ok_or_else(|| Error::other(format!("No merge base for {}", range)))?;
+                (base, b)
+            }
+        };
+        self.diff(&from, &to)
+    }
+
+    /// Compares two trees, `None` standing for the empty tree before the
+    /// first commit.
+    fn diff_trees(&self, old_tree: Option<&str>, new_tree: &str) -> Result<Vec<DiffResult>> {
+        let old_entries = match old_tree {
+            Some(id) => Tree::flatten(&self.objects, id)?,
+            None => BTreeMap::new(),
+        };
+        let new_entries = Tree::flatten(&self.objects, new_tree)?;
+        let mut paths: BTreeSet<&PathBuf> = old_entries.keys().collect();
+        paths.extend(new_entries.keys());
+
+        let read = |entry: Option<&IndexEntry>| {
+            entry
+                .map(|entry| self.objects.read_kind(&entry.id, ObjectKind::Blob))
+                .transpose()
+        };
+        let mut diffs = Vec::new();
+        for path in paths {
+            // Entries hold both the blob and the mode, so a file whose mode
+            // alone changed is reported too
+            let (old, new) = (old_entries.get(path), new_entries.get(path));
+            let diff_type = match (old, new) {
+                (Some(old), Some(new)) if old == new => continue,
+                (None, _) => DiffType::Added,
+                (_, None) => DiffType::Deleted,
+                _ => DiffType::Modified,
+            };
+            diffs.push(DiffResult {
+                file_path: path.clone(),
+                diff_type,
+                old_content: read(old)?,
+                new_content: read(new)?,
+                old_mode: old.map(|entry| entry.mode),
+                new_mode: new.map(|entry| entry.mode),
+            });
+        }
+        Ok(diffs)
+    }
+
+    /// A commit and the changes it made to its first parent, as `show`
+    /// prints them.
+    pub fn show(&self, revision: &str) -> Result<(Commit, Vec<DiffResult>)> {
+        let commit = self.read_commit(&self.resolve_revision(revision)?)?;
+        let parent_tree = match commit.parents().first() {
+            Some(parent) => Some(self.read_commit(parent)?.get_tree().to_string()),
+            None => None,
+        };
+        let diffs = self.diff_trees(parent_tree.as_deref(), commit.get_tree())?;
+        Ok((commit, diffs))
+    }
+
+    /// Merges `branch` into the current branch.
+    ///
+    /// The common ancestor is looked up in the commit history. When there are
+    /// several, as after criss-cross merges, they are first merged into a
+    /// virtual ancestor the way Git's recursive strategy does.
+    ///
+    /// When the current branch is an ancestor of `branch` it is simply moved
+    /// forward, unless `mode` asks for a merge commit anyway.
+    pub fn merge(&mut self, branch: &str, mode: FastForwardMode) -> Result<MergeStatus> {
+        if branch == self.current_branch {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Cannot merge a branch into itself",
+            ));
+        }
+
+        let git_dir = self.git_dir.clone();
+        if !self.index.conflicts().is_empty() || git_dir.join("MERGE_HEAD").is_file() {
+            return Err(Error::other(
+                "You have not concluded your merge; commit the resolved files first",
+            ));
+        }
+
+        // Remote-tracking branches such as `origin/main` merge what was fetched
+        let remote_tracking = !self.branches.contains_key(branch);
+        let kind = if remote_tracking {
+            "remote-tracking branch"
+        } else {
+            "branch"
+        };
+        let their_id = if remote_tracking {
+            read_ref(&git_dir, &format!("refs/remotes/{}", branch))?
+                .ok_or_else(|| Error::new(ErrorKind::NotFound, "Branch not found"))?
+        } else {
+            self.branch_head(branch)?
+        };
+        let our_id = self
+            .head_commit()
+            .ok_or_else(|| Error::new(ErrorKind::NotFound, "No commits in current branch"))?;
+
+        let bases = merge_bases(&self.objects, &our_id, &their_id)?;
+        if bases.is_empty() {
+            return Err(Error::other("Refusing to merge unrelated histories"));
+        }
+        if bases.contains(&their_id) {
+            return Ok(MergeStatus::UpToDate);
+        }
+
+        let our_commit = self.read_commit(&our_id)?;
+        let their_commit = self.read_commit(&their_id)?;
+        let our_tree = Tree::flatten(&self.objects, our_commit.get_tree())?;
+        let their_tree = Tree::flatten(&self.objects, their_commit.get_tree())?;
+
+        let fast_forward = bases.contains(&our_id);
+        if fast_forward && mode != FastForwardMode::Never {
+            self.update_worktree(&our_tree, &their_tree, false, "merge")?;
+            self.move_head(their_id.clone());
+            self.save()?;
+            return Ok(MergeStatus::FastForward(their_id));
+        }
+        if !fast_forward && mode == FastForwardMode::Only {
+            return Err(Error::other("Not possible to fast-forward, aborting"));
+        }
+        let base_tree = Tree::flatten(&self.objects, self.read_commit(&bases[0])?.get_tree())?;
+
+        // Perform three-way merge using the Merger module
+        let base_label = if bases.len() == 1 {
+            bases[0][..7].to_string()
+        } else {
+            "merged common ancestors".to_string()
+        };
+        let outcome = Merger::merge(
+            &self.merge_base_contents(&bases)?,
+            &self.tree_contents(our_commit.get_tree())?,
+            &self.tree_contents(their_commit.get_tree())?,
+            MergeLabels {
+                base: &base_label,
+                branch1: "HEAD",
+                branch2: branch,
+            },
+        );
 
-                Ok(())
+        // Store the merged files, conflict markers included. File modes are
+        // not part of the content merge, so a mode change on either side wins.
+        let mut entries = BTreeMap::new();
+        for (path, content) in outcome.tree {
+            let blob_id = self.objects.write(ObjectKind::Blob, &content)?;
+            let base_mode = base_tree.get(&path).map(|entry| entry.mode);
+            let our_mode = our_tree.get(&path).map(|entry| entry.mode);
+            let their_mode = their_tree.get(&path).map(|entry| entry.mode);
+            let mode = match (our_mode, their_mode) {
+                (Some(ours), Some(theirs)) if ours == base_mode.unwrap_or(ours) => theirs,
+                (Some(ours), _) => ours,
+                (None, theirs) => theirs.unwrap_or(FileMode::Regular),
+            };
+            entries.insert(path, IndexEntry { id: blob_id, mode });
+        }
+
+        if !outcome.conflicts.is_empty() {
+            // Leave the markers in the working tree and keep our version
+            // staged until each conflicted file is added again
+            self.update_worktree(&our_tree, &entries, false, "merge")?;
+            for conflict in &outcome.conflicts {
+                let path = conflict.file_path();
+                match our_tree.get(path) {
+                    Some(entry) => self.index.add(path.clone(), entry.id.clone(), entry.mode),
+                    None => self.index.remove(path),
+                }
+                self.index.mark_conflicted(path.clone());
             }
-            Err(conflicts) => {
-                // Handle merge conflicts
-                for conflict in conflicts {
-                    println!("Merge conflict: {:?}", conflict);
+            self.save_index()?;
+
+            // The next commit picks up the merged head from MERGE_HEAD
+            fs::write(git_dir.join("MERGE_HEAD"), format!("{}\n", their_id))?;
+
+            let report: Vec<String> = outcome.conflicts.iter().map(|c| c.describe()).collect();
+            return Err(Error::other(format!(
+                "{}\nAutomatic merge failed; fix conflicts and then commit the result.",
+                report.join("\n")
+            )));
+        }
+
+        // Everything that can fail is done before the working tree and index
+        // are touched, so a failed merge leaves them as they were
+        let author = self.config.identity(IdentityRole::Author)?;
+        let committer = self.config.identity(IdentityRole::Committer)?;
+        let index_entries: HashMap<PathBuf, IndexEntry> = entries
+            .iter()
+            .map(|(path, entry)| (path.clone(), entry.clone()))
+            .collect();
+        let merged_tree = Tree::write_from_index(&self.objects, &index_entries)?;
+
+        // The merge commit records exactly the two heads it joins
+        let commit = Commit::new(
+            vec![our_id, their_id.clone()],
+            merged_tree,
+            format!("Merge {} '{}' into {}", kind, branch, self.current_branch),
+            author,
+            committer,
+            self.objects.algorithm(),
+        );
+        let commit_id = commit.get_id().to_string();
+        self.objects
+            .write(ObjectKind::Commit, &commit.serialize())?;
+
+        // Update the working tree, then the current branch
+        self.update_worktree(&our_tree, &entries, false, "merge")?;
+        self.move_head(commit_id.clone());
+
+        self.save()?;
+        Ok(MergeStatus::Merged(commit_id))
+    }
+
+    /// Contents of the common ancestor of a merge. Several best ancestors are
+    /// merged with each other, conflicts and all, into a single virtual one.
+    fn merge_base_contents(&self, bases: &[String]) -> Result<HashMap<PathBuf, Vec<u8>>> {
+        let mut contents = self.tree_contents(self.read_commit(&bases[0])?.get_tree())?;
+        for other in &bases[1..] {
+            let ancestors = merge_bases(&self.objects, &bases[0], other)?;
+            let ancestor = if ancestors.is_empty() {
+                HashMap::new()
+            } else {
+                self.merge_base_contents(&ancestors)?
+            };
+
+            contents = Merger::merge(
+                &ancestor,
+                &contents,
+                &self.tree_contents(self.read_commit(other)?.get_tree())?,
+                MergeLabels {
+                    base: "merged common ancestors",
+                    branch1: "Temporary merge branch 1",
+                    branch2: "Temporary merge branch 2",
+                },
+            )
+            .tree;
+        }
+        Ok(contents)
+    }
+
+    fn read_commit(&self, id: &str) -> Result<Commit> {
+        Commit::read(&self.objects, id)
+    }
+
+    /// The commit HEAD points at, through the current branch unless it is
+    /// detached.
+    fn head_commit(&self) -> Option<String> {
+        match &self.detached_head {
+            Some(id) => Some(id.clone()),
+            None => self
+                .branches
+                .get(&self.current_branch)
+                .and_then(|branch| branch.head_commit_id())
+                .map(str::to_string),
+        }
+    }
+
+    /// Points HEAD at `id`, moving the current branch unless it is detached.
+    fn move_head(&mut self, id: String) {
+        match &mut self.detached_head {
+            Some(head) => *head = id,
+            None => {
+                if let Some(branch) = self.branches.get_mut(&self.current_branch) {
+                    branch.add_commit(id);
                 }
-                Err(Error::new(ErrorKind::Other, "Merge conflicts detected"))
             }
         }
     }
 
+    fn head_tree(&self) -> Result<BTreeMap<PathBuf, IndexEntry>> {
+        match self.head_commit() {
+            Some(id) => Tree::flatten(&self.objects, self.read_commit(&id)?.get_tree()),
+            None => Ok(BTreeMap::new()),
+        }
+    }
+
+    /// Flattens the tree of a branch's head commit, which is empty for a
+    /// branch without commits.
+    fn branch_tree(&self, name: &str) -> Result<BTreeMap<PathBuf, IndexEntry>> {
+        let head = self
+            .branches
+            .get(name)
+            .and_then(|branch| branch.head_commit_id());
+
+        match head {
+            Some(id) => Tree::flatten(&self.objects, self.read_commit(id)?.get_tree()),
+            None => Ok(BTreeMap::new()),
+        }
+    }
+
+    /// Expands a tree object into the file contents it references.
+    fn tree_contents(&self, id: &str) -> Result<HashMap<PathBuf, Vec<u8>>> {
+        let mut contents = HashMap::new();
+        for (path, entry) in Tree::flatten(&self.objects, id)? {
+            let data = self.objects.read_kind(&entry.id, ObjectKind::Blob)?;
+            contents.insert(path, data);
+        }
+        Ok(contents)
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Packs loose objects, and with `all` every existing pack too, into a
+    /// new pack.
+    pub fn repack(&self, all: bool) -> Result<RepackSummary> {
+        self.objects.repack(all)
+    }
+
+    pub fn objects(&self) -> &ObjectStore {
+        &self.objects
+    }
+
+    pub fn object_format(&self) -> HashAlgorithm {
+        self.objects.algorithm()
+    }
+
+    pub fn storage(&self) -> StorageFormat {
+        self.storage
+    }
+
+    pub fn git_dir(&self) -> &Path {
+        &self.git_dir
+    }
+
+    /// The repository's own configuration file.
+    pub fn config_path(&self) -> PathBuf {
+        self.git_dir.join("config")
+    }
+
     // method to support retrieving the current branch name
     pub fn current_branch(&self) -> &str {
         &self.current_branch
     }
 
+    /// The commit HEAD points at when no branch is checked out.
+    pub fn detached_head(&self) -> Option<&str> {
+        self.detached_head.as_deref()
+    }
+
     // method to support logging branches
     pub fn branches(&self) -> &HashMap<String, Branch> {
         &self.branches
     }
 }
+
+/// Recreates the files below `source` under `target`, hardlinking each one
+/// and falling back to copying, for instance across filesystems.
+fn link_or_copy_dir(source: &Path, target: &Path) -> Result<()> {
+    fs::create_dir_all(target)?;
+    for entry in fs::read_dir(source)? {
+        let entry = entry?;
+        let target_path = target.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            link_or_copy_dir(&entry.path(), &target_path)?;
+        } else if fs::hard_link(entry.path(), &target_path).is_err() {
+            fs::copy(entry.path(), &target_path)?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,192 @@
+#![allow(unused)]
+
+use std::io::{Error, ErrorKind, Result};
+
+/// Where a revision expression starts before any ancestry steps.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RevisionBase {
+    /// `HEAD`, or a bare `@`
+    Head,
+    /// A branch, tag or abbreviated object id, looked up in that order
+    Name(String),
+    /// `<name>@{n}`: the n-th previous position of a branch, `@{n}` alone
+    /// meaning the current branch
+    Reflog(Option<String>, usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AncestryStep {
+    /// `^n`: the n-th parent, `^0` being the commit itself
+    Parent(usize),
+    /// `~n`: the n-th first-parent ancestor
+    Ancestor(usize),
+}
+
+/// A single commit such as `main~2^2` or `HEAD@{1}`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Revision {
+    pub base: RevisionBase,
+    pub steps: Vec<AncestryStep>,
+}
+
+/// What a command was asked to look at: one commit or a set of commits.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RevisionRange {
+    Single(Revision),
+    /// `A..B`: commits reachable from B but not from A
+    Between(Revision, Revision),
+    /// `A...B`: commits reachable from either side but not from both
+    Symmetric(Revision, Revision),
+}
+
+impl Revision {
+    pub fn parse(text: &str) -> Result<Self> {
+        let invalid = || {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid revision: {}", text),
+            )
+        };
+
+        // The base ends where the first ancestry step starts
+        let split = text.find(['~', '^']).unwrap_or(text.len());
+        let (base, mut rest) = text.split_at(split);
+
+        let base = if base == "HEAD" || base == "@" {
+            RevisionBase::Head
+        } else if let Some((name, entry)) = base.split_once("@{") {
+            let entry = entry
+                .strip_suffix('}')
+                .and_then(|n| n.parse().ok())
+                .ok_or_else(invalid)?;
+            let name = match name {
+                "" | "HEAD" => None,
+                name => Some(name.to_string()),
+            };
+            RevisionBase::Reflog(name, entry)
+        } else if base.is_empty() || base.contains("..") {
+            return Err(invalid());
+        } else {
+            RevisionBase::Name(base.to_string())
+        };
+
+        let mut steps = Vec::new();
+        while let Some(operator) = rest.chars().next() {
+            let step = match operator {
+                '^' => AncestryStep::Parent,
+                '~' => AncestryStep::Ancestor,
+                _ => return Err(invalid()),
+            };
+            let after = &rest[operator.len_utf8()..];
+            let digits = after
+                .find(|c: char| !c.is_ascii_digit())
+                .unwrap_or(after.len());
+            let count = match &after[..digits] {
+                "" => 1,
+                n => n.parse().map_err(|_| invalid())?,
+            };
+            steps.push(step(count));
+            rest = &after[digits..];
+        }
+
+        Ok(Revision { base, steps })
+    }
+
+    pub fn head() -> Self {
+        Revision {
+            base: RevisionBase::Head,
+            steps: Vec::new(),
+        }
+    }
+}
+
+impl RevisionRange {
+    /// Parses `A`, `A..B` or `A...B`. A missing side of a range means `HEAD`.
+    pub fn parse(text: &str) -> Result<Self> {
+        let side = |side: &str| {
+            if side.is_empty() {
+                Ok(Revision::head())
+            } else {
+                Revision::parse(side)
+            }
+        };
+
+        if let Some((from, to)) = text.split_once("...") {
+            Ok(RevisionRange::Symmetric(side(from)?, side(to)?))
+        } else if let Some((from, to)) = text.split_once("..") {
+            Ok(RevisionRange::Between(side(from)?, side(to)?))
+        } else {
+            Ok(RevisionRange::Single(Revision::parse(text)?))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(name: &str) -> RevisionBase {
+        RevisionBase::Name(name.to_string())
+    }
+
+    #[test]
+    fn parses_ancestry_steps() {
+        let revision = Revision::parse("main~2^2^").unwrap();
+        assert_eq!(revision.base, name("main"));
+        assert_eq!(
+            revision.steps,
+            [
+                AncestryStep::Ancestor(2),
+                AncestryStep::Parent(2),
+                AncestryStep::Parent(1)
+            ]
+        );
+        assert_eq!(Revision::parse("@~").unwrap().base, RevisionBase::Head);
+        assert_eq!(
+            Revision::parse("HEAD^0").unwrap().steps,
+            [AncestryStep::Parent(0)]
+        );
+    }
+
+    #[test]
+    fn parses_reflog_entries() {
+        assert_eq!(
+            Revision::parse("feature@{3}").unwrap().base,
+            RevisionBase::Reflog(Some("feature".to_string()), 3)
+        );
+        assert_eq!(
+            Revision::parse("@{1}~1").unwrap(),
+            Revision {
+                base: RevisionBase::Reflog(None, 1),
+                steps: vec![AncestryStep::Ancestor(1)],
+            }
+        );
+        assert!(Revision::parse("main@{x}").is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_revisions() {
+        for text in ["", "~1", "main^x", "main^2x", "HEAD^é", "HEAD~1é", "a..b"] {
+            assert!(Revision::parse(text).is_err(), "{} should not parse", text);
+        }
+    }
+
+    #[test]
+    fn parses_ranges() {
+        let Ok(RevisionRange::Symmetric(a, b)) = RevisionRange::parse("main...topic") else {
+            panic!("not a symmetric range");
+        };
+        assert_eq!((a.base, b.base), (name("main"), name("topic")));
+
+        let Ok(RevisionRange::Between(from, to)) = RevisionRange::parse("..topic~1") else {
+            panic!("not a range");
+        };
+        assert_eq!(from, Revision::head());
+        assert_eq!(to.steps, [AncestryStep::Ancestor(1)]);
+
+        assert!(matches!(
+            RevisionRange::parse("v1.0"),
+            Ok(RevisionRange::Single(_))
+        ));
+    }
+}
@@ -0,0 +1,306 @@
+#![allow(unused)]
+
+use std::collections::HashSet;
+use std::io::{Error, ErrorKind, Result};
+
+use crate::git::http::AGENT;
+use crate::git::object_store::ObjectKind;
+use crate::git::pkt_line::{self, Packet, PacketReader, MAX_PACKET};
+use crate::git::remote::{objects_to_send, reachable_objects};
+use crate::git::repository::Repository;
+
+/// The two services of Git's smart protocol: `git-upload-pack` sends
+/// objects to those fetching, `git-receive-pack` takes them from those
+/// pushing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Service {
+    UploadPack,
+    ReceivePack,
+}
+
+impl Service {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "git-upload-pack" => Some(Service::UploadPack),
+            "git-receive-pack" => Some(Service::ReceivePack),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Service::UploadPack => "git-upload-pack",
+            Service::ReceivePack => "git-receive-pack",
+        }
+    }
+}
+
+/// What `GET info/refs?service=<service>` answers: the capabilities of
+/// protocol version 2 for fetching, the refs to update for pushing.
+pub fn advertise(repo: &Repository, service: Service) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    match service {
+        Service::UploadPack => {
+            pkt_line::write_line(&mut out, "version 2");
+            pkt_line::write_line(&mut out, &format!("agent={}", AGENT));
+            pkt_line::write_line(&mut out, "ls-refs=unborn");
+            pkt_line::write_line(&mut out, "fetch");
+            pkt_line::write_line(
+                &mut out,
+                &format!("object-format={}", repo.object_format().name()),
+            );
+            pkt_line::write_flush(&mut out);
+        }
+        Service::ReceivePack => {
+            pkt_line::write_line(&mut out, "# service=git-receive-pack");
+            pkt_line::write_flush(&mut out);
+
+            // Capabilities follow the first ref after a NUL, or a
+            // placeholder when there is none
+            let capabilities = format!(
+                "report-status ofs-delta object-format={} agent={}",
+                repo.object_format().name(),
+                AGENT
+            );
+            let mut refs: Vec<(String, String)> = repo.refs()?.into_iter().collect();
+            if refs.is_empty() {
+                refs.push((
+                    "capabilities^{}".to_string(),
+                    "0".repeat(repo.object_format().hex_len()),
+                ));
+            }
+            for (position, (name, id)) in refs.iter().enumerate() {
+                match position {
+                    0 => pkt_line::write_line(
+                        &mut out,
+                        &format!("{} {}\0{}", id, name, capabilities),
+                    ),
+                    _ => pkt_line::write_line(&mut out, &format!("{} {}", id, name)),
+                }
+            }
+            pkt_line::write_flush(&mut out);
+        }
+    }
+    Ok(out)
+}
+
+/// Answers a protocol version 2 request to `git-upload-pack`: a command
+/// with its capabilities, then its arguments.
+pub fn upload_pack(repo: &Repository, request: &[u8]) -> Result<Vec<u8>> {
+    let mut reader = PacketReader::new(request);
+    let (header, end) = reader.read_lines()?;
+    let arguments = match end {
+        Packet::Delimiter => reader.read_lines()?.0,
+        _ => Vec::new(),
+    };
+
+    let command = header
+        .iter()
+        .find_map(|line| line.strip_prefix("command="))
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Missing command"))?;
+    if let Some(format) = header
+        .iter()
+        .find_map(|line| line.strip_prefix("object-format="))
+    {
+        if format != repo.object_format().name() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Mismatched object format {}", format),
+            ));
+        }
+    }
+
+    match command {
+        "ls-refs" => ls_refs(repo, &arguments),
+        "fetch" => fetch(repo, &arguments),
+        _ => Err(Error::new(
+            ErrorKind::Unsupported,
+            format!("Unknown command {}", command),
+        )),
+    }
+}
+
+/// Lists `HEAD`, the branches and the tags, limited to the names starting
+/// with a `ref-prefix` when any are given.
+fn ls_refs(repo: &Repository, arguments: &[&str]) -> Result<Vec<u8>> {
+    let symrefs = arguments.contains(&"symrefs");
+    let peel = arguments.contains(&"peel");
+    let unborn = arguments.contains(&"unborn");
+    let prefixes: Vec<&str> = arguments
+        .iter()
+        .filter_map(|argument| argument.strip_prefix("ref-prefix "))
+        .collect();
+    let wanted = |name: &str| prefixes.is_empty() || prefixes.iter().any(|p| name.starts_with(p));
+
+    let refs = repo.refs()?;
+    let mut out = Vec::new();
+    if let Some(id) = repo.detached_head().filter(|_| wanted("HEAD")) {
+        pkt_line::write_line(&mut out, &format!("{} HEAD", id));
+    } else if wanted("HEAD") {
+        let target = format!("refs/heads/{}", repo.current_branch());
+        let attributes = match symrefs {
+            true => format!(" symref-target:{}", target),
+            false => String::new(),
+        };
+        match refs.get(&target) {
+            Some(id) => {
+                pkt_line::write_line(&mut out, &format!("{} HEAD{}", id, attributes));
+            }
+            None if unborn => {
+                pkt_line::write_line(&mut out, &format!("unborn HEAD{}", attributes));
+            }
+            None => {}
+        }
+    }
+    for (name, id) in &refs {
+        if !wanted(name) {
+            continue;
+        }
+        let mut line = format!("{} {}", id, name);
+        // Annotated tags also name the object they tag
+        if let Some(tag) = name.strip_prefix("refs/tags/").filter(|_| peel) {
+            if repo.objects().read_kind(id, ObjectKind::Tag).is_ok() {
+                if let Some(target) = repo.tag_target(tag)? {
+                    line.push_str(&format!(" peeled:{}", target));
+                }
+            }
+        }
+        pkt_line::write_line(&mut out, &line);
+    }
+    pkt_line::write_flush(&mut out);
+    Ok(out)
+}
+
+/// Sends a pack of what the `want` lines need and the `have` lines do not
+/// already provide.
+///
+/// Git negotiates over several requests when the client has history of its
+/// own, only saying `done` at the end. This server is always ready after the
+/// first: it acknowledges the commits it shares with the client and sends
+/// the pack right away.
+fn fetch(repo: &Repository, arguments: &[&str]) -> Result<Vec<u8>> {
+    let mut wants = Vec::new();
+    let mut haves = Vec::new();
+    for argument in arguments {
+        if let Some(id) = argument.strip_prefix("want ") {
+            wants.push(id.to_string());
+        } else if let Some(id) = argument.strip_prefix("have ") {
+            if repo.objects().exists(id) {
+                haves.push(id.to_string());
+            }
+        }
+    }
+    check_wants(repo, &wants)?;
+
+    let mut out = Vec::new();
+    if !arguments.contains(&"done") {
+        pkt_line::write_line(&mut out, "acknowledgments");
+        if haves.is_empty() {
+            pkt_line::write_line(&mut out, "NAK");
+        }
+        for id in &haves {
+            pkt_line::write_line(&mut out, &format!("ACK {}", id));
+        }
+        pkt_line::write_line(&mut out, "ready");
+        pkt_line::write_delimiter(&mut out);
+    }
+
+    let ids = objects_to_send(repo.objects(), &wants, &haves)?;
+    let pack = repo.objects().pack_data(&ids)?;
+    pkt_line::write_line(&mut out, "packfile");
+    // Pack data goes on side band 1, in packets as large as allowed
+    for chunk in pack.chunks(MAX_PACKET - 5) {
+        let mut data = Vec::with_capacity(chunk.len() + 1);
+        data.push(1);
+        data.extend_from_slice(chunk);
+        pkt_line::write(&mut out, &data);
+    }
+    pkt_line::write_flush(&mut out);
+    Ok(out)
+}
+
+/// Fails unless every object in `wants` is reachable from what `ls-refs`
+/// advertises, so that objects no ref leads to cannot be fetched by id.
+fn check_wants(repo: &Repository, wants: &[String]) -> Result<()> {
+    let mut tips: Vec<String> = repo.refs()?.into_values().collect();
+    tips.extend(repo.detached_head().map(str::to_string));
+
+    // Clients nearly always want the tips themselves, which spares the walk
+    let mut reachable: Option<HashSet<String>> = None;
+    for id in wants {
+        if tips.contains(id) {
+            continue;
+        }
+        if reachable.is_none() {
+            let objects = reachable_objects(repo.objects(), &tips, &|_| false)?;
+            reachable = Some(objects.into_iter().collect());
+        }
+        if !reachable
+            .as_ref()
+            .is_some_and(|objects| objects.contains(id))
+        {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                format!("not our ref {}", id),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Answers a request to `git-receive-pack`: commands moving refs, then a
+/// pack of the objects they need. Reports how each update went.
+pub fn receive_pack(repo: &mut Repository, request: &[u8]) -> Result<Vec<u8>> {
+    let mut reader = PacketReader::new(request);
+    let (lines, _) = reader.read_lines()?;
+    let zero = "0".repeat(repo.object_format().hex_len());
+
+    let mut commands = Vec::new();
+    for line in lines {
+        // Capabilities follow the first command after a NUL
+        let line = line.split('\0').next().unwrap_or_default();
+        let mut fields = line.splitn(3, ' ');
+        match (fields.next(), fields.next(), fields.next()) {
+            (Some(old), Some(new), Some(name)) => {
+                let old = (old != zero).then(|| old.to_string());
+                commands.push((old, new.to_string(), name.to_string()));
+            }
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("Malformed command: {}", line),
+                ))
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    let pack = reader.remaining();
+    let unpacked = match pack.is_empty() {
+        true => Ok(0),
+        false => repo.objects().unpack(pack),
+    };
+    match &unpacked {
+        Ok(_) => pkt_line::write_line(&mut out, "unpack ok"),
+        Err(error) => pkt_line::write_line(&mut out, &format!("unpack {}", error)),
+    }
+
+    for (old, new, name) in commands {
+        let result = if unpacked.is_err() {
+            Err(Error::other("unpacker error"))
+        } else if new == zero {
+            Err(Error::new(
+                ErrorKind::Unsupported,
+                "deleting refs is not supported",
+            ))
+        } else {
+            repo.receive_update(&name, old.as_deref(), &new)
+        };
+        match result {
+            Ok(()) => pkt_line::write_line(&mut out, &format!("ok {}", name)),
+            Err(error) => pkt_line::write_line(&mut out, &format!("ng {} {}", name, error)),
+        }
+    }
+    pkt_line::write_flush(&mut out);
+    Ok(out)
+}
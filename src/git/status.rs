@@ -0,0 +1,134 @@
+#![allow(unused)]
+
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use colored::Colorize;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileStatus {
+    /// In the index but not in HEAD
+    StagedNew,
+    /// In both, with a different blob or mode in the index
+    StagedModified,
+    /// In HEAD but removed from the index
+    StagedDeleted,
+    /// Left with conflict markers by a merge and not staged since
+    Unmerged,
+    /// The working file differs from the index
+    Modified,
+    /// In the index but missing from the working tree
+    Deleted,
+    Untracked,
+    Ignored,
+}
+
+impl FileStatus {
+    pub fn is_staged(&self) -> bool {
+        matches!(
+            self,
+            FileStatus::StagedNew | FileStatus::StagedModified | FileStatus::StagedDeleted
+        )
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatusEntry {
+    pub path: PathBuf,
+    pub status: FileStatus,
+}
+
+/// Renders entries in `git status --porcelain` (v1) format: one `XY path`
+/// line per path, where X is the staged and Y the unstaged state.
+pub fn render_porcelain(entries: &[StatusEntry], show_ignored: bool) -> String {
+    let mut codes: BTreeMap<&PathBuf, [char; 2]> = BTreeMap::new();
+    for entry in entries {
+        let code = codes.entry(&entry.path).or_insert([' ', ' ']);
+        match entry.status {
+            FileStatus::StagedNew => code[0] = 'A',
+            FileStatus::StagedModified => code[0] = 'M',
+            FileStatus::StagedDeleted => code[0] = 'D',
+            FileStatus::Unmerged => *code = ['U', 'U'],
+            FileStatus::Modified => code[1] = 'M',
+            FileStatus::Deleted => code[1] = 'D',
+            FileStatus::Untracked => *code = ['?', '?'],
+            FileStatus::Ignored => *code = ['!', '!'],
+        }
+    }
+
+    let mut output = String::new();
+    for (path, code) in codes {
+        if code[0] == '!' && !show_ignored {
+            continue;
+        }
+        output.push_str(&format!("{}{} {}\n", code[0], code[1], path.display()));
+    }
+    output
+}
+
+/// Renders entries the way `git status` does for humans, grouped by section.
+/// `detached` is the commit HEAD points at when no branch is checked out.
+pub fn render_long(
+    branch: &str,
+    detached: Option<&str>,
+    entries: &[StatusEntry],
+    show_ignored: bool,
+) -> String {
+    let section = |title: &str, statuses: &[FileStatus], staged: bool| {
+        let mut lines = String::new();
+        for entry in entries.iter().filter(|e| statuses.contains(&e.status)) {
+            let label = match entry.status {
+                FileStatus::StagedNew => "new file:   ",
+                FileStatus::StagedModified | FileStatus::Modified => "modified:   ",
+                FileStatus::StagedDeleted | FileStatus::Deleted => "deleted:    ",
+                FileStatus::Unmerged => "both modified:   ",
+                FileStatus::Untracked | FileStatus::Ignored => "",
+            };
+            let line = format!("{}{}", label, entry.path.display());
+            let line = if staged { line.green() } else { line.red() };
+            lines.push_str(&format!("\t{}\n", line));
+        }
+
+        if lines.is_empty() {
+            String::new()
+        } else {
+            format!("{}:\n{}\n", title, lines)
+        }
+    };
+
+    let mut output = match detached {
+        Some(id) => format!("HEAD detached at {}\n", &id[..7]),
+        None => format!("On branch {}\n", branch),
+    };
+    output.push_str(&section(
+        "Changes to be committed",
+        &[
+            FileStatus::StagedNew,
+            FileStatus::StagedModified,
+            FileStatus::StagedDeleted,
+        ],
+        true,
+    ));
+    output.push_str(&section("Unmerged paths", &[FileStatus::Unmerged], false));
+    output.push_str(&section(
+        "Changes not staged for commit",
+        &[FileStatus::Modified, FileStatus::Deleted],
+        false,
+    ));
+    output.push_str(&section("Untracked files", &[FileStatus::Untracked], false));
+    if show_ignored {
+        output.push_str(&section("Ignored files", &[FileStatus::Ignored], false));
+    }
+
+    let has_changes = entries
+        .iter()
+        .any(|e| e.status != FileStatus::Untracked && e.status != FileStatus::Ignored);
+    if !has_changes {
+        if entries.iter().any(|e| e.status == FileStatus::Untracked) {
+            output.push_str("nothing added to commit but untracked files present\n");
+        } else {
+            output.push_str("nothing to commit, working tree clean\n");
+        }
+    }
+    output
+}
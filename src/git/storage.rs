@@ -0,0 +1,390 @@
+#![allow(unused)]
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+use crate::git::branch::Branch;
+use crate::git::commit::Signature;
+use crate::git::config::Config;
+
+/// How a repository lays out its refs, index and objects on disk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum StorageFormat {
+    /// `.git-scm` with bincode branch records and index, and uncompressed
+    /// loose objects
+    #[default]
+    Native,
+    /// Git's own `.git` layout: zlib-compressed objects, text refs with
+    /// reflogs and a DIRC index, so stock Git can share the checkout
+    Git,
+}
+
+impl StorageFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StorageFormat::Native => "native",
+            StorageFormat::Git => "git",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "native" => Ok(StorageFormat::Native),
+            "git" => Ok(StorageFormat::Git),
+            _ => Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("Unknown storage format: {}", name),
+            )),
+        }
+    }
+
+    /// Name of the git directory inside a work tree.
+    pub fn dir_name(&self) -> &'static str {
+        match self {
+            StorageFormat::Native => ".git-scm",
+            StorageFormat::Git => ".git",
+        }
+    }
+
+    /// The format of the repository in `git_dir`: `core.storage` if set,
+    /// otherwise native for a `.git-scm` directory and Git's for any other.
+    pub fn detect(git_dir: &Path, config: &Config) -> Result<Self> {
+        if let Some(name) = config.get("core.storage") {
+            return Self::parse(name);
+        }
+        if git_dir.file_name().is_some_and(|name| name == ".git-scm") {
+            Ok(StorageFormat::Native)
+        } else {
+            Ok(StorageFormat::Git)
+        }
+    }
+}
+
+/// Reads every branch of a Git-layout repository from `refs/heads` and
+/// `packed-refs`, taking each branch's history from its reflog.
+pub fn load_git_branches(git_dir: &Path) -> Result<HashMap<String, Branch>> {
+    let mut branches = HashMap::new();
+    for (name, head) in list_refs(git_dir, "refs/heads/")? {
+        let log_path = git_dir.join("logs").join("refs").join("heads").join(&name);
+        let mut commits = Vec::new();
+        if log_path.is_file() {
+            for line in fs::read_to_string(&log_path)?.lines() {
+                // `<old id> <new id> <signature>\t<message>`
+                if let Some(id) = line.split(' ').nth(1) {
+                    commits.push(id.to_string());
+                }
+            }
+        }
+        // Without a matching log the branch's history starts at its head
+        if commits.last() != Some(&head) {
+            commits.push(head);
+        }
+        branches.insert(name.clone(), Branch::with_commits(name, commits));
+    }
+    Ok(branches)
+}
+
+/// The plain-text refs below `prefix`, such as `refs/tags/`, from loose
+/// files and `packed-refs`, keyed by their name after the prefix. Symbolic
+/// refs are returned as written, `ref: <target>`.
+pub fn list_refs(git_dir: &Path, prefix: &str) -> Result<BTreeMap<String, String>> {
+    let mut refs = BTreeMap::new();
+
+    // Loose refs take precedence over packed ones
+    let packed_path = git_dir.join("packed-refs");
+    if packed_path.is_file() {
+        for line in fs::read_to_string(&packed_path)?.lines() {
+            if line.starts_with('#') || line.starts_with('^') {
+                continue;
+            }
+            if let Some((id, name)) = line.split_once(' ') {
+                if let Some(name) = name.strip_prefix(prefix) {
+                    refs.insert(name.to_string(), id.to_string());
+                }
+            }
+        }
+    }
+    collect_loose_refs(&git_dir.join(prefix), "", &mut refs)?;
+    Ok(refs)
+}
+
+fn collect_loose_refs(dir: &Path, prefix: &str, refs: &mut BTreeMap<String, String>) -> Result<()> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            collect_loose_refs(&entry.path(), &format!("{}/", name), refs)?;
+        } else {
+            let value = fs::read_to_string(entry.path())?.trim().to_string();
+            refs.insert(name, value);
+        }
+    }
+    Ok(())
+}
+
+/// The id the plain-text ref `name`, such as `refs/remotes/origin/main`,
+/// points at, following symbolic refs.
+pub fn read_ref(git_dir: &Path, name: &str) -> Result<Option<String>> {
+    let path = git_dir.join(name);
+    let value = if path.is_file() {
+        fs::read_to_string(path)?.trim().to_string()
+    } else {
+        let (prefix, short) = name.rsplit_once('/').unwrap_or(("", name));
+        match list_refs(git_dir, &format!("{}/", prefix))?.remove(short) {
+            Some(value) => value,
+            None => return Ok(None),
+        }
+    };
+
+    match value.strip_prefix("ref: ") {
+        Some(target) => read_ref(git_dir, target),
+        None => Ok(Some(value)),
+    }
+}
+
+/// Points the plain-text ref `name` at `value`, an id or `ref: <target>`.
+pub fn write_ref(git_dir: &Path, name: &str, value: &str) -> Result<()> {
+    write_file(&git_dir.join(name), format!("{}\n", value).as_bytes())
+}
+
+/// Removes the plain-text ref `name`, loose and packed, along with its
+/// reflog.
+pub fn delete_ref(git_dir: &Path, name: &str) -> Result<()> {
+    let path = git_dir.join(name);
+    if path.is_file() {
+        fs::remove_file(path)?;
+    }
+    let log_path = git_dir.join("logs").join(name);
+    if log_path.is_file() {
+        fs::remove_file(log_path)?;
+    }
+
+    let packed_path = git_dir.join("packed-refs");
+    if !packed_path.is_file() {
+        return Ok(());
+    }
+    let mut kept = String::new();
+    let mut removed = false;
+    for line in fs::read_to_string(&packed_path)?.lines() {
+        // A `^<id>` line peels the tag on the line before it
+        if line
+            .split_once(' ')
+            .is_some_and(|(_, packed)| packed == name)
+            || (removed && line.starts_with('^'))
+        {
+            removed = true;
+            continue;
+        }
+        removed = false;
+        kept.push_str(line);
+        kept.push('\n');
+    }
+    write_file(&packed_path, kept.as_bytes())
+}
+
+/// Writes `branch` as a Git ref, appending the positions its reflog does not
+/// have yet. A branch without commits is left unborn, as Git does.
+pub fn save_git_branch(git_dir: &Path, branch: &Branch, who: &Signature) -> Result<()> {
+    let Some(head) = branch.head_commit_id() else {
+        return Ok(());
+    };
+
+    let ref_path = git_dir.join("refs").join("heads").join(branch.name());
+    write_file(&ref_path, format!("{}\n", head).as_bytes())?;
+
+    let log_path = git_dir
+        .join("logs")
+        .join("refs")
+        .join("heads")
+        .join(branch.name());
+    let mut log = if log_path.is_file() {
+        fs::read_to_string(&log_path)?
+    } else {
+        String::new()
+    };
+    let logged = log.lines().count();
+    let commits = branch.commits();
+    if logged >= commits.len() {
+        return Ok(());
+    }
+
+    let zero = "0".repeat(head.len());
+    for position in logged..commits.len() {
+        let old = match position {
+            0 => &zero,
+            _ => &commits[position - 1],
+        };
+        log.push_str(&format!(
+            "{} {} {}\tupdate: moving to {}\n",
+            old, commits[position], who, commits[position]
+        ));
+    }
+    write_file(&log_path, log.as_bytes())
+}
+
+/// Writes through a temporary file so readers never see a partial file.
+fn write_file(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".lock");
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_git_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("git-scm-storage-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn id(n: u8) -> String {
+        format!("{:02x}", n).repeat(20)
+    }
+
+    fn who() -> Signature {
+        Signature::new(
+            "Test".to_string(),
+            "test@example.com".to_string(),
+            1700000000,
+            60,
+        )
+    }
+
+    #[test]
+    fn writes_and_reads_back_branches_with_their_reflogs() {
+        let git_dir = temp_git_dir("branches");
+        let mut branch = Branch::with_commits("feature/x".to_string(), vec![id(1), id(2)]);
+        save_git_branch(&git_dir, &branch, &who()).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(git_dir.join("refs/heads/feature/x")).unwrap(),
+            format!("{}\n", id(2))
+        );
+        let log_path = git_dir.join("logs/refs/heads/feature/x");
+        assert_eq!(
+            fs::read_to_string(&log_path).unwrap(),
+            format!(
+                "{zero} {one} Test <test@example.com> 1700000000 +0100\tupdate: moving to {one}\n\
+                 {one} {two} Test <test@example.com> 1700000000 +0100\tupdate: moving to {two}\n",
+                zero = "0".repeat(40),
+                one = id(1),
+                two = id(2)
+            )
+        );
+
+        // Saving again only appends what the reflog is missing
+        branch.add_commit(id(3));
+        save_git_branch(&git_dir, &branch, &who()).unwrap();
+        save_git_branch(&git_dir, &branch, &who()).unwrap();
+        let log = fs::read_to_string(&log_path).unwrap();
+        assert_eq!(log.lines().count(), 3);
+        assert!(log
+            .lines()
+            .last()
+            .unwrap()
+            .starts_with(&format!("{} {} ", id(2), id(3))));
+
+        let branches = load_git_branches(&git_dir).unwrap();
+        assert_eq!(branches.len(), 1);
+        assert_eq!(branches["feature/x"].commits(), &vec![id(1), id(2), id(3)]);
+        fs::remove_dir_all(&git_dir).unwrap();
+    }
+
+    #[test]
+    fn leaves_branches_without_commits_unborn() {
+        let git_dir = temp_git_dir("unborn");
+        save_git_branch(&git_dir, &Branch::new("main".to_string()), &who()).unwrap();
+        assert!(!git_dir.join("refs/heads/main").exists());
+        assert!(load_git_branches(&git_dir).unwrap().is_empty());
+        fs::remove_dir_all(&git_dir).unwrap();
+    }
+
+    #[test]
+    fn starts_history_at_the_head_without_a_reflog() {
+        let git_dir = temp_git_dir("no-reflog");
+        write_ref(&git_dir, "refs/heads/main", &id(1)).unwrap();
+        let branches = load_git_branches(&git_dir).unwrap();
+        assert_eq!(branches["main"].commits(), &vec![id(1)]);
+        fs::remove_dir_all(&git_dir).unwrap();
+    }
+
+    #[test]
+    fn reads_loose_and_packed_refs() {
+        let git_dir = temp_git_dir("refs");
+        fs::write(
+            git_dir.join("packed-refs"),
+            format!(
+                "# pack-refs with: peeled fully-peeled sorted\n\
+                 {} refs/heads/main\n\
+                 {} refs/tags/v1\n\
+                 ^{}\n\
+                 {} refs/tags/v2\n",
+                id(1),
+                id(2),
+                id(3),
+                id(4)
+            ),
+        )
+        .unwrap();
+        // A loose ref overrides its packed value
+        write_ref(&git_dir, "refs/heads/main", &id(5)).unwrap();
+        write_ref(&git_dir, "refs/remotes/origin/main", &id(6)).unwrap();
+        write_ref(
+            &git_dir,
+            "refs/remotes/origin/HEAD",
+            "ref: refs/remotes/origin/main",
+        )
+        .unwrap();
+
+        let tags = list_refs(&git_dir, "refs/tags/").unwrap();
+        assert_eq!(
+            tags,
+            BTreeMap::from([("v1".to_string(), id(2)), ("v2".to_string(), id(4))])
+        );
+        assert_eq!(read_ref(&git_dir, "refs/heads/main").unwrap(), Some(id(5)));
+        assert_eq!(read_ref(&git_dir, "refs/tags/v2").unwrap(), Some(id(4)));
+        assert_eq!(
+            read_ref(&git_dir, "refs/remotes/origin/HEAD").unwrap(),
+            Some(id(6))
+        );
+        assert_eq!(read_ref(&git_dir, "refs/tags/v3").unwrap(), None);
+
+        // Deleting a packed tag takes its peeled line along
+        delete_ref(&git_dir, "refs/tags/v1").unwrap();
+        assert_eq!(
+            fs::read_to_string(git_dir.join("packed-refs")).unwrap(),
+            format!(
+                "# pack-refs with: peeled fully-peeled sorted\n{} refs/heads/main\n{} refs/tags/v2\n",
+                id(1),
+                id(4)
+            )
+        );
+        assert_eq!(read_ref(&git_dir, "refs/tags/v1").unwrap(), None);
+
+        // And a loose branch its reflog
+        save_git_branch(
+            &git_dir,
+            &Branch::with_commits("topic".to_string(), vec![id(7)]),
+            &who(),
+        )
+        .unwrap();
+        delete_ref(&git_dir, "refs/heads/topic").unwrap();
+        assert!(!git_dir.join("refs/heads/topic").exists());
+        assert!(!git_dir.join("logs/refs/heads/topic").exists());
+        fs::remove_dir_all(&git_dir).unwrap();
+    }
+}
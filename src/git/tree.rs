@@ -0,0 +1,224 @@
+#![allow(unused)]
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::Metadata;
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Component, Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::git::hash::{from_hex, to_hex, HashAlgorithm};
+use crate::git::index::IndexEntry;
+use crate::git::object_store::{ObjectKind, ObjectStore};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FileMode {
+    Regular,
+    Executable,
+    Symlink,
+    Tree,
+}
+
+impl FileMode {
+    /// The octal mode Git writes for this kind of entry.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileMode::Regular => "100644",
+            FileMode::Executable => "100755",
+            FileMode::Symlink => "120000",
+            FileMode::Tree => "40000",
+        }
+    }
+
+    pub fn parse(mode: &str) -> Option<Self> {
+        match mode {
+            "100644" | "100664" => Some(FileMode::Regular),
+            "100755" => Some(FileMode::Executable),
+            "120000" => Some(FileMode::Symlink),
+            "40000" | "040000" => Some(FileMode::Tree),
+            _ => None,
+        }
+    }
+
+    /// Derives the mode of a working tree file from `symlink_metadata`.
+    pub fn from_metadata(metadata: &Metadata) -> Self {
+        if metadata.file_type().is_symlink() {
+            return FileMode::Symlink;
+        }
+        if metadata.is_dir() {
+            return FileMode::Tree;
+        }
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            if metadata.permissions().mode() & 0o111 != 0 {
+                return FileMode::Executable;
+            }
+        }
+        FileMode::Regular
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreeEntry {
+    pub mode: FileMode,
+    pub name: String,
+    pub id: String,
+}
+
+impl TreeEntry {
+    /// Git orders entries by name, comparing directories as if they ended in `/`.
+    fn sort_key(&self) -> Vec<u8> {
+        let mut key = self.name.as_bytes().to_vec();
+        if self.mode == FileMode::Tree {
+            key.push(b'/');
+        }
+        key
+    }
+}
+
+/// A single directory level: sorted entries pointing at blobs or subtrees.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Tree {
+    entries: Vec<TreeEntry>,
+}
+
+impl Tree {
+    pub fn new(mut entries: Vec<TreeEntry>) -> Self {
+        entries.sort_by_key(|entry| entry.sort_key());
+        Tree { entries }
+    }
+
+    pub fn entries(&self) -> &[TreeEntry] {
+        &self.entries
+    }
+
+    /// Serializes the tree as a sequence of `<mode> <name>\0<raw id>` records.
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        for entry in &self.entries {
+            data.extend_from_slice(format!("{} {}\0", entry.mode.as_str(), entry.name).as_bytes());
+            data.extend_from_slice(&from_hex(&entry.id)?);
+        }
+        Ok(data)
+    }
+
+    pub fn deserialize(data: &[u8], algorithm: HashAlgorithm) -> Result<Self> {
+        let malformed =
+            |what: &str| Error::new(ErrorKind::InvalidData, format!("Malformed tree: {}", what));
+        let id_len = algorithm.raw_len();
+
+        let mut entries = Vec::new();
+        let mut rest = data;
+        while !rest.is_empty() {
+            let nul = rest
+                .iter()
+                .position(|&b| b == 0)
+                .ok_or_else(|| malformed("unterminated entry"))?;
+            let header = std::str::from_utf8(&rest[..nul])
+                .map_err(|_| malformed("entry name is not valid UTF-8"))?;
+            let (mode, name) = header
+                .split_once(' ')
+                .ok_or_else(|| malformed("missing mode"))?;
+            let mode = FileMode::parse(mode).ok_or_else(|| malformed(mode))?;
+            let raw_id = rest
+                .get(nul + 1..nul + 1 + id_len)
+                .ok_or_else(|| malformed("truncated object id"))?;
+
+            entries.push(TreeEntry {
+                mode,
+                name: name.to_string(),
+                id: to_hex(raw_id),
+            });
+            rest = &rest[nul + 1 + id_len..];
+        }
+
+        Ok(Tree { entries })
+    }
+
+    pub fn read(store: &ObjectStore, id: &str) -> Result<Self> {
+        let data = store.read_kind(id, ObjectKind::Tree)?;
+        Self::deserialize(&data, store.algorithm())
+    }
+
+    /// Writes the nested trees describing `entries` and returns the root tree id.
+    ///
+    /// Directories whose contents did not change hash to the same id as
+    /// before, so they are shared between commits.
+    pub fn write_from_index(
+        store: &ObjectStore,
+        entries: &HashMap<PathBuf, IndexEntry>,
+    ) -> Result<String> {
+        // Group the entries by their first path component
+        let mut files = Vec::new();
+        let mut subdirs: BTreeMap<String, HashMap<PathBuf, IndexEntry>> = BTreeMap::new();
+        for (path, entry) in entries {
+            // Only named components become tree entries, so `./src/x.rs` and
+            // `src/x.rs` land in the same place
+            let mut components = path
+                .components()
+                .filter(|c| matches!(c, Component::Normal(_)))
+                .map(|c| c.as_os_str().to_string_lossy().to_string());
+            let first = components
+                .next()
+                .ok_or_else(|| Error::new(ErrorKind::InvalidInput, "Empty path in index"))?;
+            let rest: PathBuf = components.collect();
+
+            if rest.as_os_str().is_empty() {
+                files.push(TreeEntry {
+                    mode: entry.mode,
+                    name: first,
+                    id: entry.id.clone(),
+                });
+            } else {
+                subdirs
+                    .entry(first)
+                    .or_default()
+                    .insert(rest, entry.clone());
+            }
+        }
+
+        for (name, sub_entries) in subdirs {
+            let id = Self::write_from_index(store, &sub_entries)?;
+            files.push(TreeEntry {
+                mode: FileMode::Tree,
+                name,
+                id,
+            });
+        }
+
+        let tree = Tree::new(files);
+        store.write(ObjectKind::Tree, &tree.serialize()?)
+    }
+
+    /// Expands a tree into the blobs it contains, keyed by their full path.
+    pub fn flatten(store: &ObjectStore, id: &str) -> Result<BTreeMap<PathBuf, IndexEntry>> {
+        let mut paths = BTreeMap::new();
+        Self::flatten_into(store, id, Path::new(""), &mut paths)?;
+        Ok(paths)
+    }
+
+    fn flatten_into(
+        store: &ObjectStore,
+        id: &str,
+        prefix: &Path,
+        paths: &mut BTreeMap<PathBuf, IndexEntry>,
+    ) -> Result<()> {
+        for entry in Self::read(store, id)?.entries {
+            let path = prefix.join(&entry.name);
+            if entry.mode == FileMode::Tree {
+                Self::flatten_into(store, &entry.id, &path, paths)?;
+            } else {
+                paths.insert(
+                    path,
+                    IndexEntry {
+                        id: entry.id,
+                        mode: entry.mode,
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,119 @@
+#![allow(unused)]
+
+use std::fs;
+use std::io::{ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+use crate::git::index::IndexEntry;
+use crate::git::object_store::{hash_object, ObjectKind, ObjectStore};
+use crate::git::tree::FileMode;
+
+/// The checked out files of a repository.
+#[derive(Debug)]
+pub struct WorkTree {
+    root: PathBuf,
+}
+
+impl WorkTree {
+    pub fn new(root: &Path) -> Self {
+        WorkTree {
+            root: root.to_path_buf(),
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Reads the bytes that would be stored for `path`: the file contents, or
+    /// the link target for a symlink.
+    pub fn read(&self, path: &Path) -> Result<Option<(FileMode, Vec<u8>)>> {
+        let full_path = self.root.join(path);
+        let metadata = match fs::symlink_metadata(&full_path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let mode = FileMode::from_metadata(&metadata);
+        let content = match mode {
+            FileMode::Tree => return Ok(None),
+            FileMode::Symlink => fs::read_link(&full_path)?
+                .to_string_lossy()
+                .as_bytes()
+                .to_vec(),
+            _ => fs::read(&full_path)?,
+        };
+        Ok(Some((mode, content)))
+    }
+
+    /// Computes the index entry `path` would get if it were staged now,
+    /// without writing anything to the object store.
+    pub fn entry(&self, path: &Path, store: &ObjectStore) -> Result<Option<IndexEntry>> {
+        Ok(self.read(path)?.map(|(mode, content)| IndexEntry {
+            id: hash_object(store.algorithm(), ObjectKind::Blob, &content),
+            mode,
+        }))
+    }
+
+    /// Materializes a blob from the object store at `path`.
+    pub fn write(&self, path: &Path, entry: &IndexEntry, store: &ObjectStore) -> Result<()> {
+        let content = store.read_kind(&entry.id, ObjectKind::Blob)?;
+        self.write_content(path, entry.mode, &content)
+    }
+
+    pub fn write_content(&self, path: &Path, mode: FileMode, content: &[u8]) -> Result<()> {
+        let full_path = self.root.join(path);
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Replace whatever is there, including a symlink or an empty directory
+        match fs::symlink_metadata(&full_path) {
+            Ok(metadata) if metadata.is_dir() => fs::remove_dir(&full_path)?,
+            Ok(_) => fs::remove_file(&full_path)?,
+            Err(_) => {}
+        }
+
+        if mode == FileMode::Symlink {
+            #[cfg(unix)]
+            {
+                let target = String::from_utf8_lossy(content).to_string();
+                return std::os::unix::fs::symlink(target, &full_path);
+            }
+        }
+
+        fs::write(&full_path, content)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let permissions = if mode == FileMode::Executable {
+                0o755
+            } else {
+                0o644
+            };
+            fs::set_permissions(&full_path, fs::Permissions::from_mode(permissions))?;
+        }
+        Ok(())
+    }
+
+    /// Deletes `path` and any parent directories it leaves empty.
+    pub fn remove(&self, path: &Path) -> Result<()> {
+        let full_path = self.root.join(path);
+        match fs::remove_file(&full_path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        }
+
+        let mut dir = full_path.parent();
+        while let Some(current) = dir {
+            if current == self.root || fs::remove_dir(current).is_err() {
+                break;
+            }
+            dir = current.parent();
+        }
+        Ok(())
+    }
+}
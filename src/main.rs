@@ -3,14 +3,42 @@ mod git;
 
 use anyhow::{Context, Result};
 use clap::{Args, Parser, Subcommand};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use git::repository::Repository;
+use git::config::{parse_bool, set_value, unset_value, Config, ConfigScope};
+use git::diff::{DiffAlgorithm, DiffOptions};
+use git::hash::HashAlgorithm;
+use git::http::Url;
+use git::log::{parse_date, LogEntry, LogFormat, LogOptions};
+use git::merge::{FastForwardMode, MergeStatus};
+use git::object_store::RepackSummary;
+use git::remote::RefUpdateStatus;
+use git::repository::{Repository, ResetMode};
+use git::storage::StorageFormat;
 
 #[derive(Parser)]
 #[command(name = "git-scm")]
 #[command(about = "Git-SCM - A Git-like version control tool")]
 struct Cli {
+    /// Run as if started in this directory; repeat to descend further
+    #[arg(short = 'C', global = true, value_name = "PATH")]
+    directories: Vec<PathBuf>,
+
+    /// Repository directory to use instead of looking for `.git-scm`
+    /// (`$GIT_DIR`)
+    #[arg(long, global = true, value_name = "PATH")]
+    git_dir: Option<PathBuf>,
+
+    /// Work tree to use, the current directory by default with --git-dir
+    /// (`$GIT_WORK_TREE`)
+    #[arg(long, global = true, value_name = "PATH")]
+    work_tree: Option<PathBuf>,
+
+    /// Set a configuration value for this command only
+    #[arg(short = 'c', global = true, value_name = "NAME=VALUE")]
+    config: Vec<String>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -21,6 +49,31 @@ enum Commands {
     Init {
         #[arg(default_value = ".")]
         path: PathBuf,
+
+        /// Hash algorithm used for object ids (sha1 or sha256), sha1 for new
+        /// repositories unless given
+        #[arg(long)]
+        object_format: Option<String>,
+
+        /// On-disk layout: native (`.git-scm`) or git (`.git`, shared with
+        /// stock Git)
+        #[arg(long)]
+        storage: Option<String>,
+
+        /// Create a repository without a work tree, directly in the
+        /// directory, for others to push to
+        #[arg(long)]
+        bare: bool,
+    },
+
+    /// Copy a repository into a new directory
+    Clone {
+        /// Work tree or bare repository to clone, as a path or `file://` URL,
+        /// or an `http://` URL
+        source: String,
+
+        /// Where to clone to, by default a directory named after the source
+        directory: Option<PathBuf>,
     },
 
     /// Stage files for commit
@@ -29,6 +82,20 @@ enum Commands {
         paths: Vec<PathBuf>,
     },
 
+    /// Stop tracking files and delete them from the working tree
+    Rm {
+        /// Files to remove
+        paths: Vec<PathBuf>,
+
+        /// Only remove the files from the index, keeping them on disk
+        #[arg(long)]
+        cached: bool,
+
+        /// Allow removing directories
+        #[arg(short)]
+        r: bool,
+    },
+
     /// Commit staged changes
     // Commit {
     //     /// Commit message
@@ -46,38 +113,187 @@ enum Commands {
         name: String,
     },
 
-    /// Switch to a different branch
+    /// Switch to a different branch, or restore files from a revision
     Checkout {
-        /// Name of the branch to switch to
+        /// Name of the branch to switch to, a revision to detach HEAD at, or
+        /// the revision to restore from
         branch: String,
+
+        /// Discard local changes that would be overwritten
+        #[arg(short, long)]
+        force: bool,
+
+        /// Files to restore from the revision instead of switching branches
+        #[arg(last = true)]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Move the current branch to another commit
+    Reset {
+        /// Commit to move to
+        #[arg(default_value = "HEAD")]
+        revision: String,
+
+        /// Keep the index and working tree
+        #[arg(long, conflicts_with_all = ["mixed", "hard"])]
+        soft: bool,
+
+        /// Reset the index but keep the working tree (the default)
+        #[arg(long, conflicts_with = "hard")]
+        mixed: bool,
+
+        /// Reset the index and working tree, discarding local changes
+        #[arg(long)]
+        hard: bool,
+    },
+
+    /// Show a commit and the changes it made
+    Show {
+        /// Commit to show
+        #[arg(default_value = "HEAD")]
+        revision: String,
+
+        /// Lines of context around each change
+        #[arg(short = 'U', long = "unified", default_value_t = 3)]
+        context: usize,
     },
 
-    /// Show differences between branches
+    /// Show differences between commits
     Diff {
-        /// Source branch
-        #[arg(default_value = "main")]
-        branch1: String,
+        /// Revision to compare from, or a range such as `A..B` or `A...B`,
+        /// `init.defaultBranch` (`main` unless set) by default
+        from: Option<String>,
 
-        /// Target branch
-        #[arg(default_value = "")]
-        branch2: Option<String>,
+        /// Revision to compare to, HEAD by default
+        to: Option<String>,
+
+        /// Lines of context around each change, `diff.context` by default
+        #[arg(short = 'U', long = "unified")]
+        context: Option<usize>,
+
+        /// Line matching algorithm (myers, patience or histogram),
+        /// `diff.algorithm` by default
+        #[arg(long)]
+        diff_algorithm: Option<String>,
     },
 
-    /// Merge branches
+    /// Merge a branch into the current branch
     Merge {
-        /// Base branch name
-        base: String,
+        /// Branch to merge
+        branch: String,
 
-        /// Source branch
-        source: String,
+        /// Fast-forward when possible, overriding `merge.ff`
+        #[arg(long, conflicts_with_all = ["no_ff", "ff_only"])]
+        ff: bool,
 
-        /// Target branch (defaults to current branch)
-        #[arg(default_value = "")]
-        target: Option<String>,
+        /// Create a merge commit even when a fast-forward is possible
+        #[arg(long, conflicts_with = "ff_only")]
+        no_ff: bool,
+
+        /// Refuse to merge unless the current branch can be fast-forwarded
+        #[arg(long)]
+        ff_only: bool,
     },
 
     /// Show commit history
-    Log,
+    Log {
+        /// Commit to start from, or a range such as `A..B` or `A...B`
+        #[arg(default_value = "HEAD")]
+        revision: String,
+
+        /// Show each commit on a single line
+        #[arg(long)]
+        oneline: bool,
+
+        /// Draw the branch and merge structure next to the commits
+        #[arg(long)]
+        graph: bool,
+
+        /// Show at most this many commits
+        #[arg(short = 'n', long = "max-count")]
+        max_count: Option<usize>,
+
+        /// Only commits whose author contains this text
+        #[arg(long)]
+        author: Option<String>,
+
+        /// Only commits whose message contains this text
+        #[arg(long)]
+        grep: Option<String>,
+
+        /// Only commits made at or after this date
+        #[arg(long, alias = "after")]
+        since: Option<String>,
+
+        /// Only commits made at or before this date
+        #[arg(long, alias = "before")]
+        until: Option<String>,
+
+        /// Only commits changing these paths
+        #[arg(last = true)]
+        paths: Vec<PathBuf>,
+    },
+
+    /// Get and set repository or global options
+    Config(ConfigCommand),
+
+    /// Manage the repositories this one fetches from and pushes to
+    Remote(RemoteCommand),
+
+    /// Download the branches of a remote into its remote-tracking branches
+    Fetch {
+        /// Remote to fetch from, the one the current branch tracks by default
+        remote: Option<String>,
+    },
+
+    /// Update a branch of a remote with local commits
+    Push {
+        /// Remote to push to, the one the current branch tracks by default
+        remote: Option<String>,
+
+        /// Branch to push, the current branch by default
+        branch: Option<String>,
+
+        /// Update the remote branch even when it is not a fast-forward
+        #[arg(short, long)]
+        force: bool,
+    },
+
+    /// Show which paths are ignored, and why
+    CheckIgnore {
+        /// Paths to check
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Print the ignore file, line and pattern that matched
+        #[arg(short, long)]
+        verbose: bool,
+
+        /// With --verbose, also list paths that match no pattern
+        #[arg(short, long, requires = "verbose")]
+        non_matching: bool,
+    },
+
+    /// Pack loose objects into a packfile, storing similar objects as deltas
+    Repack {
+        /// Also consolidate existing packs into the new one
+        #[arg(short, long)]
+        all: bool,
+    },
+
+    /// Consolidate every object into a single pack
+    Gc,
+
+    /// Show staged, unstaged and untracked files
+    Status {
+        /// Machine-readable output
+        #[arg(long)]
+        porcelain: bool,
+
+        /// Also list ignored files
+        #[arg(long)]
+        ignored: bool,
+    },
 }
 
 #[derive(Debug, Args)]
@@ -94,35 +310,254 @@ pub enum CommitCommands {
     History,
 }
 
+#[derive(Debug, Args)]
+pub struct ConfigCommand {
+    /// Use the user's global file
+    #[arg(long, global = true, conflicts_with_all = ["system", "local"])]
+    global: bool,
+
+    /// Use the system-wide file
+    #[arg(long, global = true, conflicts_with = "local")]
+    system: bool,
+
+    /// Use the repository's file
+    #[arg(long, global = true)]
+    local: bool,
+
+    #[clap(subcommand)]
+    pub command: ConfigCommands,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommands {
+    /// Print the value of a setting
+    Get {
+        /// Setting such as `user.name`
+        name: String,
+    },
+    /// Set a setting, by default in the repository's file
+    Set {
+        /// Setting such as `user.name`
+        name: String,
+        /// New value
+        value: String,
+    },
+    /// Remove a setting
+    Unset {
+        /// Setting such as `user.name`
+        name: String,
+    },
+    /// List every setting
+    List {
+        /// Prefix each setting with the scope it comes from
+        #[arg(long)]
+        show_scope: bool,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct RemoteCommand {
+    /// Show the URL of each remote
+    #[arg(short, long, global = true)]
+    verbose: bool,
+
+    /// Lists the remotes when omitted
+    #[clap(subcommand)]
+    pub command: Option<RemoteCommands>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RemoteCommands {
+    /// Add a remote tracking the branches of the repository at a URL
+    Add {
+        /// Name such as `origin`
+        name: String,
+        /// Path, `file://` or `http://` URL of the repository
+        url: String,
+    },
+    /// Remove a remote and its remote-tracking branches
+    #[command(alias = "rm")]
+    Remove {
+        /// Remote to remove
+        name: String,
+    },
+    /// List the remotes
+    List,
+}
+
 #[derive(Debug, Args)]
 pub struct CommitCommandMessage {
     /// Commit message
     #[arg(short, long)]
     message: String,
-    /// Author name
-    #[arg(short, long, default_value = "Anonymous")]
-    author: String,
+    /// Author as `Name <email>`, instead of the configured identity
+    #[arg(short, long)]
+    author: Option<String>,
+}
+
+/// Opens the repository selected by `--git-dir` and `--work-tree`, or their
+/// environment variables, else the one enclosing the current directory.
+fn open_repository(cli: &Cli) -> Result<Repository> {
+    let git_dir = cli
+        .git_dir
+        .clone()
+        .or_else(|| std::env::var_os("GIT_DIR").map(PathBuf::from));
+    let work_tree = cli
+        .work_tree
+        .clone()
+        .or_else(|| std::env::var_os("GIT_WORK_TREE").map(PathBuf::from));
+
+    let repo = match (git_dir, work_tree) {
+        (Some(git_dir), work_tree) => {
+            Repository::open_with(&git_dir, &work_tree.unwrap_or_else(|| PathBuf::from(".")))?
+        }
+        (None, Some(work_tree)) => {
+            let found = Repository::discover(".")?;
+            Repository::open_with(found.git_dir(), &work_tree)?
+        }
+        (None, None) => Repository::discover(".")?,
+    };
+    Ok(repo)
+}
+
+fn print_repack_summary(summary: &RepackSummary) {
+    match &summary.pack {
+        Some(name) => println!(
+            "Packed {} object(s) into pack-{}, removing {} loose object(s) and {} old pack(s)",
+            summary.objects, name, summary.removed_loose, summary.removed_packs
+        ),
+        None => println!("Nothing to pack"),
+    }
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
+    for directory in &cli.directories {
+        std::env::set_current_dir(directory)
+            .with_context(|| format!("Cannot change to {}", directory.display()))?;
+    }
+    Config::add_parameters(&cli.config).context("Invalid -c option")?;
 
-    // Initialize the repository once, or create a dummy repository for `Init` command
+    // Open the repository once, or skip it for the `Init` command
     let mut repo = match &cli.command {
         Commands::Init { .. } => None, // No repository exists yet; Init will create one
-        _ => Some(Repository::init(".").context("Failed to open or initialize repository")?),
+        Commands::Clone { .. } => None,
+        // Global and system settings work outside a repository too
+        Commands::Config(_) => open_repository(&cli).ok(),
+        _ => Some(open_repository(&cli).context("Failed to open repository")?),
     };
 
+    // Bare repositories have history but no files to work on
+    let needs_work_tree = matches!(
+        cli.command,
+        Commands::Add { .. }
+            | Commands::Rm { .. }
+            | Commands::Commit(_)
+            | Commands::Checkout { .. }
+            | Commands::Reset { .. }
+            | Commands::Merge { .. }
+            | Commands::CheckIgnore { .. }
+            | Commands::Status { .. }
+    );
+    if needs_work_tree && repo.as_ref().is_some_and(Repository::is_bare) {
+        return Err(anyhow::anyhow!("This operation must be run in a work tree"));
+    }
+
     match &cli.command {
-        Commands::Init { path } => {
-            let _repo = Repository::init(path).context("Failed to initialize repository")?;
-            println!("Initialized empty Git repository in {}", path.display());
+        Commands::Init {
+            path,
+            object_format,
+            storage,
+            bare,
+        } => {
+            let existed = if *bare {
+                path.join("HEAD").is_file()
+            } else {
+                Repository::is_initialized(path)
+            };
+            // Formats not given keep those of an existing repository
+            let existing = existed
+                .then(|| match bare {
+                    true => Repository::open_with(path, path),
+                    false => Repository::open(path),
+                })
+                .transpose()
+                .context("Failed to open existing repository")?;
+            let algorithm = match object_format {
+                Some(name) => HashAlgorithm::parse(name)?,
+                None => existing
+                    .as_ref()
+                    .map_or(HashAlgorithm::default(), Repository::object_format),
+            };
+            let storage = match storage {
+                Some(name) => StorageFormat::parse(name)?,
+                None => existing
+                    .as_ref()
+                    .map_or(StorageFormat::default(), Repository::storage),
+            };
+            let repo = if *bare {
+                Repository::init_bare(path, algorithm, storage)
+            } else {
+                Repository::init_with(path, algorithm, storage)
+            }
+            .context("Failed to initialize repository")?;
+            if existed {
+                println!(
+                    "Reinitialized existing Git repository in {}/",
+                    repo.git_dir().display()
+                );
+            } else {
+                println!(
+                    "Initialized empty Git repository in {}/",
+                    repo.git_dir().display()
+                );
+            }
+            Ok(())
+        }
+
+        Commands::Clone { source, directory } => {
+            // A `file://` URL names a local repository like its path does
+            let local = source.strip_prefix("file://").unwrap_or(source);
+
+            // Like Git, name the clone after the source without `.git`
+            let directory = match directory {
+                Some(directory) => directory.clone(),
+                // The last part of the URL's path, or its host when it has none
+                None if Url::is_http(source) => {
+                    let url = Url::parse(source)?;
+                    let name = url.path.rsplit('/').next().unwrap_or_default();
+                    match name {
+                        "" => PathBuf::from(url.host),
+                        name => PathBuf::from(name.strip_suffix(".git").unwrap_or(name)),
+                    }
+                }
+                None => {
+                    let source = std::fs::canonicalize(local)
+                        .with_context(|| format!("Repository {} does not exist", source))?;
+                    let name = source.file_name().context("Cannot name the clone")?;
+                    let name = name.to_string_lossy();
+                    PathBuf::from(name.strip_suffix(".git").unwrap_or(&name))
+                }
+            };
+            println!("Cloning into '{}'...", directory.display());
+
+            let repo = if Url::is_http(source) {
+                Repository::clone_url(source, &directory)
+            } else {
+                Repository::clone_local(Path::new(local), &directory)
+            }
+            .context("Failed to clone repository")?;
+            if repo.branches()[repo.current_branch()]
+                .head_commit_id()
+                .is_none()
+            {
+                println!("warning: You appear to have cloned an empty repository.");
+            }
             Ok(())
         }
 
         Commands::Add { paths } => {
-            // let mut repo = Repository::init(".")?;
-            let repo= repo.as_mut().expect("Repository must be initialized");
+            let repo = repo.as_mut().expect("Repository must be initialized");
             for path in paths {
                 repo.add(path)
                     .with_context(|| format!("Failed to add {}", path.display()))?;
@@ -131,108 +566,465 @@ fn main() -> Result<()> {
             Ok(())
         }
 
+        Commands::Rm { paths, cached, r } => {
+            let repo = repo.as_mut().expect("Repository must be initialized");
+            for path in paths {
+                repo.remove(path, *cached, *r)
+                    .with_context(|| format!("Failed to remove {}", path.display()))?;
+                println!("rm '{}'", path.display());
+            }
+            Ok(())
+        }
+
         Commands::Commit(Commit { command }) => match command {
             CommitCommands::Message(CommitCommandMessage { message, author }) => {
-                // let mut repo = Repository::init(".")?;
-                let repo= repo.as_mut().expect("Repository must be initialized");
+                let repo = repo.as_mut().expect("Repository must be initialized");
                 let commit_id = repo
-                    .commit(message.clone(), author.clone())
+                    .commit(message.clone(), author.as_deref())
                     .context("Failed to create commit")?;
                 println!("Commit created: {}", commit_id);
                 Ok(())
             }
             CommitCommands::History => {
-                // let repo = Repository::init(".")?;
-                let repo= repo.as_mut().expect("Repository must be initialized");
+                let repo = repo.as_mut().expect("Repository must be initialized");
                 repo.log();
                 Ok(())
             }
         },
 
         Commands::Branch { name } => {
-            // let mut repo = Repository::init(".")?;
-            let repo= repo.as_mut().expect("Repository must be initialized");
+            let repo = repo.as_mut().expect("Repository must be initialized");
             repo.create_branch(name.clone())
                 .context("Failed to create branch")?;
             println!("Created branch: {}", name);
             Ok(())
         }
 
-        Commands::Checkout { branch } => {
-            // let mut repo = Repository::init(".")?;
-            let repo= repo.as_mut().expect("Repository must be initialized");
-            repo.switch_branch(&branch)
+        Commands::Checkout {
+            branch,
+            force,
+            paths,
+        } => {
+            let repo = repo.as_mut().expect("Repository must be initialized");
+            if !paths.is_empty() {
+                repo.restore(branch, paths)
+                    .context("Failed to restore files")?;
+                println!("Updated {} path(s) from {}", paths.len(), branch);
+                return Ok(());
+            }
+            // Anything but a branch name detaches HEAD at that commit
+            if !repo.branches().contains_key(branch) {
+                let commit_id = repo
+                    .detach_head(branch, *force)
+                    .context("Failed to check out revision")?;
+                println!("HEAD is now at {}", &commit_id[..7]);
+                return Ok(());
+            }
+            repo.switch_branch(branch, *force)
                 .context("Failed to switch branch")?;
             println!("Switched to branch: {}", branch);
             Ok(())
         }
 
-        Commands::Diff { branch1, branch2 } => {
-            // let repo = Repository::init(".")?;
-            let repo= repo.as_mut().expect("Repository must be initialized");
+        Commands::Reset {
+            revision,
+            soft,
+            mixed: _,
+            hard,
+        } => {
+            let repo = repo.as_mut().expect("Repository must be initialized");
+
+            let mode = if *soft {
+                ResetMode::Soft
+            } else if *hard {
+                ResetMode::Hard
+            } else {
+                ResetMode::Mixed
+            };
+            let commit_id = repo.reset(revision, mode).context("Failed to reset")?;
+            println!("HEAD is now at {}", &commit_id[..7]);
+            Ok(())
+        }
+
+        Commands::Show { revision, context } => {
+            let repo = repo.as_mut().expect("Repository must be initialized");
+            let (commit, diffs) = repo.show(revision).context("Failed to show commit")?;
 
-            // If no second branch specified, use current branch
-            let branch2 = branch2
-                .clone()
-                .unwrap_or_else(|| repo.current_branch().to_string());
+            let entry = LogEntry {
+                parents: commit.parents().to_vec(),
+                commit,
+            };
+            println!("{}", git::log::render(&[entry], LogFormat::Medium, false));
+            let options = DiffOptions {
+                context: *context,
+                ..DiffOptions::default()
+            };
+            for diff in diffs {
+                print!("{}", git::diff::Differ::render_diff(&diff, &options));
+            }
+            Ok(())
+        }
 
-            let diffs = repo
-                .diff(branch1, &branch2)
-                .context("Failed to generate diff")?;
+        Commands::Diff {
+            from,
+            to,
+            context,
+            diff_algorithm,
+        } => {
+            let repo = repo.as_mut().expect("Repository must be initialized");
 
-            if diffs.is_empty() {
-                println!("No differences found between branches");
-            } else {
-                println!("Differences between {} and {}:", branch1, branch2);
-                for diff in diffs {
-                    println!("{}", git::diff::Differ::render_diff(&diff));
-                }
+            let config = repo.config();
+            let context = match context {
+                Some(context) => *context,
+                None => match config.get("diff.context") {
+                    Some(value) => value.parse().context("Invalid diff.context")?,
+                    None => DiffOptions::default().context,
+                },
+            };
+            let algorithm = diff_algorithm
+                .as_deref()
+                .or(config.get("diff.algorithm"))
+                .map(DiffAlgorithm::parse)
+                .transpose()?
+                .unwrap_or_default();
+            let options = DiffOptions { context, algorithm };
+
+            let from = match from {
+                Some(from) => from.as_str(),
+                None => config.get("init.defaultBranch").unwrap_or("main"),
+            };
+            // A single revision or range is compared with HEAD as needed
+            let diffs = match to {
+                Some(to) => repo.diff(from, to),
+                None => repo.diff_range(from),
+            }
+            .context("Failed to generate diff")?;
+
+            // Printed as a bare patch so it can be piped into `patch` or `git apply`
+            for diff in diffs {
+                print!("{}", git::diff::Differ::render_diff(&diff, &options));
             }
             Ok(())
         }
 
         Commands::Merge {
-            base,
-            source,
-            target,
+            branch,
+            ff,
+            no_ff,
+            ff_only,
         } => {
-            // let mut repo = Repository::init(".")?;
-            let repo= repo.as_mut().expect("Repository must be initialized");
+            let repo = repo.as_mut().expect("Repository must be initialized");
 
-            // If no target specified, use current branch
-            let target = target
-                .clone()
-                .unwrap_or_else(|| repo.current_branch().to_string());
+            // `merge.ff` is a boolean or `only`
+            let configured = match repo.config().get("merge.ff") {
+                Some("only") => FastForwardMode::Only,
+                Some(value) if !parse_bool("merge.ff", value)? => FastForwardMode::Never,
+                _ => FastForwardMode::Allow,
+            };
+            let mode = if *ff {
+                FastForwardMode::Allow
+            } else if *no_ff {
+                FastForwardMode::Never
+            } else if *ff_only {
+                FastForwardMode::Only
+            } else {
+                configured
+            };
 
-            match repo.merge(&base, &source, &target) {
-                Ok(_) => {
+            match repo.merge(branch, mode) {
+                Ok(MergeStatus::UpToDate) => {
+                    println!("Already up to date.");
+                    Ok(())
+                }
+                Ok(MergeStatus::FastForward(commit_id)) => {
+                    println!("Fast-forward {} to {}", repo.current_branch(), commit_id);
+                    Ok(())
+                }
+                Ok(MergeStatus::Merged(commit_id)) => {
                     println!(
-                        "Successfully merged {} and {} into {}",
-                        source, target, base
+                        "Merged {} into {}: {}",
+                        branch,
+                        repo.current_branch(),
+                        commit_id
                     );
                     Ok(())
                 }
                 Err(merge_conflicts) => {
-                    println!("Merge conflicts detected:");
-                    println!("{:?}", merge_conflicts);
-                    Err(anyhow::anyhow!("Merge failed due to conflicts"))
+                    println!("{}", merge_conflicts);
+                    Err(anyhow::anyhow!("Merge failed"))
+                }
+            }
+        }
+
+        Commands::Config(ConfigCommand {
+            global,
+            system,
+            local,
+            command,
+        }) => {
+            let scope = if *global {
+                Some(ConfigScope::Global)
+            } else if *system {
+                Some(ConfigScope::System)
+            } else if *local {
+                Some(ConfigScope::Local)
+            } else {
+                None
+            };
+            let path = |scope: ConfigScope| -> Result<PathBuf> {
+                match scope {
+                    ConfigScope::Global => {
+                        Config::global_path().context("No home directory for --global")
+                    }
+                    ConfigScope::System => Ok(Config::system_path()),
+                    _ => Ok(repo
+                        .as_ref()
+                        .context("Not a git-scm repository")?
+                        .config_path()),
+                }
+            };
+
+            // Reading merges every scope unless one is picked
+            let config = match (scope, repo.as_ref()) {
+                (Some(scope), _) => {
+                    let mut config = Config::default();
+                    config.read_file(&path(scope)?, scope)?;
+                    config
+                }
+                (None, Some(repo)) => repo.config().clone(),
+                (None, None) => Config::load(None)?,
+            };
+
+            // Writing goes to the repository's file by default
+            let write_path = || path(scope.unwrap_or(ConfigScope::Local));
+
+            match command {
+                ConfigCommands::Get { name } => match config.get(name) {
+                    Some(value) => {
+                        println!("{}", value);
+                        Ok(())
+                    }
+                    // A missing setting is reported by the exit code alone
+                    None => std::process::exit(1),
+                },
+                ConfigCommands::Set { name, value } => {
+                    set_value(&write_path()?, name, value)
+                        .with_context(|| format!("Failed to set {}", name))?;
+                    Ok(())
+                }
+                ConfigCommands::Unset { name } => {
+                    if unset_value(&write_path()?, name)? == 0 {
+                        return Err(anyhow::anyhow!("No such setting: {}", name));
+                    }
+                    Ok(())
+                }
+                ConfigCommands::List { show_scope } => {
+                    for entry in config.entries() {
+                        if *show_scope {
+                            print!("{}\t", entry.scope.name());
+                        }
+                        println!("{}={}", entry.key, entry.value);
+                    }
+                    Ok(())
+                }
+            }
+        }
+
+        Commands::Remote(RemoteCommand { verbose, command }) => {
+            let repo = repo.as_mut().expect("Repository must be initialized");
+            match command {
+                Some(RemoteCommands::Add { name, url }) => repo
+                    .add_remote(name, url)
+                    .with_context(|| format!("Failed to add remote {}", name)),
+                Some(RemoteCommands::Remove { name }) => repo
+                    .remove_remote(name)
+                    .with_context(|| format!("Failed to remove remote {}", name)),
+                Some(RemoteCommands::List) | None => {
+                    for remote in repo.remotes()? {
+                        if *verbose {
+                            println!("{}\t{} (fetch)", remote.name, remote.url);
+                            println!("{}\t{} (push)", remote.name, remote.url);
+                        } else {
+                            println!("{}", remote.name);
+                        }
+                    }
+                    Ok(())
+                }
+            }
+        }
+
+        Commands::Fetch { remote } => {
+            let repo = repo.as_mut().expect("Repository must be initialized");
+            let name = remote.clone().unwrap_or_else(|| repo.default_remote());
+            let updates = repo
+                .fetch(&name)
+                .with_context(|| format!("Failed to fetch from {}", name))?;
+
+            // Like Git, stay quiet when nothing changed
+            let changed: Vec<_> = updates
+                .iter()
+                .filter(|update| update.status != RefUpdateStatus::UpToDate)
+                .collect();
+            if !changed.is_empty() {
+                println!("From {}", repo.remote(&name)?.url);
+                for update in &changed {
+                    println!("{}", update.describe());
+                }
+            }
+            if changed
+                .iter()
+                .any(|update| update.status == RefUpdateStatus::Rejected)
+            {
+                return Err(anyhow::anyhow!(
+                    "Some remote-tracking branches were not updated"
+                ));
+            }
+            Ok(())
+        }
+
+        Commands::Push {
+            remote,
+            branch,
+            force,
+        } => {
+            let repo = repo.as_mut().expect("Repository must be initialized");
+            let name = remote.clone().unwrap_or_else(|| repo.default_remote());
+            let branch = match branch {
+                Some(branch) => branch.clone(),
+                None if repo.detached_head().is_some() => {
+                    return Err(anyhow::anyhow!(
+                        "You are not currently on a branch; name the branch to push"
+                    ))
+                }
+                None => repo.current_branch().to_string(),
+            };
+            let update = repo
+                .push(&name, &branch, *force)
+                .with_context(|| format!("Failed to push to {}", name))?;
+
+            match update.status {
+                RefUpdateStatus::UpToDate => println!("Everything up-to-date"),
+                _ => {
+                    println!("To {}", repo.remote(&name)?.url);
+                    println!("{}", update.describe());
                 }
             }
+            if update.status == RefUpdateStatus::Rejected {
+                return Err(anyhow::anyhow!(
+                    "Updates to {} were rejected; fetch and merge the remote's commits first, or use --force",
+                    branch
+                ));
+            }
+            Ok(())
         }
 
-        Commands::Log => {
-            // let repo = Repository::init(".")?;
-            let repo= repo.as_mut().expect("Repository must be initialized");
+        Commands::CheckIgnore {
+            paths,
+            verbose,
+            non_matching,
+        } => {
+            let repo = repo.as_mut().expect("Repository must be initialized");
 
-            // Implement a proper log display
-            println!("Commit History:");
-            for (branch_name, branch) in repo.branches() {
-                println!("Branch: {}", branch_name);
-                for commit_id in branch.commits() {
-                    // TODO: fetch and display more commit details
-                    println!("  Commit: {}", commit_id);
+            let mut any_ignored = false;
+            for path in paths {
+                let pattern = repo
+                    .check_ignore(path)
+                    .with_context(|| format!("Failed to check {}", path.display()))?;
+                let ignored = pattern.as_ref().is_some_and(|p| !p.negated);
+                any_ignored |= ignored;
+
+                match pattern {
+                    Some(pattern) if *verbose => {
+                        println!(
+                            "{}:{}:{}\t{}",
+                            pattern.source.display(),
+                            pattern.line,
+                            pattern.text,
+                            path.display()
+                        );
+                    }
+                    None if *non_matching => println!("::\t{}", path.display()),
+                    _ if ignored => println!("{}", path.display()),
+                    _ => {}
                 }
             }
+
+            // Like Git, exit with 1 when nothing is ignored
+            if !any_ignored {
+                std::process::exit(1);
+            }
+            Ok(())
+        }
+
+        Commands::Repack { all } => {
+            let repo = repo.as_mut().expect("Repository must be initialized");
+            let summary = repo.repack(*all).context("Failed to repack objects")?;
+            print_repack_summary(&summary);
+            Ok(())
+        }
+
+        Commands::Gc => {
+            let repo = repo.as_mut().expect("Repository must be initialized");
+            let summary = repo.repack(true).context("Failed to pack objects")?;
+            print_repack_summary(&summary);
+            Ok(())
+        }
+
+        Commands::Status { porcelain, ignored } => {
+            let repo = repo.as_mut().expect("Repository must be initialized");
+            let entries = repo.status().context("Failed to compute status")?;
+
+            if *porcelain {
+                print!("{}", git::status::render_porcelain(&entries, *ignored));
+            } else {
+                print!(
+                    "{}",
+                    git::status::render_long(
+                        repo.current_branch(),
+                        repo.detached_head(),
+                        &entries,
+                        *ignored
+                    )
+                );
+            }
+            Ok(())
+        }
+
+        Commands::Log {
+            revision,
+            oneline,
+            graph,
+            max_count,
+            author,
+            grep,
+            since,
+            until,
+            paths,
+        } => {
+            let repo = repo.as_mut().expect("Repository must be initialized");
+
+            let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+            let options = LogOptions {
+                max_count: *max_count,
+                author: author.clone(),
+                grep: grep.clone(),
+                since: since.as_deref().map(|d| parse_date(d, now)).transpose()?,
+                until: until.as_deref().map(|d| parse_date(d, now)).transpose()?,
+                paths: paths
+                    .iter()
+                    .map(|path| repo.repo_path(path))
+                    .collect::<std::io::Result<_>>()?,
+            };
+            let format = if *oneline {
+                LogFormat::Oneline
+            } else {
+                LogFormat::Medium
+            };
+
+            let entries = repo
+                .log_entries(revision, &options)
+                .context("Failed to read history")?;
+            print!("{}", git::log::render(&entries, format, *graph));
             Ok(())
         }
     }
@@ -1,7 +1,7 @@
 mod git;
 
-use std::path::Path;
 use git::repository::Repository;
+use std::path::Path;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Initialize a new repository
@@ -12,10 +12,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     repo.add(Path::new("./test_repo/file2.txt"))?;
 
     // Commit changes
-    let commit_id = repo.commit(
-        "initial commit".to_string(), 
-        "Doe <doe@gmail.com>".to_string()
-    )?;
+    let commit_id = repo.commit("initial commit".to_string(), Some("Doe <doe@gmail.com>"))?;
 
     println!("Committed changes with ID: {}", commit_id);
 
@@ -23,4 +20,4 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     repo.log();
 
     Ok(())
-}
\ No newline at end of file
+}
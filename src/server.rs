@@ -0,0 +1,153 @@
+mod git;
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use std::io::{ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+
+use git::http::Message;
+use git::pkt_line;
+use git::repository::Repository;
+use git::service::{self, Service};
+
+/// Largest request body accepted, inflated or not. Pushes bigger than this
+/// are refused rather than held in memory.
+const MAX_REQUEST_BODY: usize = 256 * 1024 * 1024;
+
+/// Serves a repository over Git's smart HTTP protocol, so that `git clone`,
+/// `fetch` and `push` with an `http://` URL reach it. Only meant for
+/// localhost: there is no authentication or TLS.
+#[derive(Parser)]
+#[command(name = "git-scm-server")]
+#[command(about = "Serve a repository over Git's smart HTTP protocol")]
+struct Cli {
+    /// Work tree or bare repository to serve
+    repository: PathBuf,
+
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1")]
+    bind: String,
+
+    /// Port to listen on, any free one when 0
+    #[arg(short, long, default_value_t = 8080)]
+    port: u16,
+
+    /// Print the request line of every request to stderr
+    #[arg(short, long)]
+    verbose: bool,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let repository = std::fs::canonicalize(&cli.repository)
+        .with_context(|| format!("Repository {} does not exist", cli.repository.display()))?;
+    Repository::open_source(&repository).context("Failed to open repository")?;
+
+    let listener = TcpListener::bind((cli.bind.as_str(), cli.port))
+        .with_context(|| format!("Cannot listen on {}:{}", cli.bind, cli.port))?;
+    // The address goes out first and at once, so scripts starting the
+    // server on port 0 can read where it listens
+    println!(
+        "Serving {} at http://{}/",
+        repository.display(),
+        listener.local_addr()?
+    );
+    std::io::stdout().flush()?;
+
+    // One request at a time, so pushes never race each other
+    for stream in listener.incoming() {
+        let result = stream
+            .context("Failed to accept connection")
+            .and_then(|stream| handle(&repository, &stream, cli.verbose));
+        if let Err(error) = result {
+            eprintln!("error: {:#}", error);
+        }
+    }
+    Ok(())
+}
+
+fn handle(repository: &Path, stream: &TcpStream, verbose: bool) -> Result<()> {
+    let request = match Message::read(stream, false, Some(MAX_REQUEST_BODY)) {
+        Ok(request) => request,
+        Err(error) if error.kind() == ErrorKind::FileTooLarge => {
+            let response = Message {
+                start: "HTTP/1.1 413 Content Too Large".to_string(),
+                headers: Vec::new(),
+                body: format!("{}\n", error).into_bytes(),
+            };
+            response.write(stream)?;
+            return Ok(());
+        }
+        Err(error) => return Err(error.into()),
+    };
+    if verbose {
+        eprintln!("{}", request.start);
+    }
+
+    let mut parts = request.start.split(' ');
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or_default();
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let service = query
+        .split('&')
+        .find_map(|parameter| parameter.strip_prefix("service="))
+        .and_then(Service::parse);
+
+    // Requests for the repository itself may come with any path in front
+    let response = match (method, path.rsplit('/').next().unwrap_or_default(), service) {
+        ("GET", "refs", Some(service)) if path.ends_with("/info/refs") => {
+            let repo = Repository::open_source(repository)?;
+            respond(
+                &format!("application/x-{}-advertisement", service.name()),
+                service::advertise(&repo, service),
+            )
+        }
+        ("POST", "git-upload-pack", None) => {
+            let repo = Repository::open_source(repository)?;
+            match service::upload_pack(&repo, &request.body) {
+                Ok(body) => respond("application/x-git-upload-pack-result", Ok(body)),
+                // Protocol version 2 reports errors in an `ERR` packet
+                Err(error) => {
+                    let mut body = Vec::new();
+                    pkt_line::write_line(&mut body, &format!("ERR {}", error));
+                    respond("application/x-git-upload-pack-result", Ok(body))
+                }
+            }
+        }
+        ("POST", "git-receive-pack", None) => {
+            let mut repo = Repository::open_source(repository)?;
+            respond(
+                "application/x-git-receive-pack-result",
+                service::receive_pack(&mut repo, &request.body),
+            )
+        }
+        _ => Message {
+            start: "HTTP/1.1 404 Not Found".to_string(),
+            headers: Vec::new(),
+            body: b"Not found\n".to_vec(),
+        },
+    };
+    response.write(stream)?;
+    Ok(())
+}
+
+/// A `200 OK` response with `body`, or `500` with the error that prevented
+/// it.
+fn respond(content_type: &str, body: std::io::Result<Vec<u8>>) -> Message {
+    match body {
+        Ok(body) => Message {
+            start: "HTTP/1.1 200 OK".to_string(),
+            headers: vec![
+                ("Content-Type".to_string(), content_type.to_string()),
+                ("Cache-Control".to_string(), "no-cache".to_string()),
+            ],
+            body,
+        },
+        Err(error) => Message {
+            start: "HTTP/1.1 500 Internal Server Error".to_string(),
+            headers: Vec::new(),
+            body: format!("{}\n", error).into_bytes(),
+        },
+    }
+}
@@ -0,0 +1,81 @@
+//! Branch switches driven through the `git` binary.
+
+mod common;
+
+use common::Sandbox;
+
+/// A repository whose branches `main` and `topic` hold different contents
+/// of `f`, with `main` checked out.
+fn two_branches(sandbox: &Sandbox) {
+    sandbox.git(".", &["init", "repo"]);
+    sandbox.write("repo/f", "main\n");
+    sandbox.git("repo", &["add", "f"]);
+    sandbox.git("repo", &["commit", "message", "-m", "Add f"]);
+
+    sandbox.git("repo", &["branch", "topic"]);
+    sandbox.git("repo", &["checkout", "topic"]);
+    sandbox.write("repo/f", "topic\n");
+    sandbox.write("repo/g", "topic only\n");
+    sandbox.git("repo", &["add", "f"]);
+    sandbox.git("repo", &["add", "g"]);
+    sandbox.git("repo", &["commit", "message", "-m", "Change f, add g"]);
+    sandbox.git("repo", &["checkout", "main"]);
+}
+
+#[test]
+fn switches_the_working_tree_between_branches() {
+    let sandbox = Sandbox::new("checkout-switch");
+    two_branches(&sandbox);
+    assert_eq!(sandbox.read("repo/f"), "main\n");
+    assert!(!sandbox.root.join("repo/g").exists());
+
+    sandbox.git("repo", &["checkout", "topic"]);
+    assert_eq!(sandbox.read("repo/f"), "topic\n");
+    assert_eq!(sandbox.read("repo/g"), "topic only\n");
+    assert_eq!(sandbox.git("repo", &["status", "--porcelain"]), "");
+}
+
+#[test]
+fn refuses_to_overwrite_local_changes() {
+    let sandbox = Sandbox::new("checkout-refuse");
+    two_branches(&sandbox);
+
+    // An unstaged edit to a file the branches disagree on
+    sandbox.write("repo/f", "edited\n");
+    let error = sandbox.git_fails("repo", &["checkout", "topic"]);
+    assert!(error.contains("would be overwritten"), "{}", error);
+    assert!(error.contains("\tf\n"), "{}", error);
+    assert_eq!(sandbox.read("repo/f"), "edited\n");
+    assert!(!sandbox.root.join("repo/g").exists());
+    assert!(sandbox
+        .git("repo", &["status"])
+        .starts_with("On branch main\n"));
+    assert_eq!(sandbox.git("repo", &["status", "--porcelain"]), " M f\n");
+
+    // A staged one is just as much at risk
+    sandbox.git("repo", &["add", "f"]);
+    let error = sandbox.git_fails("repo", &["checkout", "topic"]);
+    assert!(error.contains("would be overwritten"), "{}", error);
+    assert_eq!(sandbox.read("repo/f"), "edited\n");
+
+    // So is an untracked file the other branch would replace
+    sandbox.git("repo", &["checkout", "--force", "main"]);
+    sandbox.write("repo/g", "mine\n");
+    let error = sandbox.git_fails("repo", &["checkout", "topic"]);
+    assert!(error.contains("\tg\n"), "{}", error);
+    assert_eq!(sandbox.read("repo/g"), "mine\n");
+}
+
+#[test]
+fn force_discards_local_changes() {
+    let sandbox = Sandbox::new("checkout-force");
+    two_branches(&sandbox);
+
+    sandbox.write("repo/f", "edited\n");
+    sandbox.git("repo", &["checkout", "--force", "topic"]);
+    assert_eq!(sandbox.read("repo/f"), "topic\n");
+    assert!(sandbox
+        .git("repo", &["status"])
+        .starts_with("On branch topic\n"));
+    assert_eq!(sandbox.git("repo", &["status", "--porcelain"]), "");
+}
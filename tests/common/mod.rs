@@ -0,0 +1,160 @@
+//! A scratch directory to drive the `git` and `server` binaries in, the way
+//! a user would.
+
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::{Child, Command, Output, Stdio};
+
+/// A scratch directory and the server running in it, both cleaned up when
+/// the test ends, whether it passes or not.
+pub struct Sandbox {
+    pub root: PathBuf,
+    server: Option<Child>,
+}
+
+impl Sandbox {
+    pub fn new(name: &str) -> Self {
+        let root = std::env::temp_dir().join(format!("git-scm-{}-{}", name, std::process::id()));
+        if root.exists() {
+            fs::remove_dir_all(&root).unwrap();
+        }
+        fs::create_dir_all(&root).unwrap();
+        Sandbox { root, server: None }
+    }
+
+    /// Runs the `git` binary in `dir`, below the sandbox, with no identity
+    /// configured.
+    pub fn run(&self, dir: &str, args: &[&str]) -> Output {
+        self.command(dir, args).output().unwrap()
+    }
+
+    fn command(&self, dir: &str, args: &[&str]) -> Command {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_git"));
+        command
+            .args(args)
+            .current_dir(self.root.join(dir))
+            // Keep the user's own configuration out of the test
+            .env("HOME", &self.root)
+            .env("XDG_CONFIG_HOME", &self.root)
+            .env("GIT_CONFIG_NOSYSTEM", "1");
+        for variable in [
+            "GIT_DIR",
+            "GIT_WORK_TREE",
+            "GIT_CONFIG_GLOBAL",
+            "GIT_CONFIG_PARAMETERS",
+            "GIT_AUTHOR_NAME",
+            "GIT_AUTHOR_EMAIL",
+            "GIT_AUTHOR_DATE",
+            "GIT_COMMITTER_NAME",
+            "GIT_COMMITTER_EMAIL",
+            "GIT_COMMITTER_DATE",
+            "EMAIL",
+        ] {
+            command.env_remove(variable);
+        }
+        command
+    }
+
+    /// Runs the `git` binary in `dir` as a configured user and returns what
+    /// it printed, failing the test if it fails.
+    pub fn git(&self, dir: &str, args: &[&str]) -> String {
+        let mut full = vec!["-c", "user.name=Test", "-c", "user.email=test@example.com"];
+        full.extend_from_slice(args);
+        let output = self.run(dir, &full);
+        assert!(
+            output.status.success(),
+            "git {} failed:\n{}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    /// Like [`Sandbox::git`], with `date` as the author and committer date of
+    /// any commit it makes.
+    pub fn git_at(&self, dir: &str, date: &str, args: &[&str]) -> String {
+        let mut full = vec!["-c", "user.name=Test", "-c", "user.email=test@example.com"];
+        full.extend_from_slice(args);
+        let output = self
+            .command(dir, &full)
+            .env("GIT_AUTHOR_DATE", date)
+            .env("GIT_COMMITTER_DATE", date)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "git {} failed:\n{}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        );
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    /// Runs the `git` binary in `dir` as a configured user and returns the
+    /// error it reported, failing the test if it succeeds.
+    pub fn git_fails(&self, dir: &str, args: &[&str]) -> String {
+        let mut full = vec!["-c", "user.name=Test", "-c", "user.email=test@example.com"];
+        full.extend_from_slice(args);
+        let output = self.run(dir, &full);
+        assert!(
+            !output.status.success(),
+            "git {} succeeded:\n{}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stdout)
+        );
+        String::from_utf8(output.stderr).unwrap()
+    }
+
+    /// Starts serving `repository` on a free port and returns its URL.
+    pub fn serve(&mut self, repository: &str) -> String {
+        let mut server = Command::new(env!("CARGO_BIN_EXE_server"))
+            .arg(self.root.join(repository))
+            .args(["--port", "0"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        // `Serving <path> at http://<address>/` comes first
+        let mut line = String::new();
+        BufReader::new(server.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        self.server = Some(server);
+        let url = line.trim().rsplit(' ').next().unwrap();
+        assert!(url.starts_with("http://"), "unexpected output: {}", line);
+        url.trim_end_matches('/').to_string()
+    }
+
+    pub fn write(&self, path: &str, content: &str) {
+        let path = self.root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    pub fn read(&self, path: &str) -> String {
+        fs::read_to_string(self.root.join(path)).unwrap()
+    }
+
+    /// The abbreviated id `revision` resolves to in `dir`.
+    pub fn head(&self, dir: &str, revision: &str) -> String {
+        self.git(dir, &["log", "--oneline", "-n", "1", revision])
+            .split(' ')
+            .next()
+            .unwrap()
+            .to_string()
+    }
+}
+
+impl Drop for Sandbox {
+    fn drop(&mut self) {
+        if let Some(server) = &mut self.server {
+            let _ = server.kill();
+            let _ = server.wait();
+        }
+        let _ = fs::remove_dir_all(&self.root);
+    }
+}
@@ -0,0 +1,65 @@
+//! Clones, pushes to and fetches from a repository through a `file://`
+//! URL, driving the `git` binary the way a user would.
+
+mod common;
+
+use common::Sandbox;
+
+#[test]
+fn clone_push_and_fetch_over_file_urls() {
+    let sandbox = Sandbox::new("file");
+
+    // A bare repository with one commit, pushed from a local seed
+    sandbox.git(".", &["init", "--bare", "shared.git"]);
+    let url = format!("file://{}", sandbox.root.join("shared.git").display());
+    sandbox.git(".", &["init", "seed"]);
+    sandbox.write("seed/README", "hello\n");
+    sandbox.git("seed", &["add", "README"]);
+    sandbox.git("seed", &["commit", "message", "-m", "Initial commit"]);
+    sandbox.git("seed", &["remote", "add", "origin", &url]);
+    sandbox.git("seed", &["push", "origin", "main"]);
+
+    // Two clones of the shared repository
+    sandbox.git(".", &["clone", &url, "alice"]);
+    sandbox.git(".", &["clone", &url, "bob"]);
+    assert_eq!(sandbox.read("alice/README"), "hello\n");
+    assert_eq!(sandbox.read("bob/README"), "hello\n");
+
+    // Alice pushes a commit
+    sandbox.write("alice/notes.txt", "from alice\n");
+    sandbox.git("alice", &["add", "notes.txt"]);
+    sandbox.git("alice", &["commit", "message", "-m", "Add notes"]);
+    let pushed = sandbox.git("alice", &["push"]);
+    assert!(pushed.contains("main -> main"), "{}", pushed);
+    let alice_head = sandbox.head("alice", "HEAD");
+    assert_eq!(sandbox.head("shared.git", "main"), alice_head);
+
+    // Bob commits without fetching first, so his push is not a fast-forward
+    sandbox.write("bob/todo.txt", "from bob\n");
+    sandbox.git("bob", &["add", "todo.txt"]);
+    sandbox.git("bob", &["commit", "message", "-m", "Add todo"]);
+    let rejected = sandbox.run("bob", &["push"]);
+    assert!(!rejected.status.success());
+    let output = String::from_utf8_lossy(&rejected.stdout);
+    assert!(output.contains("[rejected]"), "{}", output);
+    assert!(output.contains("(non-fast-forward)"), "{}", output);
+    assert_eq!(sandbox.head("shared.git", "main"), alice_head);
+
+    // Once he has fetched and merged Alice's commit the push goes through
+    let fetched = sandbox.git("bob", &["fetch"]);
+    assert!(fetched.contains("main -> origin/main"), "{}", fetched);
+    assert_eq!(sandbox.head("bob", "origin/main"), alice_head);
+    sandbox.git("bob", &["merge", "origin/main"]);
+    assert_eq!(sandbox.read("bob/notes.txt"), "from alice\n");
+    sandbox.git("bob", &["push"]);
+    assert_eq!(
+        sandbox.head("shared.git", "main"),
+        sandbox.head("bob", "HEAD")
+    );
+
+    // Alice fetches Bob's work, and fetching again reports nothing new
+    sandbox.git("alice", &["fetch"]);
+    sandbox.git("alice", &["merge", "origin/main"]);
+    assert_eq!(sandbox.read("alice/todo.txt"), "from bob\n");
+    assert_eq!(sandbox.git("alice", &["fetch"]), "");
+}
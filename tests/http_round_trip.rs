@@ -0,0 +1,128 @@
+//! Clones, pushes to and fetches from a repository served by the `server`
+//! binary, driving both binaries the way a user would.
+
+mod common;
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+use sha1::{Digest, Sha1};
+
+use common::Sandbox;
+
+#[test]
+fn clone_push_and_fetch_over_http() {
+    let mut sandbox = Sandbox::new("http");
+
+    // A bare repository with one commit, pushed from a local seed
+    sandbox.git(".", &["init", "--bare", "served.git"]);
+    sandbox.git(".", &["init", "seed"]);
+    sandbox.write("seed/README", "hello\n");
+    sandbox.git("seed", &["add", "README"]);
+    sandbox.git("seed", &["commit", "message", "-m", "Initial commit"]);
+    sandbox.git("seed", &["remote", "add", "origin", "../served.git"]);
+    sandbox.git("seed", &["push", "origin", "main"]);
+
+    let url = sandbox.serve("served.git");
+
+    // Two clones of the served repository
+    sandbox.git(".", &["clone", &url, "alice"]);
+    sandbox.git(".", &["clone", &url, "bob"]);
+    assert_eq!(sandbox.read("alice/README"), "hello\n");
+    assert_eq!(sandbox.read("bob/README"), "hello\n");
+
+    // Alice pushes a commit
+    sandbox.write("alice/notes.txt", "from alice\n");
+    sandbox.git("alice", &["add", "notes.txt"]);
+    sandbox.git("alice", &["commit", "message", "-m", "Add notes"]);
+    let pushed = sandbox.git("alice", &["push"]);
+    assert!(pushed.contains("main -> main"), "{}", pushed);
+    let alice_head = sandbox.head("alice", "HEAD");
+
+    // Bob fetches it and fast-forwards onto it
+    let fetched = sandbox.git("bob", &["fetch"]);
+    assert!(fetched.contains("main -> origin/main"), "{}", fetched);
+    assert_eq!(sandbox.head("bob", "origin/main"), alice_head);
+    sandbox.git("bob", &["merge", "origin/main"]);
+    assert_eq!(sandbox.read("bob/notes.txt"), "from alice\n");
+
+    // Fetching again has nothing new to report
+    assert_eq!(sandbox.git("bob", &["fetch"]), "");
+}
+
+#[test]
+fn refuses_oversized_requests() {
+    let mut sandbox = Sandbox::new("http-oversized");
+    sandbox.git(".", &["init", "--bare", "served.git"]);
+    let url = sandbox.serve("served.git");
+    let address = url.trim_start_matches("http://").split('/').next().unwrap();
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(
+        stream,
+        "POST /git-receive-pack HTTP/1.1\r\nContent-Length: 1000000000000\r\n\r\n"
+    )
+    .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 413 "), "{}", response);
+
+    // The server keeps serving afterwards
+    sandbox.git(".", &["clone", &url, "clone"]);
+}
+
+/// Sends a protocol version 2 `fetch` for `wants` and returns the response.
+fn fetch_request(url: &str, wants: &[&str]) -> String {
+    let packet = |line: &str| format!("{:04x}{}", line.len() + 4, line);
+    let mut body = packet("command=fetch\n") + "0001";
+    for id in wants {
+        body += &packet(&format!("want {}\n", id));
+    }
+    body += &packet("done\n");
+    body += "0000";
+
+    let address = url.trim_start_matches("http://").split('/').next().unwrap();
+    let mut stream = TcpStream::connect(address).unwrap();
+    write!(
+        stream,
+        "POST /git-upload-pack HTTP/1.1\r\nGit-Protocol: version=2\r\nContent-Length: {}\r\n\r\n{}",
+        body.len(),
+        body
+    )
+    .unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+    String::from_utf8_lossy(&response).to_string()
+}
+
+#[test]
+fn only_sends_objects_the_refs_reach() {
+    let mut sandbox = Sandbox::new("http-wants");
+    sandbox.git(".", &["init", "served"]);
+    sandbox.write("served/README", "hello\n");
+    sandbox.git("served", &["add", "README"]);
+    sandbox.git("served", &["commit", "message", "-m", "Initial commit"]);
+    sandbox.write("served/README", "hello again\n");
+    sandbox.git("served", &["add", "README"]);
+    sandbox.git("served", &["commit", "message", "-m", "Second commit"]);
+
+    // Staged but never committed, so no ref reaches it
+    let secret = "not for anyone else\n";
+    sandbox.write("served/secret", secret);
+    sandbox.git("served", &["add", "secret"]);
+    let framed = format!("blob {}\0{}", secret.len(), secret);
+    let secret_id: String = Sha1::digest(framed.as_bytes())
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    let url = sandbox.serve("served");
+    let response = fetch_request(&url, &[&secret_id]);
+    assert!(response.contains("ERR not our ref"), "{}", response);
+
+    // History below a ref can still be asked for directly
+    let log = sandbox.git("served", &["log", "-n", "1", "HEAD~1"]);
+    let first = log.split_whitespace().nth(1).unwrap();
+    let response = fetch_request(&url, &[first]);
+    assert!(response.contains("packfile"), "{}", response);
+}
@@ -0,0 +1,59 @@
+//! History shown by the `git` binary's `log`.
+
+mod common;
+
+use common::Sandbox;
+
+/// Commits `path` with `content` at `date`.
+fn commit(sandbox: &Sandbox, date: &str, path: &str, content: &str, message: &str) {
+    sandbox.write(&format!("repo/{}", path), content);
+    sandbox.git("repo", &["add", path]);
+    sandbox.git_at("repo", date, &["commit", "message", "-m", message]);
+}
+
+/// The subjects `log --oneline` lists for `args`, in order.
+fn subjects(sandbox: &Sandbox, args: &[&str]) -> Vec<String> {
+    let mut full = vec!["log", "--oneline"];
+    full.extend_from_slice(args);
+    sandbox
+        .git("repo", &full)
+        .lines()
+        .map(|line| line.split_once(' ').unwrap().1.to_string())
+        .collect()
+}
+
+#[test]
+fn lists_merged_history_newest_first_after_children() {
+    let sandbox = Sandbox::new("log-order");
+    sandbox.git(".", &["init", "repo"]);
+    commit(&sandbox, "@1000 +0000", "a", "a\n", "Root");
+    sandbox.git("repo", &["branch", "topic"]);
+
+    sandbox.git("repo", &["checkout", "topic"]);
+    commit(&sandbox, "@2000 +0000", "t", "1\n", "Topic one");
+    sandbox.git("repo", &["checkout", "main"]);
+    commit(&sandbox, "@3000 +0000", "m", "1\n", "Main one");
+    sandbox.git("repo", &["checkout", "topic"]);
+    // A clock running behind must not put a commit after its parent
+    commit(&sandbox, "@1500 +0000", "t", "2\n", "Topic two");
+    sandbox.git("repo", &["checkout", "main"]);
+    sandbox.git_at("repo", "@4000 +0000", &["merge", "topic"]);
+
+    let history = subjects(&sandbox, &[]);
+    assert_eq!(
+        history,
+        [
+            "Merge branch 'topic' into main",
+            "Main one",
+            "Topic two",
+            "Topic one",
+            "Root"
+        ]
+    );
+
+    assert_eq!(subjects(&sandbox, &["-n", "2"]), history[..2]);
+    assert_eq!(
+        subjects(&sandbox, &["topic"]),
+        ["Topic two", "Topic one", "Root"]
+    );
+}
@@ -0,0 +1,52 @@
+//! Merges driven through the `git` binary.
+
+mod common;
+
+use common::Sandbox;
+
+/// Two branches that changed different files, ready to be merged with a
+/// merge commit.
+fn diverged(sandbox: &Sandbox) {
+    sandbox.git(".", &["init", "repo"]);
+    sandbox.write("repo/f", "one\n");
+    sandbox.git("repo", &["add", "f"]);
+    sandbox.git("repo", &["commit", "message", "-m", "Add f"]);
+
+    sandbox.git("repo", &["branch", "topic"]);
+    sandbox.git("repo", &["checkout", "topic"]);
+    sandbox.write("repo/f", "two\n");
+    sandbox.write("repo/g", "new\n");
+    sandbox.git("repo", &["add", "f"]);
+    sandbox.git("repo", &["add", "g"]);
+    sandbox.git("repo", &["commit", "message", "-m", "Change f, add g"]);
+
+    sandbox.git("repo", &["checkout", "main"]);
+    sandbox.write("repo/h", "main\n");
+    sandbox.git("repo", &["add", "h"]);
+    sandbox.git("repo", &["commit", "message", "-m", "Add h"]);
+}
+
+#[test]
+fn a_failed_merge_leaves_the_repository_unchanged() {
+    let sandbox = Sandbox::new("failed-merge");
+    diverged(&sandbox);
+    let head = sandbox.head("repo", "HEAD");
+
+    // Without an identity the merge commit cannot be written
+    let output = sandbox.run("repo", &["merge", "topic"]);
+    assert!(!output.status.success());
+    let error = String::from_utf8_lossy(&output.stdout);
+    assert!(error.contains("Author identity unknown"), "{}", error);
+
+    assert_eq!(sandbox.git("repo", &["status", "--porcelain"]), "");
+    assert_eq!(sandbox.read("repo/f"), "one\n");
+    assert!(!sandbox.root.join("repo/g").exists());
+    assert!(!sandbox.root.join("repo/.git/MERGE_HEAD").exists());
+    assert_eq!(sandbox.head("repo", "HEAD"), head);
+
+    // Once an identity is set the same merge goes through
+    sandbox.git("repo", &["merge", "topic"]);
+    assert_eq!(sandbox.read("repo/f"), "two\n");
+    assert_eq!(sandbox.read("repo/g"), "new\n");
+    assert_eq!(sandbox.git("repo", &["status", "--porcelain"]), "");
+}